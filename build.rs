@@ -9,8 +9,8 @@ use std::fs;
 use std::path::PathBuf;
 use std::process::Command;
 use test_generator::{
-    build_ignores_from_textfile, test_directory, test_directory_module, wasi_processor,
-    wast_processor, with_features, with_test_module, Testsuite,
+    build_ignores_from_textfile, generated_header, test_directory, test_directory_module,
+    wasi_processor, wast_processor, with_features, with_test_module, write_inventory, Testsuite,
 };
 
 fn main() -> anyhow::Result<()> {
@@ -31,9 +31,10 @@ fn main() -> anyhow::Result<()> {
     // Spectests test generation
     {
         let mut spectests = Testsuite {
-            buffer: String::new(),
+            buffer: generated_header(&compilers),
             path: vec![],
             ignores: ignores.clone(),
+            generated: vec![],
         };
 
         with_features(&mut spectests, &compilers, |mut spectests| {
@@ -45,15 +46,40 @@ fn main() -> anyhow::Result<()> {
                     wast_processor,
                 )?;
                 test_directory_module(spectests, "tests/wast/spec/proposals/simd", wast_processor)?;
+                if cfg!(feature = "test-reference-types") {
+                    test_directory_module(
+                        spectests,
+                        "tests/wast/spec/proposals/reference-types",
+                        wast_processor,
+                    )?;
+                }
                 // test_directory_module(spectests, "tests/wast/spec/proposals/bulk-memory-operations", wast_processor)?;
                 Ok(())
             })?;
             with_test_module(&mut spectests, "wasmer", |spectests| {
                 let _spec_tests = test_directory(spectests, "tests/wast/wasmer", wast_processor)?;
+                if cfg!(feature = "test-reference-types") {
+                    test_directory_module(
+                        spectests,
+                        "tests/wast/wasmer/reference-types",
+                        wast_processor,
+                    )?;
+                }
                 Ok(())
             })?;
             Ok(())
         })?;
+        let mut spectest_dirs = vec![
+            "tests/wast/spec",
+            "tests/wast/spec/proposals/multi-value",
+            "tests/wast/spec/proposals/simd",
+            "tests/wast/wasmer",
+        ];
+        if cfg!(feature = "test-reference-types") {
+            spectest_dirs.push("tests/wast/spec/proposals/reference-types");
+            spectest_dirs.push("tests/wast/wasmer/reference-types");
+        }
+        write_inventory(&mut spectests, &spectest_dirs, compilers.len())?;
 
         let spectests_output = out_dir.join("generated_spectests.rs");
         fs::write(&spectests_output, spectests.buffer)?;
@@ -68,9 +94,10 @@ fn main() -> anyhow::Result<()> {
     // Wasitest test generation
     {
         let mut wasitests = Testsuite {
-            buffer: String::new(),
+            buffer: generated_header(&compilers),
             path: vec![],
             ignores,
+            generated: vec![],
         };
         let wasi_versions = ["unstable", "snapshot1"];
         with_features(&mut wasitests, &compilers, |mut wasitests| {
@@ -322,7 +322,7 @@ pub use wasmer_types::{
 };
 
 // TODO: should those be moved into wasmer::vm as well?
-pub use wasmer_vm::{raise_user_trap, MemoryError, VMExport};
+pub use wasmer_vm::{raise_user_trap, MemoryError, TrapCode, VMExport};
 pub mod vm {
     //! The vm module re-exports wasmer-vm types.
 
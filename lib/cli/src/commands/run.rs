@@ -1,23 +1,40 @@
 use crate::common::get_cache_dir;
-#[cfg(feature = "debug")]
+use crate::error::{runtime_error, ColorChoice, PrettyError};
 use crate::logging;
 use crate::store::{CompilerType, EngineType, StoreOptions};
 use crate::suggestions::suggest_function_exports;
+use crate::utils::{parse_memory_size, parse_preload};
 use crate::warning;
-use anyhow::{anyhow, Context, Result};
-use std::path::PathBuf;
+use anyhow::{anyhow, Context, Error, Result};
+use std::num::NonZeroUsize;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
+use std::time::{Duration, Instant, SystemTime};
 use wasmer::*;
 #[cfg(feature = "cache")]
 use wasmer_cache::{Cache, FileSystemCache, Hash};
 
 use clap::Clap;
 
+#[cfg(feature = "wasi")]
+mod coredump;
+#[cfg(feature = "emscripten")]
+mod emscripten;
+#[cfg(feature = "wasi")]
+mod interrupt;
+mod memory_limit;
+mod preload;
+#[cfg(all(feature = "wasi", unix))]
+mod sampler;
 #[cfg(feature = "wasi")]
 mod wasi;
 
+use memory_limit::MemoryLimit;
+
+#[cfg(feature = "emscripten")]
+pub use emscripten::Emscripten;
 #[cfg(feature = "wasi")]
-use wasi::Wasi;
+pub use wasi::{Wasi, WasiBuilder};
 
 #[derive(Debug, Clap, Clone)]
 /// The options for the `wasmer run` subcommand
@@ -26,6 +43,17 @@ pub struct Run {
     #[clap(long = "disable-cache")]
     disable_cache: bool,
 
+    /// Keep the compiled modules in this directory instead of the default
+    /// cache, and reuse them on later runs with the same compiler, engine
+    /// and Wasm features. Modules of any size are cached there
+    #[clap(
+        long = "cache-dir",
+        name = "CACHE_DIR",
+        parse(from_os_str),
+        conflicts_with = "disable-cache"
+    )]
+    cache_dir: Option<PathBuf>,
+
     /// File to run
     #[clap(name = "FILE", parse(from_os_str))]
     path: PathBuf,
@@ -34,18 +62,139 @@ pub struct Run {
     #[clap(long = "invoke", short = 'i')]
     invoke: Option<String>,
 
+    /// Call the function given to `--invoke` N times on the same instance,
+    /// printing the time each call takes. The memory, globals and tables
+    /// of the instance carry over from one call to the next
+    #[clap(long = "repeat", name = "N", requires = "invoke")]
+    repeat: Option<NonZeroUsize>,
+
+    /// Pass the arguments of the function given to `--invoke` as a JSON
+    /// array instead, e.g. `[1, 2.5, "0x10"]`. Numbers and strings are
+    /// converted to the parameter types, and hexadecimal strings give the
+    /// bits of the value
+    #[clap(long = "entry-args-json", name = "JSON", requires = "invoke")]
+    entry_args_json: Option<String>,
+
+    /// Invoke, in alphabetical order, every exported function whose name
+    /// starts with the given prefix
+    #[clap(long = "invoke-all", name = "PREFIX", conflicts_with = "invoke")]
+    invoke_all: Option<String>,
+
+    /// Compile and instantiate the module, and check that it exports an
+    /// entry point, without running it
+    #[clap(long = "dry-run", conflicts_with_all = &["invoke", "PREFIX"])]
+    dry_run: bool,
+
+    /// Compile the module and write the serialized artifact to the given
+    /// file instead of running it. The artifact can then be run with
+    /// `wasmer run` in place of the Wasm file
+    #[clap(
+        long = "precompile",
+        name = "OUT",
+        parse(from_os_str),
+        conflicts_with_all = &["invoke", "PREFIX", "dry-run"]
+    )]
+    precompile: Option<PathBuf>,
+
+    /// Set the first argument (`argv[0]`) seen by the Wasm program, which
+    /// defaults to the name of the file being run
+    #[clap(long = "argv0", name = "NAME", conflicts_with = "command-name")]
+    argv0: Option<String>,
+
     /// The command name is a string that will override the first argument passed
     /// to the wasm program. This is used in wapm to provide nicer output in
     /// help commands and error messages of the running wasm program
     #[clap(long = "command-name", hidden = true)]
     command_name: Option<String>,
 
+    /// Set the first argument (`argv[0]`) seen by the Wasm program to the
+    /// name of the file being run without its extension, e.g. `ls` for
+    /// `bin/ls.wasm`, for programs which behave according to their name
+    #[clap(long = "argv0-from-basename", conflicts_with_all = &["NAME", "command-name"])]
+    argv0_from_basename: bool,
+
+    /// Pass each line of the given file as an argument to the Wasm
+    /// program, after the arguments given on the command line
+    #[clap(long = "argv-from-file", name = "ARGS_FILE", parse(from_os_str))]
+    argv_from_file: Option<PathBuf>,
+
     /// A prehashed string, used to speed up start times by avoiding hashing the
     /// wasm module. If the specified hash is not found, Wasmer will hash the module
     /// as if no `cache-key` argument was passed.
     #[clap(long = "cache-key", hidden = true)]
     cache_key: Option<String>,
 
+    /// The ABI to run the module with, either `wasi` or `emscripten`.
+    /// Required when the module imports from both
+    #[clap(long = "abi", name = "ABI", possible_values = &["wasi", "emscripten"])]
+    abi: Option<Abi>,
+
+    /// When to print errors and warnings in color: `auto` does unless
+    /// stderr isn't a terminal or `NO_COLOR` is set
+    #[clap(
+        long = "color",
+        name = "WHEN",
+        possible_values = &["auto", "always", "never"],
+        default_value = "auto"
+    )]
+    color: ColorChoice,
+
+    /// Run the module again each time its file changes, until interrupted
+    #[clap(long = "watch", conflicts_with_all = &["OUT", "dry-run"])]
+    watch: bool,
+
+    /// Don't print Wasmer's own status messages and warnings, so only the
+    /// module's output and the reports asked for by other options remain
+    #[clap(long = "quiet", short = 'q')]
+    quiet: bool,
+
+    /// Cap the size the Wasm module's memory may grow to, whatever maximum
+    /// the module declares, given as a number of 64 KiB pages or as a size
+    /// such as `64MiB`. Growing the memory past it fails
+    #[clap(long = "max-memory", name = "SIZE", parse(try_from_str = parse_memory_size))]
+    max_memory: Option<Pages>,
+
+    /// Instantiate another Wasm module first and provide its exports to
+    /// the module being run under the `NAME` import namespace. Preloaded
+    /// modules are instantiated in the order they are given, and each one
+    /// may import from the ones before it
+    #[clap(long = "preload", name = "NAME=MODULE", multiple = true, parse(try_from_str = parse_preload))]
+    preloads: Vec<(String, PathBuf)>,
+
+    /// Write the contents of the module's memory to the given file once
+    /// `_start` or the function given to `--invoke` returns or traps. The
+    /// memory exported as `memory` is written, or else the first exported
+    /// one
+    #[clap(long = "memory-dump", name = "DUMP", parse(from_os_str))]
+    memory_dump: Option<PathBuf>,
+
+    /// Copy the contents of the given file into the module's memory, at the
+    /// offset given to `--input-addr`, before `_start` or the function given
+    /// to `--invoke` runs. The memory is found like for `--memory-dump`, and
+    /// grown if the file doesn't fit
+    #[clap(
+        long = "input",
+        name = "INPUT",
+        parse(from_os_str),
+        requires = "INPUT_ADDR"
+    )]
+    input: Option<PathBuf>,
+
+    /// The offset in memory where `--input` copies the file
+    #[clap(long = "input-addr", name = "INPUT_ADDR", requires = "INPUT")]
+    input_addr: Option<u32>,
+
+    /// Write the size of the `--input` file in bytes, as a little-endian
+    /// 32-bit integer, at the given offset in memory
+    #[clap(long = "input-len-addr", name = "INPUT_LEN_ADDR", requires = "INPUT")]
+    input_len_addr: Option<u32>,
+
+    /// Exit with the given code when the module traps, to tell traps apart
+    /// from the module exiting with a non-zero code of its own. Other
+    /// errors still exit with 1
+    #[clap(long = "exit-on-trap-code", name = "CODE")]
+    exit_on_trap_code: Option<u8>,
+
     #[clap(flatten)]
     store: StoreOptions,
 
@@ -54,6 +203,10 @@ pub struct Run {
     #[clap(flatten)]
     wasi: Wasi,
 
+    #[cfg(feature = "emscripten")]
+    #[clap(flatten)]
+    emscripten: Emscripten,
+
     /// Enable non-standard experimental IO devices
     #[cfg(feature = "io-devices")]
     #[clap(long = "enable-io-devices")]
@@ -64,19 +217,50 @@ pub struct Run {
     #[clap(long = "debug", short = 'd')]
     debug: bool,
 
+    /// Log what Wasmer does up to the given level to stderr, e.g. how long
+    /// compiling, instantiating and running the module take. Defaults to
+    /// the level `RUST_LOG` is set to, if any
+    #[clap(
+        long = "log-level",
+        name = "LEVEL",
+        possible_values = &["off", "error", "warn", "info", "debug", "trace"]
+    )]
+    log_level: Option<log::LevelFilter>,
+
     /// Application arguments
     #[clap(name = "--", multiple = true)]
     args: Vec<String>,
 }
 
+/// The ABI a module is run with
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Abi {
+    /// WASI
+    Wasi,
+    /// Emscripten
+    Emscripten,
+}
+
+impl FromStr for Abi {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "wasi" => Ok(Self::Wasi),
+            "emscripten" => Ok(Self::Emscripten),
+            abi => bail!("The `{}` ABI does not exist.", abi),
+        }
+    }
+}
+
 impl Run {
     /// Execute the run command
     pub fn execute(&self) -> Result<()> {
-        #[cfg(feature = "debug")]
-        if self.debug {
-            logging::set_up_logging().unwrap();
+        self.color.apply();
+        if let Some(level) = self.log_level() {
+            logging::set_up_logging(level)
+                .map_err(|e| anyhow!("failed to set up logging: {}", e))?;
         }
-        self.inner_execute().with_context(|| {
+        let result = self.inner_execute().with_context(|| {
             format!(
                 "failed to run `{}`{}",
                 self.path.display(),
@@ -86,76 +270,137 @@ impl Run {
                     ""
                 }
             )
-        })
+        });
+        match (result, self.exit_on_trap_code) {
+            (Err(error), Some(exit_code)) if is_trap(&error) => {
+                PrettyError::exit_with(error, exit_code.into())
+            }
+            (result, _) => result,
+        }
+    }
+
+    /// The level to log at, from `--log-level`, `--debug` or `RUST_LOG`,
+    /// or `None` not to log.
+    fn log_level(&self) -> Option<log::LevelFilter> {
+        #[cfg(feature = "debug")]
+        if self.log_level.is_none() && self.debug {
+            return Some(log::LevelFilter::Debug);
+        }
+        self.log_level.or_else(logging::level_from_env)
     }
 
     fn inner_execute(&self) -> Result<()> {
-        let module = self.get_module()?;
+        if self.watch {
+            return self.watch();
+        }
+        match self.run_module()? {
+            0 => Ok(()),
+            exit_code => std::process::exit(exit_code as _),
+        }
+    }
+
+    /// Re-runs the module each time its file changes, reporting how each
+    /// run ends, until interrupted.
+    fn watch(&self) -> Result<()> {
+        let mut stamp = file_stamp(&self.path);
+        loop {
+            match self.run_module() {
+                Ok(exit_code) if !self.quiet => eprintln!(
+                    "--- `{}` exited with code {} ---",
+                    self.path.display(),
+                    exit_code
+                ),
+                Ok(_) => (),
+                Err(e) => eprintln!("--- `{}` failed: {:?} ---", self.path.display(), e),
+            }
+            stamp = wait_for_change(&self.path, stamp);
+            if !self.quiet {
+                eprintln!(
+                    "--- `{}` changed, running it again ---",
+                    self.path.display()
+                );
+            }
+        }
+    }
+
+    /// Runs the module, returning its WASI exit code, or 0 for modules
+    /// that don't use WASI.
+    fn run_module(&self) -> Result<u32> {
+        let compile_span = tracing::info_span!("compile", path = %self.path.display());
+        let module = compile_span.in_scope(|| -> Result<Module> {
+            let start = Instant::now();
+            let module = self.get_module()?;
+            tracing::info!(
+                "Compiled `{}` in {:?}",
+                self.path.display(),
+                start.elapsed()
+            );
+            Ok(module)
+        })?;
+        if let Some(ref output) = self.precompile {
+            module.serialize_to_file(output)?;
+            if !self.quiet {
+                eprintln!("✔ File compiled successfully to `{}`.", output.display());
+            }
+            return Ok(0);
+        }
+        let preloads = preload::compile_preloads(module.store(), &self.preloads)?;
         // Do we want to invoke a function?
         if let Some(ref invoke) = self.invoke {
-            let imports = imports! {};
-            let instance = Instance::new(&module, &imports)?;
-            let result = self.invoke_function(&instance, &invoke, &self.args)?;
-            println!(
-                "{}",
-                result
-                    .iter()
-                    .map(|val| val.to_string())
-                    .collect::<Vec<String>>()
-                    .join(" ")
-            );
-            return Ok(());
+            let instance = Self::instantiate(&module, &preloads)?;
+            self.load_input(&instance)?;
+            let result = self.invoke_and_print(&instance, invoke);
+            return result.and(self.dump_memory(&instance)).map(|()| 0);
         }
+        if let Some(ref prefix) = self.invoke_all {
+            let instance = Self::instantiate(&module, &preloads)?;
+            self.load_input(&instance)?;
+            return self.invoke_all_functions(&instance, &prefix).map(|()| 0);
+        }
+        #[cfg(any(feature = "wasi", feature = "emscripten"))]
+        let abi = self.get_abi(&module)?;
         #[cfg(feature = "emscripten")]
-        {
-            use wasmer_emscripten::{
-                generate_emscripten_env, is_emscripten_module, run_emscripten_instance, EmEnv,
-                EmscriptenGlobals,
-            };
-            // TODO: refactor this
-            if is_emscripten_module(&module) {
-                let mut emscripten_globals = EmscriptenGlobals::new(module.store(), &module)
-                    .map_err(|e| anyhow!("{}", e))?;
-                let mut em_env = EmEnv::new(&emscripten_globals.data, Default::default());
-                let import_object =
-                    generate_emscripten_env(module.store(), &mut emscripten_globals, &mut em_env);
-                let mut instance = match Instance::new(&module, &import_object) {
-                    Ok(instance) => instance,
-                    Err(e) => {
-                        let err: Result<(), _> = Err(e);
-                        #[cfg(feature = "wasi")]
-                        {
-                            if Wasi::has_wasi_imports(&module) {
-                                return err.with_context(|| "This module has both Emscripten and WASI imports. Wasmer does not currently support Emscripten modules using WASI imports.");
-                            }
-                        }
-                        return err.with_context(|| "Can't instantiate emscripten module");
-                    }
-                };
-
-                run_emscripten_instance(
-                    &mut instance,
-                    &mut em_env,
-                    &mut emscripten_globals,
-                    if let Some(cn) = &self.command_name {
-                        cn
-                    } else {
-                        self.path.to_str().unwrap()
-                    },
-                    self.args.iter().map(|arg| arg.as_str()).collect(),
-                    None, //run.em_entrypoint.clone(),
-                )?;
-                return Ok(());
+        if abi == Some(Abi::Emscripten) {
+            if !preloads.is_empty() {
+                bail!("`--preload` is not supported for Emscripten modules");
             }
+            #[cfg(feature = "wasi")]
+            let (mapped_dirs, env_vars) = self.wasi.emscripten_options()?;
+            #[cfg(not(feature = "wasi"))]
+            let (mapped_dirs, env_vars) = (Default::default(), Vec::new());
+            let program_name = self
+                .argv0
+                .clone()
+                .or_else(|| self.command_name.clone())
+                .or_else(|| self.basename())
+                .unwrap_or_else(|| self.path.to_string_lossy().into_owned());
+            return self
+                .emscripten
+                .execute(
+                    &module,
+                    &program_name,
+                    &self.program_args()?,
+                    mapped_dirs,
+                    &env_vars,
+                    self.dry_run,
+                )
+                .map(|()| 0);
         }
 
         // If WASI is enabled, try to execute it with it
         #[cfg(feature = "wasi")]
-        {
+        if abi != Some(Abi::Emscripten) {
             use std::collections::BTreeSet;
             use wasmer_wasi::WasiVersion;
 
-            let wasi_versions = Wasi::get_versions(&module);
+            let wasi = self
+                .wasi
+                .with_preloads(preloads.clone())
+                .with_quiet(self.quiet)
+                .with_memory_dump(self.memory_dump.clone())
+                .with_input(self.memory_input());
+            wasi.check_non_wasi_imports(&module)?;
+            let wasi_versions = wasi.get_linked_versions(&module);
             if let Some(wasi_versions) = wasi_versions {
                 if wasi_versions.len() >= 2 {
                     let get_version_list = |versions: &BTreeSet<WasiVersion>| -> String {
@@ -168,44 +413,182 @@ impl Run {
                     if self.wasi.deny_multiple_wasi_versions {
                         let version_list = get_version_list(&wasi_versions);
                         bail!("Found more than 1 WASI version in this module ({}) and `--deny-multiple-wasi-versions` is enabled.", version_list);
-                    } else if !self.wasi.allow_multiple_wasi_versions {
+                    } else if !self.wasi.allow_multiple_wasi_versions && !self.quiet {
                         let version_list = get_version_list(&wasi_versions);
                         warning!("Found more than 1 WASI version in this module ({}). If this is intentional, pass `--allow-multiple-wasi-versions` to suppress this warning.", version_list);
                     }
                 }
 
                 let program_name = self
-                    .command_name
+                    .argv0
                     .clone()
+                    .or_else(|| self.command_name.clone())
+                    .or_else(|| self.basename())
                     .or_else(|| {
                         self.path
                             .file_name()
                             .map(|f| f.to_string_lossy().to_string())
                     })
                     .unwrap_or_default();
-                return self
-                    .wasi
-                    .execute(module, program_name, self.args.clone())
+                return wasi
+                    .execute_with_exit_code(
+                        module,
+                        program_name,
+                        self.program_args()?,
+                        self.dry_run,
+                    )
                     .with_context(|| "WASI execution failed");
             }
         }
 
-        // Try to instantiate the wasm file, with no provided imports
-        let imports = imports! {};
-        let instance = Instance::new(&module, &imports)?;
+        // Try to instantiate the wasm file, with only the preloaded modules
+        // as imports
+        let instance = Self::instantiate(&module, &preloads)?;
         let start: Function = self.try_find_function(&instance, "_start", &[])?;
-        start.call(&[])?;
+        if self.dry_run {
+            println!("Entry point: `_start`");
+            return Ok(0);
+        }
+        self.load_input(&instance)?;
+        let result = start.call(&[]).map(|_| ()).map_err(Error::from);
+        result.and(self.dump_memory(&instance)).map(|()| 0)
+    }
+
+    /// The name of the file being run without its extension, with
+    /// `--argv0-from-basename`.
+    #[cfg(any(feature = "wasi", feature = "emscripten"))]
+    fn basename(&self) -> Option<String> {
+        if !self.argv0_from_basename {
+            return None;
+        }
+        self.path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().into_owned())
+    }
 
+    /// The arguments passed to the Wasm program: those given on the command
+    /// line followed by the lines of the `--argv-from-file` file.
+    #[cfg(any(feature = "wasi", feature = "emscripten"))]
+    fn program_args(&self) -> Result<Vec<String>> {
+        let mut args = self.args.clone();
+        if let Some(path) = &self.argv_from_file {
+            let contents = std::fs::read_to_string(path).with_context(|| {
+                format!("failed to read the arguments file `{}`", path.display())
+            })?;
+            args.extend(contents.lines().map(String::from));
+        }
+        Ok(args)
+    }
+
+    /// Calls the function given to `--invoke`, as many times as `--repeat`
+    /// asks, printing its results.
+    fn invoke_and_print(&self, instance: &Instance, invoke: &str) -> Result<()> {
+        let repeat = self.repeat.map_or(1, NonZeroUsize::get);
+        let mut times = Vec::with_capacity(repeat);
+        for _ in 0..repeat {
+            let start = Instant::now();
+            let result = self.invoke_function(instance, invoke, &self.args)?;
+            times.push(start.elapsed());
+            println!(
+                "{}",
+                result
+                    .iter()
+                    .map(|val| val.to_string())
+                    .collect::<Vec<String>>()
+                    .join(" ")
+            );
+        }
+        if self.repeat.is_some() {
+            report_repeat_timing(&times);
+        }
         Ok(())
     }
 
+    /// Writes the memory of `instance` to the `--memory-dump` file, if
+    /// given.
+    fn dump_memory(&self, instance: &Instance) -> Result<()> {
+        match &self.memory_dump {
+            Some(path) => dump_memory(instance, path),
+            None => Ok(()),
+        }
+    }
+
+    /// The file to copy into memory before the module runs, from `--input`.
+    fn memory_input(&self) -> Option<MemoryInput> {
+        Some(MemoryInput {
+            path: self.input.clone()?,
+            // Clap makes `--input` require `--input-addr`.
+            addr: self.input_addr.expect("--input without --input-addr"),
+            len_addr: self.input_len_addr,
+        })
+    }
+
+    /// Copies the `--input` file into the memory of `instance`, if given.
+    fn load_input(&self, instance: &Instance) -> Result<()> {
+        match self.memory_input() {
+            Some(input) => load_input(instance, &input),
+            None => Ok(()),
+        }
+    }
+
+    /// Instantiates the module outside of any ABI, importing only from the
+    /// preloaded modules.
+    fn instantiate(module: &Module, preloads: &[(String, Module)]) -> Result<Instance> {
+        let imports = preload::instantiate_preloads(preloads)?;
+        if !preloads.is_empty() {
+            preload::check_unresolved_imports(module, &imports, &preload::describe(preloads))?;
+        }
+        Ok(Instance::new(module, &imports)?)
+    }
+
+    /// Gets the ABI to run the module with, either as given by `--abi` or
+    /// detected from the module imports.
+    #[cfg(any(feature = "wasi", feature = "emscripten"))]
+    fn get_abi(&self, module: &Module) -> Result<Option<Abi>> {
+        if let Some(abi) = self.abi {
+            #[cfg(not(feature = "wasi"))]
+            if abi == Abi::Wasi {
+                bail!("WASI support is not enabled in this build");
+            }
+            #[cfg(not(feature = "emscripten"))]
+            if abi == Abi::Emscripten {
+                bail!("Emscripten support is not enabled in this build");
+            }
+            return Ok(Some(abi));
+        }
+        #[cfg(feature = "wasi")]
+        let is_wasi = Wasi::has_wasi_imports(module);
+        #[cfg(not(feature = "wasi"))]
+        let is_wasi = false;
+        #[cfg(feature = "emscripten")]
+        let is_emscripten = wasmer_emscripten::is_emscripten_module(module);
+        #[cfg(not(feature = "emscripten"))]
+        let is_emscripten = false;
+        match (is_wasi, is_emscripten) {
+            (true, true) => bail!(
+                "This module imports both the WASI and the Emscripten ABIs. Pass `--abi wasi` or `--abi emscripten` to choose which one to run it with."
+            ),
+            (true, false) => Ok(Some(Abi::Wasi)),
+            (false, true) => Ok(Some(Abi::Emscripten)),
+            (false, false) => Ok(None),
+        }
+    }
+
     fn get_module(&self) -> Result<Module> {
         let contents = std::fs::read(self.path.clone())?;
+        tracing::info!(
+            "Compiling `{}` ({} bytes)",
+            self.path.display(),
+            contents.len()
+        );
+        // Deserializing an artifact loads native code from it as is, so
+        // artifacts must come from a trusted source, such as `wasmer compile`
+        // or `wasmer run --precompile`.
         #[cfg(feature = "native")]
         {
             if wasmer_engine_native::NativeArtifact::is_deserializable(&contents) {
                 let engine = wasmer_engine_native::Native::headless().engine();
-                let store = Store::new(&engine);
+                let store = self.limit_memory(Store::new(&engine));
                 let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
                 return Ok(module);
             }
@@ -214,14 +597,20 @@ impl Run {
         {
             if wasmer_engine_jit::JITArtifact::is_deserializable(&contents) {
                 let engine = wasmer_engine_jit::JIT::headless().engine();
-                let store = Store::new(&engine);
+                let store = self.limit_memory(Store::new(&engine));
                 let module = unsafe { Module::deserialize_from_file(&store, &self.path)? };
                 return Ok(module);
             }
         }
         let (store, engine_type, compiler_type) = self.store.get_store()?;
+        let store = self.limit_memory(store);
+        // Caching small modules isn't worth it, unless asked to with
+        // `--cache-dir`.
         #[cfg(feature = "cache")]
-        let module_result: Result<Module> = if !self.disable_cache && contents.len() > 0x1000 {
+        let use_cache =
+            !self.disable_cache && (self.cache_dir.is_some() || contents.len() > 0x1000);
+        #[cfg(feature = "cache")]
+        let module_result: Result<Module> = if use_cache {
             self.get_module_from_cache(&store, &contents, &engine_type, &compiler_type)
         } else {
             Module::new(&store, &contents).map_err(|e| e.into())
@@ -242,6 +631,19 @@ impl Run {
         Ok(module)
     }
 
+    /// Replaces the tunables of `store` to cap the memories at
+    /// `--max-memory`, if given.
+    fn limit_memory(&self, store: Store) -> Store {
+        match self.max_memory {
+            Some(limit) => {
+                let engine = store.engine();
+                let base = BaseTunables::for_target(engine.target());
+                Store::new_with_tunables(&**engine, MemoryLimit::new(base, limit))
+            }
+            None => store,
+        }
+    }
+
     #[cfg(feature = "cache")]
     fn get_module_from_cache(
         &self,
@@ -269,9 +671,10 @@ impl Run {
                     DeserializeError::Io(_) => {
                         // Do not notify on IO errors
                     }
-                    err => {
+                    err if !self.quiet => {
                         warning!("cached module is corrupted: {}", err);
                     }
+                    _ => (),
                 }
                 let module = Module::new(&store, &contents)?;
                 // Store the compiled Module in cache
@@ -288,8 +691,13 @@ impl Run {
         engine_type: &EngineType,
         compiler_type: &CompilerType,
     ) -> Result<FileSystemCache> {
-        let mut cache_dir_root = get_cache_dir();
+        let mut cache_dir_root = match &self.cache_dir {
+            Some(cache_dir) => cache_dir.clone(),
+            None => get_cache_dir(),
+        };
         cache_dir_root.push(compiler_type.to_string());
+        #[cfg(all(feature = "compiler", feature = "engine"))]
+        cache_dir_root.push(features_cache_dir(&self.store.get_features()?));
         let mut cache = FileSystemCache::new(cache_dir_root)?;
         // Important: Native files need to have a `.dll` extension on Windows, otherwise
         // they will not load, so we just add an extension always to make it easier
@@ -365,6 +773,13 @@ impl Run {
     ) -> Result<Box<[Val]>> {
         let func: Function = self.try_find_function(&instance, invoke, args)?;
         let func_ty = func.ty();
+        if let Some(json) = &self.entry_args_json {
+            if !args.is_empty() {
+                bail!("Arguments can't be given both as JSON and on the command line");
+            }
+            let invoke_args = parse_json_args(json, func_ty.params())?;
+            return Ok(func.call(&invoke_args)?);
+        }
         let required_arguments = func_ty.params().len();
         let provided_arguments = args.len();
         if required_arguments != provided_arguments {
@@ -408,4 +823,281 @@ impl Run {
             .collect::<Result<Vec<_>>>()?;
         Ok(func.call(&invoke_args)?)
     }
+
+    /// Call every exported function whose name starts with `prefix`,
+    /// reporting all the failures at the end.
+    fn invoke_all_functions(&self, instance: &Instance, prefix: &str) -> Result<()> {
+        let mut names = instance
+            .exports
+            .iter()
+            .functions()
+            .filter(|(name, _)| name.starts_with(prefix))
+            .map(|(name, _)| name.clone())
+            .collect::<Vec<String>>();
+        if names.is_empty() {
+            bail!("No exported function starts with `{}`", prefix);
+        }
+        names.sort();
+
+        let mut failures = vec![];
+        for name in names.iter() {
+            match self.invoke_function(&instance, &name, &[]) {
+                Ok(_) => println!("{} ... ok", name),
+                Err(e) => {
+                    println!("{} ... FAILED", name);
+                    failures.push(format!("`{}`: {:#}", name, e));
+                }
+            }
+        }
+        if !failures.is_empty() {
+            bail!(
+                "{} of {} functions failed:\n{}",
+                failures.len(),
+                names.len(),
+                failures.join("\n")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// Names the subdirectory of the cache for modules compiled with
+/// `features`, so that the modules are compiled again when they change.
+#[cfg(all(feature = "cache", feature = "compiler", feature = "engine"))]
+fn features_cache_dir(features: &Features) -> String {
+    let enabled = [
+        ("threads", features.threads),
+        ("reference-types", features.reference_types),
+        ("simd", features.simd),
+        ("bulk-memory", features.bulk_memory),
+        ("multi-value", features.multi_value),
+        ("tail-call", features.tail_call),
+        ("module-linking", features.module_linking),
+        ("multi-memory", features.multi_memory),
+        ("memory64", features.memory64),
+        ("exceptions", features.exceptions),
+    ]
+    .iter()
+    .filter(|(_, enabled)| *enabled)
+    .map(|(name, _)| *name)
+    .collect::<Vec<_>>();
+    if enabled.is_empty() {
+        "no-features".to_string()
+    } else {
+        enabled.join("+")
+    }
+}
+
+/// Checks whether `error` comes from a trap in the module, including one in
+/// its start function, rather than e.g. from a host function.
+fn is_trap(error: &Error) -> bool {
+    error
+        .chain()
+        .filter_map(|cause| runtime_error(cause))
+        .any(|error| error.clone().to_trap().is_some())
+}
+
+/// The memory `instance` exports as `memory`, or else its first exported
+/// memory.
+fn exported_memory(instance: &Instance) -> Option<&Memory> {
+    match instance.exports.get_memory("memory") {
+        Ok(memory) => Some(memory),
+        Err(_) => instance
+            .exports
+            .iter()
+            .memories()
+            .next()
+            .map(|(_, memory)| memory),
+    }
+}
+
+/// Writes the contents of the memory exported by `instance` as `memory`, or
+/// else of its first exported memory, to `path`.
+fn dump_memory(instance: &Instance, path: &Path) -> Result<()> {
+    let memory = match exported_memory(instance) {
+        Some(memory) => memory,
+        None => bail!("The module exports no memory to dump"),
+    };
+    std::fs::write(path, unsafe { memory.data_unchecked() })
+        .with_context(|| format!("failed to write the memory dump `{}`", path.display()))
+}
+
+/// A file to copy into the module's memory before it runs.
+#[derive(Debug, Clone)]
+pub(crate) struct MemoryInput {
+    /// The file to copy.
+    path: PathBuf,
+    /// The offset in memory to copy it to.
+    addr: u32,
+    /// The offset in memory to write the size of the file to, if any.
+    len_addr: Option<u32>,
+}
+
+/// Copies the file of `input` into the memory of `instance`, found like by
+/// [`dump_memory`], growing the memory if the file doesn't fit, and writes
+/// the size of the file where `input` asks.
+fn load_input(instance: &Instance, input: &MemoryInput) -> Result<()> {
+    let path = input.path.display();
+    let contents =
+        std::fs::read(&input.path).with_context(|| format!("failed to read `{}`", path))?;
+    let memory = match exported_memory(instance) {
+        Some(memory) => memory,
+        None => bail!("The module exports no memory to copy `{}` into", path),
+    };
+    let start = u64::from(input.addr);
+    let end = start + contents.len() as u64;
+    let needed = match input.len_addr {
+        Some(len_addr) => end.max(u64::from(len_addr) + 4),
+        None => end,
+    };
+    if needed > 1u64 << 32 {
+        bail!(
+            "`{}` of {} bytes doesn't fit in memory at offset {}",
+            path,
+            contents.len(),
+            input.addr
+        );
+    }
+    let size = memory.data_size();
+    if needed > size {
+        let page_size = WASM_PAGE_SIZE as u64;
+        let pages = (needed - size + page_size - 1) / page_size;
+        memory
+            .grow(pages as u32)
+            .with_context(|| format!("failed to grow the memory to fit `{}`", path))?;
+    }
+    let data = unsafe { memory.data_unchecked_mut() };
+    data[start as usize..end as usize].copy_from_slice(&contents);
+    if let Some(len_addr) = input.len_addr {
+        let len_addr = len_addr as usize;
+        data[len_addr..len_addr + 4].copy_from_slice(&(contents.len() as u32).to_le_bytes());
+    }
+    Ok(())
+}
+
+/// Prints the time each `--repeat` call took, followed by the fastest,
+/// mean and slowest times.
+fn report_repeat_timing(times: &[Duration]) {
+    for (i, time) in times.iter().enumerate() {
+        eprintln!("call {}: {:?}", i + 1, time);
+    }
+    let total: Duration = times.iter().sum();
+    eprintln!(
+        "{} calls: min {:?}, mean {:?}, max {:?}",
+        times.len(),
+        times.iter().min().unwrap(),
+        total / times.len() as u32,
+        times.iter().max().unwrap()
+    );
+}
+
+/// Parses a JSON array of arguments, converting each one to the type of the
+/// matching parameter.
+fn parse_json_args(json: &str, params: &[ValType]) -> Result<Vec<Val>> {
+    let args: Vec<serde_json::Value> =
+        serde_json::from_str(json).with_context(|| format!("`{}` is not a JSON array", json))?;
+    if args.len() != params.len() {
+        bail!(
+            "Function expected {} arguments, but received {}: {}",
+            params.len(),
+            args.len(),
+            json
+        );
+    }
+    args.iter()
+        .zip(params.iter())
+        .enumerate()
+        .map(|(i, (arg, param_type))| {
+            json_arg_to_val(arg, param_type).ok_or_else(|| {
+                anyhow!(
+                    "Can't convert argument {} `{}` into a {}",
+                    i,
+                    arg,
+                    param_type.to_string().to_lowercase()
+                )
+            })
+        })
+        .collect()
+}
+
+/// Converts a JSON number, or a string holding either a number or the
+/// hexadecimal bits of one, to a value of type `ty`.
+fn json_arg_to_val(arg: &serde_json::Value, ty: &ValType) -> Option<Val> {
+    use serde_json::Value as Json;
+    use std::convert::TryFrom;
+
+    if let Json::String(s) = arg {
+        if let Some(hex) = s.strip_prefix("0x") {
+            return match ty {
+                ValType::I32 => u32::from_str_radix(hex, 16)
+                    .ok()
+                    .map(|v| Val::I32(v as i32)),
+                ValType::I64 => u64::from_str_radix(hex, 16)
+                    .ok()
+                    .map(|v| Val::I64(v as i64)),
+                ValType::F32 => u32::from_str_radix(hex, 16)
+                    .ok()
+                    .map(|v| Val::F32(f32::from_bits(v))),
+                ValType::F64 => u64::from_str_radix(hex, 16)
+                    .ok()
+                    .map(|v| Val::F64(f64::from_bits(v))),
+                _ => None,
+            };
+        }
+        return match ty {
+            ValType::I32 => s.parse().ok().map(Val::I32),
+            ValType::I64 => s.parse().ok().map(Val::I64),
+            ValType::F32 => s.parse().ok().map(Val::F32),
+            ValType::F64 => s.parse().ok().map(Val::F64),
+            _ => None,
+        };
+    }
+    match (arg, ty) {
+        (Json::Number(n), ValType::I32) => {
+            n.as_i64().and_then(|v| i32::try_from(v).ok()).map(Val::I32)
+        }
+        (Json::Number(n), ValType::I64) => n.as_i64().map(Val::I64),
+        (Json::Number(n), ValType::F32) => n.as_f64().map(|v| Val::F32(v as f32)),
+        (Json::Number(n), ValType::F64) => n.as_f64().map(Val::F64),
+        _ => None,
+    }
+}
+
+/// How often `--watch` checks whether the file changed.
+const WATCH_INTERVAL: Duration = Duration::from_millis(250);
+
+/// How long the file must stay unchanged before `--watch` runs it again, so
+/// that a file being written runs once it is complete.
+const WATCH_DEBOUNCE: Duration = Duration::from_millis(100);
+
+/// The modification time and size of a file, or `None` if it can't be read,
+/// e.g. while an editor replaces it.
+type FileStamp = Option<(SystemTime, u64)>;
+
+fn file_stamp(path: &Path) -> FileStamp {
+    let metadata = std::fs::metadata(path).ok()?;
+    Some((metadata.modified().ok()?, metadata.len()))
+}
+
+/// Waits until the file at `path` exists and differs from `stamp`, then
+/// until it stops changing, returning its new stamp.
+fn wait_for_change(path: &Path, stamp: FileStamp) -> FileStamp {
+    loop {
+        std::thread::sleep(WATCH_INTERVAL);
+        let mut new_stamp = file_stamp(path);
+        if new_stamp.is_none() || new_stamp == stamp {
+            continue;
+        }
+        loop {
+            std::thread::sleep(WATCH_DEBOUNCE);
+            let settled = file_stamp(path);
+            if settled == new_stamp {
+                break;
+            }
+            new_stamp = settled;
+        }
+        if new_stamp.is_some() {
+            return new_stamp;
+        }
+    }
 }
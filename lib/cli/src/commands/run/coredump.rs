@@ -0,0 +1,116 @@
+//! Writing Wasm coredumps, in the format described at
+//! <https://github.com/WebAssembly/tool-conventions/blob/main/Coredump.md>.
+
+use anyhow::{Context, Result};
+use std::cell::Cell;
+use std::path::Path;
+use wasmer::{Memory, RuntimeError};
+
+/// Writes a coredump of `error`, trapping in the module `module_name`, to
+/// `path`.
+///
+/// The coredump records the Wasm frames of the trap and the contents of
+/// `memory`. Locals and operand stack values aren't available, so they
+/// are left out.
+pub fn write_coredump(
+    path: &Path,
+    program_name: &str,
+    module_name: &str,
+    memory: Option<&Memory>,
+    error: &RuntimeError,
+) -> Result<()> {
+    let mut out = b"\0asm".to_vec();
+    out.extend_from_slice(&1u32.to_le_bytes());
+
+    let mut core = vec![0x00];
+    write_name(&mut core, program_name);
+    write_section(&mut out, 0, Some("core"), &core);
+
+    let mut modules = Vec::new();
+    write_u32(&mut modules, 1);
+    modules.push(0x00);
+    write_name(&mut modules, module_name);
+    write_section(&mut out, 0, Some("coremodules"), &modules);
+
+    let mut instances = Vec::new();
+    write_u32(&mut instances, 1);
+    instances.push(0x00);
+    // The module index, the memory indices and the global indices.
+    write_u32(&mut instances, 0);
+    if memory.is_some() {
+        write_u32(&mut instances, 1);
+        write_u32(&mut instances, 0);
+    } else {
+        write_u32(&mut instances, 0);
+    }
+    write_u32(&mut instances, 0);
+    write_section(&mut out, 0, Some("coreinstances"), &instances);
+
+    let mut stack = vec![0x00];
+    write_name(&mut stack, "main");
+    write_u32(&mut stack, error.trace().len() as u32);
+    for frame in error.trace() {
+        stack.push(0x00);
+        // The instance index, then no locals nor operand stack values.
+        write_u32(&mut stack, 0);
+        write_u32(&mut stack, frame.func_index());
+        write_u32(&mut stack, frame.func_offset() as u32);
+        write_u32(&mut stack, 0);
+        write_u32(&mut stack, 0);
+    }
+    write_section(&mut out, 0, Some("corestack"), &stack);
+
+    if let Some(memory) = memory {
+        let mut memories = Vec::new();
+        write_u32(&mut memories, 1);
+        memories.push(0x00);
+        write_u32(&mut memories, memory.size().0);
+        write_section(&mut out, 5, None, &memories);
+
+        let contents = memory
+            .view::<u8>()
+            .iter()
+            .map(Cell::get)
+            .collect::<Vec<u8>>();
+        let mut data = Vec::new();
+        write_u32(&mut data, 1);
+        // An active segment at offset `i32.const 0`.
+        data.extend_from_slice(&[0x00, 0x41, 0x00, 0x0b]);
+        write_u32(&mut data, contents.len() as u32);
+        data.extend_from_slice(&contents);
+        write_section(&mut out, 11, None, &data);
+    }
+
+    std::fs::write(path, out)
+        .with_context(|| format!("failed to write the coredump to `{}`", path.display()))
+}
+
+/// Appends a section, named if it's a custom one.
+fn write_section(out: &mut Vec<u8>, id: u8, name: Option<&str>, contents: &[u8]) {
+    let mut section = Vec::new();
+    if let Some(name) = name {
+        write_name(&mut section, name);
+    }
+    section.extend_from_slice(contents);
+    out.push(id);
+    write_u32(out, section.len() as u32);
+    out.extend_from_slice(&section);
+}
+
+fn write_name(out: &mut Vec<u8>, name: &str) {
+    write_u32(out, name.len() as u32);
+    out.extend_from_slice(name.as_bytes());
+}
+
+/// Appends `value` encoded as an unsigned LEB128.
+fn write_u32(out: &mut Vec<u8>, mut value: u32) {
+    loop {
+        let byte = (value & 0x7f) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            return;
+        }
+        out.push(byte | 0x80);
+    }
+}
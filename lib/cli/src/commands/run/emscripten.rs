@@ -0,0 +1,70 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use wasmer::{Instance, Module};
+use wasmer_emscripten::{
+    generate_emscripten_env, run_emscripten_instance, EmEnv, EmscriptenGlobals,
+};
+
+use clap::Clap;
+
+#[derive(Debug, Clap, Clone, Default)]
+/// Emscripten Options
+pub struct Emscripten {
+    /// Call the given export with a pointer to `argv[0]` instead of `main`,
+    /// for Emscripten modules
+    #[clap(long = "em-entrypoint", name = "EXPORT")]
+    entrypoint: Option<String>,
+}
+
+impl Emscripten {
+    /// Runs an Emscripten module.
+    ///
+    /// `mapped_dirs` gives the host directory of each guest path, and the
+    /// module sees the variables in `env_vars` on top of the environment of
+    /// the process.
+    pub fn execute(
+        &self,
+        module: &Module,
+        program_name: &str,
+        args: &[String],
+        mapped_dirs: HashMap<String, PathBuf>,
+        env_vars: &[(String, String)],
+        dry_run: bool,
+    ) -> Result<()> {
+        let mut emscripten_globals =
+            EmscriptenGlobals::new(module.store(), module).map_err(|e| anyhow!("{}", e))?;
+        let mut em_env = EmEnv::new(&emscripten_globals.data, mapped_dirs);
+        em_env.set_env_vars(env_vars.to_vec());
+        let import_object =
+            generate_emscripten_env(module.store(), &mut emscripten_globals, &mut em_env);
+        let mut instance = match Instance::new(module, &import_object) {
+            Ok(instance) => instance,
+            Err(e) => {
+                let err: Result<(), _> = Err(e);
+                #[cfg(feature = "wasi")]
+                {
+                    if super::Wasi::has_wasi_imports(module) {
+                        return err.with_context(|| "This module has both Emscripten and WASI imports. Wasmer does not currently support Emscripten modules using WASI imports.");
+                    }
+                }
+                return err.with_context(|| "Can't instantiate emscripten module");
+            }
+        };
+
+        if dry_run {
+            println!("Emscripten module instantiated");
+            return Ok(());
+        }
+
+        run_emscripten_instance(
+            &mut instance,
+            &mut em_env,
+            &mut emscripten_globals,
+            program_name,
+            args.iter().map(|arg| arg.as_str()).collect(),
+            self.entrypoint.clone(),
+        )?;
+        Ok(())
+    }
+}
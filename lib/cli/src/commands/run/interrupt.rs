@@ -0,0 +1,95 @@
+//! Stopping a WASI module once `--timeout` expires or a syscall outlasts
+//! `--host-function-timeout`.  While either is watched, Ctrl-C stops the
+//! module the same way.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::sync::Arc;
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use wasmer_wasi::{InterruptHandle, SyscallTimeout};
+
+/// How often the watcher checks whether the module should be stopped.
+const POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Set by the `SIGINT` handler.
+static SIGINT_RECEIVED: AtomicBool = AtomicBool::new(false);
+
+/// Interrupts a module on Ctrl-C or after a timeout, until dropped.
+///
+/// The module only stops at its next interrupt check, see
+/// [`InterruptHandle`], so a second Ctrl-C exits the process right away.
+/// A syscall running for longer than its timeout is interrupted too, which
+/// wakes it up if it is sleeping, and then fails once it returns.
+pub struct InterruptWatcher {
+    /// Dropped to stop the watcher thread.
+    done: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+    /// The `SIGINT` action to restore once the module is done.
+    #[cfg(unix)]
+    previous_action: libc::sigaction,
+}
+
+impl InterruptWatcher {
+    /// Starts watching for Ctrl-C and, if given, for `timeout` to expire
+    /// and for a syscall to outlast `syscall_timeout`.
+    pub fn start(
+        handle: InterruptHandle,
+        timeout: Option<Duration>,
+        syscall_timeout: Option<Arc<SyscallTimeout>>,
+    ) -> Self {
+        SIGINT_RECEIVED.store(false, Ordering::SeqCst);
+        #[cfg(unix)]
+        let previous_action = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigint as libc::sighandler_t;
+            action.sa_flags = libc::SA_RESTART;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous_action: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGINT, &action, &mut previous_action);
+            previous_action
+        };
+        let deadline = timeout.map(|timeout| Instant::now() + timeout);
+        let (done, receiver) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || loop {
+            match receiver.recv_timeout(POLL_INTERVAL) {
+                Err(RecvTimeoutError::Timeout) => (),
+                _ => return,
+            }
+            let syscall_expired = syscall_timeout
+                .as_ref()
+                .map_or(false, |timeout| timeout.expired().is_some());
+            let timed_out = deadline.map_or(false, |deadline| Instant::now() >= deadline);
+            if syscall_expired || timed_out || SIGINT_RECEIVED.load(Ordering::SeqCst) {
+                handle.interrupt();
+                return;
+            }
+        });
+        Self {
+            done: Some(done),
+            thread: Some(thread),
+            #[cfg(unix)]
+            previous_action,
+        }
+    }
+}
+
+impl Drop for InterruptWatcher {
+    fn drop(&mut self) {
+        drop(self.done.take());
+        if let Some(thread) = self.thread.take() {
+            thread.join().ok();
+        }
+        #[cfg(unix)]
+        unsafe {
+            libc::sigaction(libc::SIGINT, &self.previous_action, std::ptr::null_mut());
+        }
+    }
+}
+
+#[cfg(unix)]
+extern "C" fn on_sigint(_signal: libc::c_int) {
+    if SIGINT_RECEIVED.swap(true, Ordering::SeqCst) {
+        unsafe { libc::_exit(128 + libc::SIGINT) };
+    }
+}
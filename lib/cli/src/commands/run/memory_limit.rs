@@ -0,0 +1,98 @@
+//! Tunables capping the size of the linear memories of a module, for the
+//! `--max-memory` option.
+
+use loupe::MemoryUsage;
+use std::ptr::NonNull;
+use std::sync::Arc;
+use wasmer::vm::{
+    self, MemoryError, MemoryStyle, TableStyle, VMMemoryDefinition, VMTableDefinition,
+};
+use wasmer::{BaseTunables, MemoryType, Pages, TableType, Tunables};
+
+/// Tunables lowering the maximum of every memory to `limit`, whatever the
+/// module declares, and delegating everything else to [`BaseTunables`].
+///
+/// Growing a memory past its maximum fails, so `memory.grow` returns `-1`
+/// to the guest once the limit is reached.
+#[derive(MemoryUsage)]
+pub struct MemoryLimit {
+    /// The largest size a memory may have.
+    limit: Pages,
+    /// The tunables the memories are created with once adjusted.
+    base: BaseTunables,
+}
+
+impl MemoryLimit {
+    /// Creates tunables capping the memories at `limit`.
+    pub fn new(base: BaseTunables, limit: Pages) -> Self {
+        Self { limit, base }
+    }
+
+    /// Lowers the maximum of `requested` to the limit, failing if even its
+    /// minimum is above the limit.
+    fn adjust_memory(&self, requested: &MemoryType) -> Result<MemoryType, MemoryError> {
+        if requested.minimum > self.limit {
+            return Err(MemoryError::Generic(format!(
+                "the memory needs at least {} pages but `--max-memory` allows {}",
+                requested.minimum.0, self.limit.0
+            )));
+        }
+        let mut adjusted = requested.clone();
+        adjusted.maximum = Some(match requested.maximum {
+            Some(maximum) if maximum < self.limit => maximum,
+            _ => self.limit,
+        });
+        Ok(adjusted)
+    }
+}
+
+impl Tunables for MemoryLimit {
+    fn memory_style(&self, memory: &MemoryType) -> MemoryStyle {
+        // An invalid memory fails when it is created, so its style doesn't
+        // matter.
+        match self.adjust_memory(memory) {
+            Ok(adjusted) => self.base.memory_style(&adjusted),
+            Err(_) => self.base.memory_style(memory),
+        }
+    }
+
+    fn table_style(&self, table: &TableType) -> TableStyle {
+        self.base.table_style(table)
+    }
+
+    fn create_host_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        self.base
+            .create_host_memory(&self.adjust_memory(ty)?, style)
+    }
+
+    unsafe fn create_vm_memory(
+        &self,
+        ty: &MemoryType,
+        style: &MemoryStyle,
+        vm_definition_location: NonNull<VMMemoryDefinition>,
+    ) -> Result<Arc<dyn vm::Memory>, MemoryError> {
+        self.base
+            .create_vm_memory(&self.adjust_memory(ty)?, style, vm_definition_location)
+    }
+
+    fn create_host_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.base.create_host_table(ty, style)
+    }
+
+    unsafe fn create_vm_table(
+        &self,
+        ty: &TableType,
+        style: &TableStyle,
+        vm_definition_location: NonNull<VMTableDefinition>,
+    ) -> Result<Arc<dyn vm::Table>, String> {
+        self.base.create_vm_table(ty, style, vm_definition_location)
+    }
+}
@@ -0,0 +1,83 @@
+//! Modules linked with the `--preload` option, whose exports the module
+//! being run imports.
+
+use anyhow::{bail, Context, Result};
+use std::path::PathBuf;
+use wasmer::{ImportObject, Instance, Module, NamedResolver, Store};
+
+/// Compiles the modules given to `--preload`, keeping their names.
+pub fn compile_preloads(
+    store: &Store,
+    preloads: &[(String, PathBuf)],
+) -> Result<Vec<(String, Module)>> {
+    preloads
+        .iter()
+        .map(|(name, path)| {
+            let module = Module::from_file(store, path).with_context(|| {
+                format!(
+                    "failed to compile the preloaded module `{}` from `{}`",
+                    name,
+                    path.display()
+                )
+            })?;
+            Ok((name.clone(), module))
+        })
+        .collect()
+}
+
+/// Instantiates the preloaded modules in the order they are given and
+/// registers the exports of each one as an import namespace named after
+/// it. Each module may only import from the ones before it.
+pub fn instantiate_preloads(preloads: &[(String, Module)]) -> Result<ImportObject> {
+    let mut imports = ImportObject::new();
+    for (index, (name, module)) in preloads.iter().enumerate() {
+        let instance = check_unresolved_imports(module, &imports, &describe(&preloads[..index]))
+            .and_then(|()| Ok(Instance::new(module, &imports)?))
+            .with_context(|| format!("failed to instantiate the preloaded module `{}`", name))?;
+        imports.register(name, instance.exports);
+    }
+    Ok(imports)
+}
+
+/// Describes the preloaded modules for error messages.
+pub fn describe(preloads: &[(String, Module)]) -> String {
+    match preloads.len() {
+        0 => "nothing, as no module is preloaded before it".to_string(),
+        1 => format!("the preloaded module `{}`", preloads[0].0),
+        _ => format!(
+            "the preloaded modules {}",
+            preloads
+                .iter()
+                .map(|(name, _)| format!("`{}`", name))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+    }
+}
+
+/// Fails with a list of the imports of `module` that `resolver` doesn't
+/// provide, where `providers` describes what the resolver holds.
+pub fn check_unresolved_imports(
+    module: &Module,
+    resolver: &dyn NamedResolver,
+    providers: &str,
+) -> Result<()> {
+    let unresolved = module
+        .imports()
+        .filter(|import| {
+            resolver
+                .resolve_by_name(import.module(), import.name())
+                .is_none()
+        })
+        .map(|import| format!("`{}`.`{}`", import.module(), import.name()))
+        .collect::<Vec<_>>();
+    if unresolved.is_empty() {
+        return Ok(());
+    }
+    bail!(
+        "{} import(s) can't be resolved:\n    {}\nImports are looked up in {}.",
+        unresolved.len(),
+        unresolved.join("\n    "),
+        providers
+    )
+}
@@ -0,0 +1,343 @@
+//! Sampling the stack of the thread running a WASI module, for
+//! `--profile flamegraph`.
+
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
+use wasmer_engine::FRAME_INFO;
+
+/// How often the stack is sampled.
+const SAMPLE_INTERVAL: Duration = Duration::from_millis(1);
+
+/// How long to wait for the sampled thread to record its stack before
+/// giving up on a sample.
+const SAMPLE_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// The most frames recorded per sample, counting from the innermost one.
+const MAX_DEPTH: usize = 128;
+
+/// The return addresses of the last sample, written by the `SIGPROF`
+/// handler, which may only do async-signal-safe work, so it records them
+/// in preallocated atomics rather than in a `Vec`.
+static FRAMES: [AtomicUsize; MAX_DEPTH] = [ZERO; MAX_DEPTH];
+const ZERO: AtomicUsize = AtomicUsize::new(0);
+
+/// How many entries of [`FRAMES`] the last sample filled.
+static DEPTH: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of the last sample requested by the sampling thread.
+static REQUESTED: AtomicUsize = AtomicUsize::new(0);
+
+/// The number of the sample [`FRAMES`] holds, set by the `SIGPROF`
+/// handler once it has recorded it.
+static COMPLETED: AtomicUsize = AtomicUsize::new(0);
+
+/// Incremented by the `SIGPROF` handler before and after it writes to
+/// [`FRAMES`], so it is odd while a handler is running. The sampling thread
+/// drops a sample if it changed while it was read, which happens if a
+/// handler it gave up on runs late.
+static WRITES: AtomicUsize = AtomicUsize::new(0);
+
+/// The end of the stack of the sampled thread, which bounds the frames
+/// walked.
+static STACK_TOP: AtomicUsize = AtomicUsize::new(0);
+
+/// The raw stacks sampled, innermost frame first, with how many times
+/// each one was seen.
+type Samples = HashMap<Vec<usize>, u64>;
+
+/// Samples the stack of the thread which started it at a fixed interval,
+/// until [finished](Sampler::finish).
+///
+/// A helper thread sends `SIGPROF` to the sampled thread, whose handler
+/// walks its own stack by following the frame pointers. The samples are
+/// only mapped to Wasm functions once sampling is over.
+///
+/// Only Linux on x86-64 and AArch64 and macOS on x86-64 are supported. The
+/// frames of host code built without frame pointers may cut a stack short.
+pub struct Sampler {
+    /// Dropped to stop the sampling thread.
+    done: Option<mpsc::Sender<()>>,
+    thread: Option<JoinHandle<Samples>>,
+    /// The `SIGPROF` action to restore once sampling is over.
+    previous_action: libc::sigaction,
+}
+
+impl Sampler {
+    /// Starts sampling the current thread.
+    pub fn start() -> Self {
+        let previous_action = unsafe {
+            let mut action: libc::sigaction = std::mem::zeroed();
+            action.sa_sigaction = on_sigprof as libc::sighandler_t;
+            // Don't make the syscalls of the module fail with `EINTR`.
+            action.sa_flags = libc::SA_RESTART | libc::SA_SIGINFO;
+            libc::sigemptyset(&mut action.sa_mask);
+            let mut previous_action: libc::sigaction = std::mem::zeroed();
+            libc::sigaction(libc::SIGPROF, &action, &mut previous_action);
+            previous_action
+        };
+        // `pthread_t` is an integer on some platforms and a pointer on
+        // others, which can't be sent to another thread.
+        let target = unsafe { libc::pthread_self() } as usize;
+        STACK_TOP.store(stack_top(), Ordering::SeqCst);
+        let (done, receiver) = mpsc::channel::<()>();
+        let thread = std::thread::spawn(move || {
+            let mut samples = Samples::new();
+            loop {
+                match receiver.recv_timeout(SAMPLE_INTERVAL) {
+                    Err(RecvTimeoutError::Timeout) => (),
+                    _ => return samples,
+                }
+                let sample = REQUESTED.fetch_add(1, Ordering::SeqCst) + 1;
+                if unsafe { libc::pthread_kill(target as libc::pthread_t, libc::SIGPROF) } != 0 {
+                    return samples;
+                }
+                if let Some(stack) = read_sample(sample) {
+                    *samples.entry(stack).or_default() += 1;
+                }
+            }
+        });
+        Self {
+            done: Some(done),
+            thread: Some(thread),
+            previous_action,
+        }
+    }
+
+    /// Stops sampling and returns the profile taken.
+    pub fn finish(mut self) -> Profile {
+        Profile {
+            samples: self.stop(),
+        }
+    }
+
+    fn stop(&mut self) -> Samples {
+        drop(self.done.take());
+        let samples = match self.thread.take() {
+            Some(thread) => thread.join().unwrap_or_default(),
+            None => Samples::new(),
+        };
+        unsafe {
+            libc::sigaction(libc::SIGPROF, &self.previous_action, std::ptr::null_mut());
+        }
+        samples
+    }
+}
+
+impl Drop for Sampler {
+    fn drop(&mut self) {
+        self.stop();
+    }
+}
+
+/// Waits for the `SIGPROF` handler to record `sample` and returns its
+/// stack, or `None` if it took too long or was overwritten meanwhile.
+fn read_sample(sample: usize) -> Option<Vec<usize>> {
+    let deadline = Instant::now() + SAMPLE_TIMEOUT;
+    let writes = loop {
+        let writes = WRITES.load(Ordering::SeqCst);
+        if writes % 2 == 0 && COMPLETED.load(Ordering::SeqCst) == sample {
+            break writes;
+        }
+        if Instant::now() >= deadline {
+            return None;
+        }
+        std::thread::yield_now();
+    };
+    let depth = DEPTH.load(Ordering::SeqCst);
+    let stack = FRAMES[..depth]
+        .iter()
+        .map(|frame| frame.load(Ordering::SeqCst))
+        .collect();
+    if WRITES.load(Ordering::SeqCst) != writes {
+        return None;
+    }
+    Some(stack)
+}
+
+extern "C" fn on_sigprof(
+    _signal: libc::c_int,
+    _info: *mut libc::siginfo_t,
+    context: *mut libc::c_void,
+) {
+    let sample = REQUESTED.load(Ordering::SeqCst);
+    WRITES.fetch_add(1, Ordering::SeqCst);
+    let depth = match unsafe { registers(context) } {
+        Some(registers) => unsafe { walk_frames(registers) },
+        None => 0,
+    };
+    DEPTH.store(depth, Ordering::SeqCst);
+    COMPLETED.store(sample, Ordering::SeqCst);
+    WRITES.fetch_add(1, Ordering::SeqCst);
+}
+
+/// Records in [`FRAMES`] the program counter of the interrupted code and
+/// the return addresses found by following the frame pointers from
+/// `registers`, and returns how many it recorded.
+///
+/// Each frame starts with the caller's frame pointer followed by the
+/// return address, on both x86-64 and AArch64. Only frames above the stack
+/// pointer and below [`STACK_TOP`], and each above the last, are read, so a
+/// register which doesn't hold a frame pointer can't make it read outside
+/// of the stack.
+unsafe fn walk_frames(registers: Registers) -> usize {
+    // Stored like a return address, which `wasm_function_name` expects.
+    FRAMES[0].store(registers.pc + 1, Ordering::SeqCst);
+    let top = STACK_TOP.load(Ordering::SeqCst);
+    let word = std::mem::size_of::<usize>();
+    let mut fp = registers.fp;
+    let mut depth = 1;
+    while depth < MAX_DEPTH
+        && fp >= registers.sp
+        && fp % word == 0
+        && fp.checked_add(2 * word).map_or(false, |end| end <= top)
+    {
+        let frame = fp as *const usize;
+        let (caller_fp, return_address) = (*frame, *frame.add(1));
+        if return_address == 0 {
+            break;
+        }
+        FRAMES[depth].store(return_address, Ordering::SeqCst);
+        depth += 1;
+        if caller_fp <= fp {
+            break;
+        }
+        fp = caller_fp;
+    }
+    depth
+}
+
+/// The registers of the interrupted code a stack walk starts from.
+struct Registers {
+    pc: usize,
+    sp: usize,
+    fp: usize,
+}
+
+/// The registers saved in the `ucontext_t` a `SA_SIGINFO` handler gets.
+#[cfg(all(target_os = "linux", target_arch = "x86_64"))]
+unsafe fn registers(context: *mut libc::c_void) -> Option<Registers> {
+    let context = &*(context as *const libc::ucontext_t);
+    let gregs = &context.uc_mcontext.gregs;
+    Some(Registers {
+        pc: gregs[libc::REG_RIP as usize] as usize,
+        sp: gregs[libc::REG_RSP as usize] as usize,
+        fp: gregs[libc::REG_RBP as usize] as usize,
+    })
+}
+
+/// The registers saved in the `ucontext_t` a `SA_SIGINFO` handler gets.
+#[cfg(all(target_os = "linux", target_arch = "aarch64"))]
+unsafe fn registers(context: *mut libc::c_void) -> Option<Registers> {
+    let context = &*(context as *const libc::ucontext_t);
+    Some(Registers {
+        pc: context.uc_mcontext.pc as usize,
+        sp: context.uc_mcontext.sp as usize,
+        fp: context.uc_mcontext.regs[29] as usize,
+    })
+}
+
+/// The registers saved in the `ucontext_t` a `SA_SIGINFO` handler gets.
+#[cfg(all(target_os = "macos", target_arch = "x86_64"))]
+unsafe fn registers(context: *mut libc::c_void) -> Option<Registers> {
+    let context = &*(context as *const libc::ucontext_t);
+    let state = &(*context.uc_mcontext).__ss;
+    Some(Registers {
+        pc: state.__rip as usize,
+        sp: state.__rsp as usize,
+        fp: state.__rbp as usize,
+    })
+}
+
+#[cfg(not(any(
+    all(
+        target_os = "linux",
+        any(target_arch = "x86_64", target_arch = "aarch64")
+    ),
+    all(target_os = "macos", target_arch = "x86_64"),
+)))]
+unsafe fn registers(_context: *mut libc::c_void) -> Option<Registers> {
+    None
+}
+
+/// The end of the stack of the current thread, the highest address in it.
+#[cfg(target_os = "linux")]
+fn stack_top() -> usize {
+    unsafe {
+        let mut attr: libc::pthread_attr_t = std::mem::zeroed();
+        if libc::pthread_getattr_np(libc::pthread_self(), &mut attr) != 0 {
+            return 0;
+        }
+        let mut addr = std::ptr::null_mut();
+        let mut size = 0;
+        let result = libc::pthread_attr_getstack(&attr, &mut addr, &mut size);
+        libc::pthread_attr_destroy(&mut attr);
+        if result != 0 {
+            return 0;
+        }
+        addr as usize + size
+    }
+}
+
+/// The end of the stack of the current thread, the highest address in it.
+#[cfg(target_os = "macos")]
+fn stack_top() -> usize {
+    unsafe { libc::pthread_get_stackaddr_np(libc::pthread_self()) as usize }
+}
+
+#[cfg(not(any(target_os = "linux", target_os = "macos")))]
+fn stack_top() -> usize {
+    0
+}
+
+/// The stacks sampled by a [`Sampler`].
+pub struct Profile {
+    samples: Samples,
+}
+
+impl Profile {
+    /// Writes the samples to `path` as collapsed stacks, the input of
+    /// flamegraph tools: one `outer;...;inner count` line per stack of Wasm
+    /// functions. Samples taken outside of Wasm code are left out.
+    pub fn write_collapsed(&self, path: &Path) -> Result<()> {
+        let mut stacks = HashMap::<String, u64>::new();
+        for (frames, count) in self.samples.iter() {
+            let names = frames
+                .iter()
+                .rev()
+                .filter_map(|&pc| wasm_function_name(pc))
+                .collect::<Vec<_>>();
+            if !names.is_empty() {
+                *stacks.entry(names.join(";")).or_default() += count;
+            }
+        }
+        let mut stacks = stacks.into_iter().collect::<Vec<_>>();
+        stacks.sort();
+        let collapsed = stacks
+            .iter()
+            .map(|(stack, count)| format!("{} {}\n", stack, count))
+            .collect::<String>();
+        std::fs::write(path, collapsed)
+            .with_context(|| format!("failed to write `{}`", path.display()))
+    }
+}
+
+/// The name of the Wasm function `pc` returns to, from the name section if
+/// it has one, or `None` if `pc` isn't in Wasm code.
+fn wasm_function_name(pc: usize) -> Option<String> {
+    // Return addresses point right after the call, which may be past the
+    // end of the function.
+    let pc = pc.checked_sub(1)?;
+    if FRAME_INFO.read().unwrap().should_process_frame(pc)? {
+        FRAME_INFO.write().unwrap().maybe_process_frame(pc);
+    }
+    let info = FRAME_INFO.read().unwrap().lookup_frame_info(pc)?;
+    Some(match info.function_name() {
+        Some(name) => name.replace(';', ":"),
+        None => format!("{}[{}]", info.module_name(), info.func_index()),
+    })
+}
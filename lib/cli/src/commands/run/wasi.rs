@@ -1,26 +1,273 @@
-use crate::utils::{parse_envvar, parse_mapdir};
-use anyhow::{Context, Result};
-use std::collections::BTreeSet;
-use std::path::PathBuf;
-use wasmer::{Instance, Module};
-use wasmer_wasi::{get_wasi_versions, WasiError, WasiState, WasiVersion};
+use super::coredump::write_coredump;
+use super::interrupt::InterruptWatcher;
+use super::preload::{check_unresolved_imports, describe, instantiate_preloads};
+#[cfg(unix)]
+use super::sampler::Sampler;
+use super::{dump_memory, load_input, MemoryInput};
+use crate::utils::{
+    check_host_dir, normalize_guest_path, parse_envvar, parse_fs_archive, parse_mapdir,
+    parse_mapfile, parse_preopen_fd, parse_stack_size, parse_stdio_file, parse_timestamp,
+    MapDirRights,
+};
+use crate::warning;
+use anyhow::{anyhow, bail, Context, Result};
+#[cfg(feature = "emscripten")]
+use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::File;
+use std::io::{Read, Write};
+use std::path::{Component, Path, PathBuf};
+use std::time::{Duration, Instant};
+use wasmer::{ChainableNamedResolver, ImportObject, Instance, Module, NamedResolver};
+use wasmer_wasi::{
+    generate_import_object_from_env, get_wasi_versions, LazyHostFile, Pipe, SyscallProfile,
+    WasiError, WasiFs, WasiState, WasiVersion, VIRTUAL_ROOT_FD,
+};
 
 use clap::Clap;
 
-#[derive(Debug, Clap, Clone)]
+/// How many non-WASI imports are listed by
+/// [`Wasi::check_non_wasi_imports`] when debug logging is disabled.
+const NON_WASI_IMPORTS_SHOWN: usize = 5;
+
+/// The name of the thread running the module when a stack size is set
+/// but no thread name.
+const DEFAULT_THREAD_NAME: &str = "wasmer-wasi";
+
+/// The file descriptor of the first pre-opened directory, right after the
+/// virtual root.
+const FIRST_PREOPEN_FD: u32 = VIRTUAL_ROOT_FD + 1;
+
+/// The host variables `--wasi-env-inherit-path` passes by default, which
+/// command-line programs commonly expect.
+const INHERITED_PATH_ENV: &[&str] = &["PATH", "HOME", "USER", "TERM", "LANG", "LC_ALL", "TZ"];
+
+/// A directory to pre-open: its alias, if any, the host directory and the
+/// rights given to the module.
+type Preopen<'a> = (Option<&'a str>, &'a Path, MapDirRights);
+
+#[derive(Debug, Clap, Clone, Default)]
 /// WASI Options
 pub struct Wasi {
     /// WASI pre-opened directory
     #[clap(long = "dir", name = "DIR", multiple = true, group = "wasi")]
     pre_opened_directories: Vec<PathBuf>,
 
-    /// Map a host directory to a different location for the Wasm module
-    #[clap(long = "mapdir", name = "GUEST_DIR:HOST_DIR", multiple = true, parse(try_from_str = parse_mapdir))]
-    mapped_dirs: Vec<(String, PathBuf)>,
+    /// Pre-open the current working directory as `.` for the Wasm module,
+    /// which is the same as `--mapdir .:<current directory>`
+    #[clap(long = "pre-open-current-dir")]
+    pre_open_current_dir: bool,
+
+    /// Map a host directory to a different location for the Wasm module,
+    /// optionally restricting its rights to a comma-separated list of
+    /// `read`, `write` and `create`.  A nested location like `/app/data`
+    /// doesn't need `/app` to be mapped as well
+    #[clap(long = "mapdir", name = "GUEST_DIR:HOST_DIR[:RIGHTS]", multiple = true, parse(try_from_str = parse_mapdir))]
+    mapped_dirs: Vec<(String, PathBuf, MapDirRights)>,
+
+    /// Create the host directories given to `--dir` and `--mapdir` which
+    /// don't exist, unless they are mapped without the `write` and `create`
+    /// rights
+    #[clap(long = "create-dirs")]
+    create_dirs: bool,
+
+    /// Take the `write` and `create` rights away from every directory
+    /// given to `--dir`, `--mapdir` and `--pre-open-current-dir`, whatever
+    /// rights `--mapdir` gives it, so the Wasm module can't modify the host
+    /// file system
+    #[clap(long = "fs-readonly")]
+    fs_readonly: bool,
+
+    /// Mount the contents of a tar archive as a read-only directory, kept
+    /// in memory, for the Wasm module
+    #[clap(long = "fs", name = "GUEST_DIR:ARCHIVE", multiple = true, parse(try_from_str = parse_fs_archive))]
+    fs_archives: Vec<(String, PathBuf)>,
+
+    /// Map a single host file for the Wasm module. It is read when the
+    /// module starts and placed alone in a read-only directory, kept in
+    /// memory, named by the first component of the guest path, so none of
+    /// its siblings on the host are visible
+    #[clap(long = "mapfile", name = "GUEST_PATH=HOST_FILE", multiple = true, parse(try_from_str = parse_mapfile))]
+    mapped_files: Vec<(String, PathBuf, PathBuf)>,
+
+    /// Fail instead of warning when a host directory is pre-opened more than
+    /// once, or inside another pre-opened directory
+    #[clap(long = "strict-preopens")]
+    strict_preopens: bool,
+
+    /// Give a pre-opened directory a fixed file descriptor. The other
+    /// directories from `--dir`, `--pre-open-current-dir` and `--mapdir`
+    /// fill the remaining descriptors, starting at 4, in the order they
+    /// are given
+    #[clap(long = "preopen-fd", name = "GUEST_PATH=FD", multiple = true, parse(try_from_str = parse_preopen_fd))]
+    preopen_fds: Vec<(String, u32)>,
+
+    /// Pass custom environment variables. A `KEY` without a value passes
+    /// the host's variable, if it is set
+    #[clap(long = "env", name = "KEY[=VALUE]", multiple = true, parse(try_from_str = parse_envvar))]
+    env_vars: Vec<(String, Option<String>)>,
+
+    /// Pass the host's `PATH`, `HOME`, `USER`, `TERM`, `LANG`, `LC_ALL` and
+    /// `TZ` variables which are set, rather than its whole environment.
+    /// Variables given to `--env` take precedence
+    #[clap(long = "wasi-env-inherit-path")]
+    wasi_env_inherit_path: bool,
+
+    /// The comma-separated variables `--wasi-env-inherit-path` passes,
+    /// instead of its default ones
+    #[clap(
+        long = "wasi-env-inherit-set",
+        name = "VARS",
+        multiple = true,
+        use_delimiter = true,
+        requires = "wasi-env-inherit-path"
+    )]
+    wasi_env_inherit_set: Vec<String>,
+
+    /// Pass every host variable whose name starts with the given prefix,
+    /// e.g. `MYAPP_`. Variables given to `--env` take precedence
+    #[clap(long = "env-passthrough-prefix", name = "ENV_PREFIX", multiple = true)]
+    env_passthrough_prefixes: Vec<String>,
+
+    /// Strip the prefix from the names of the variables passed with
+    /// `--env-passthrough-prefix`, e.g. passing `MYAPP_DEBUG` as `DEBUG`
+    #[clap(long = "env-strip-prefix", requires = "ENV_PREFIX")]
+    env_strip_prefix: bool,
+
+    /// Feed the given text to the Wasm module as its standard input
+    #[clap(long = "stdin-string", name = "TEXT")]
+    stdin_string: Option<String>,
+
+    /// Read the module's standard input from a host file, e.g. a FIFO. The
+    /// file is only opened when the module first reads from it, so a FIFO
+    /// without a writer doesn't keep the module from starting
+    #[clap(
+        long = "stdin",
+        name = "STDIN_FILE",
+        parse(from_os_str),
+        conflicts_with = "TEXT"
+    )]
+    stdin_file: Option<PathBuf>,
+
+    /// Bind a standard stream to a file of a pre-opened directory, given by
+    /// its path as the Wasm module sees it rather than by a host path, e.g.
+    /// `stdout=out/log.txt`. Output files are created if the directory
+    /// allows it, and truncated. No stream is rebound if any of the files
+    /// can't be
+    #[clap(long = "preopen-stdio-as-files", name = "STREAM=GUEST_PATH", multiple = true, parse(try_from_str = parse_stdio_file))]
+    stdio_files: Vec<(u32, String)>,
+
+    /// Limit how many files the Wasm module may have open at once, not
+    /// counting the standard streams and the pre-opened directories.
+    /// Opening more fails with `EMFILE`
+    #[clap(long = "max-open-files", name = "COUNT")]
+    max_open_files: Option<u32>,
+
+    /// Refuse to open a symlink when the WASI module doesn't ask to follow it
+    #[clap(long = "no-follow-symlinks")]
+    no_follow_symlinks: bool,
 
-    /// Pass custom environment variables
-    #[clap(long = "env", name = "KEY=VALUE", multiple = true, parse(try_from_str = parse_envvar))]
-    env_vars: Vec<(String, String)>,
+    /// Allow symlinks inside pre-opened directories to point outside of them
+    #[clap(long = "allow-symlink-escape")]
+    allow_symlink_escape: bool,
+
+    /// Make the WASI calls that observe the host, such as reading the
+    /// clocks or random bytes, return reproducible results
+    #[clap(long = "deterministic")]
+    deterministic: bool,
+
+    /// Seed the random bytes returned to the WASI module, making them
+    /// reproducible across runs
+    #[clap(long = "random-seed", name = "SEED")]
+    random_seed: Option<u64>,
+
+    /// Shift the wall clock seen by the Wasm module by the given number of
+    /// seconds, which may be negative
+    #[clap(long = "clock-offset", name = "SECONDS", allow_hyphen_values = true)]
+    clock_offset: Option<i64>,
+
+    /// Freeze all the clocks seen by the Wasm module at the given RFC 3339
+    /// date, e.g. `2001-02-03T04:05:06Z`
+    #[clap(
+        long = "freeze-time",
+        name = "DATE",
+        parse(try_from_str = parse_timestamp),
+        conflicts_with = "SECONDS"
+    )]
+    freeze_time: Option<u64>,
+
+    /// Stop the Wasm module once it has run for the given duration, e.g.
+    /// `10s` or `500ms`. This only stops a module when it calls
+    /// `sched_yield` or sleeps in `poll_oneoff`, and so does Ctrl-C while a
+    /// timeout is set
+    #[clap(long = "timeout", name = "DURATION", parse(try_from_str = humantime::parse_duration))]
+    timeout: Option<Duration>,
+
+    /// Bound the time each WASI syscall may take, e.g. `500ms`, unlike
+    /// `--timeout` which bounds the whole run. A syscall returning late
+    /// makes the module trap, and one still sleeping in `poll_oneoff` is
+    /// woken up to do so
+    #[clap(long = "host-function-timeout", name = "CALL_DURATION", parse(try_from_str = humantime::parse_duration))]
+    host_function_timeout: Option<Duration>,
+
+    /// Run the Wasm module on a new thread whose stack has the given size in
+    /// bytes, for deeply recursive programs
+    #[clap(long = "stack-size", name = "BYTES", parse(try_from_str = parse_stack_size))]
+    stack_size: Option<usize>,
+
+    /// The name of the thread the module runs on, only set through
+    /// [`WasiBuilder::thread_name`]
+    #[clap(skip)]
+    thread_name: Option<String>,
+
+    /// The modules the module imports from by namespace, set from
+    /// `wasmer run --preload` or through [`WasiBuilder::preload`]
+    #[clap(skip)]
+    preloads: Vec<(String, Module)>,
+
+    /// Don't print warnings, set from `wasmer run --quiet` or through
+    /// [`WasiBuilder::quiet`]
+    #[clap(skip)]
+    quiet: bool,
+
+    /// The file to write the module's memory to once `_start` returns, set
+    /// from `wasmer run --memory-dump` or through [`WasiBuilder::memory_dump`]
+    #[clap(skip)]
+    memory_dump: Option<PathBuf>,
+
+    /// The file to copy into the module's memory before `_start` runs, set
+    /// from `wasmer run --input` or through [`WasiBuilder::input`]
+    #[clap(skip)]
+    input: Option<MemoryInput>,
+
+    /// Write a Wasm coredump to the given file if the module traps
+    #[clap(long = "coredump", name = "COREDUMP", parse(from_os_str))]
+    coredump: Option<PathBuf>,
+
+    /// Print the number of calls to each WASI syscall and the time spent in
+    /// them once the module returns
+    #[clap(long = "log-syscall-timing")]
+    log_syscall_timing: bool,
+
+    /// Write the syscall timings to the given file as CSV instead of
+    /// printing them, or the samples of `--profile flamegraph` as
+    /// collapsed stacks
+    #[clap(long = "profile-out", name = "PROFILE_OUT", parse(from_os_str))]
+    profile_out: Option<PathBuf>,
+
+    /// Profile the Wasm module while it runs. `flamegraph` samples the
+    /// Wasm functions on the stack every millisecond and writes them to
+    /// the `--profile-out` file as collapsed stacks, the input of
+    /// flamegraph tools. Sampling slows the module down
+    #[clap(
+        long = "profile",
+        name = "PROFILER",
+        possible_values = &["flamegraph"],
+        parse(try_from_str = parse_profiler),
+        requires = "PROFILE_OUT",
+        conflicts_with = "log-syscall-timing"
+    )]
+    profiler: Option<Profiler>,
 
     /// Enable experimental IO devices
     #[cfg(feature = "experimental-io-devices")]
@@ -34,10 +281,64 @@ pub struct Wasi {
     /// Require WASI modules to only import 1 version of WASI.
     #[clap(long = "deny-multiple-wasi-versions")]
     pub deny_multiple_wasi_versions: bool,
+
+    /// Provide only the given version of WASI, `snapshot0` or `snapshot1`,
+    /// failing if the module imports another one
+    #[clap(
+        long = "wasi-version",
+        name = "WASI_VERSION",
+        possible_values = &["snapshot0", "snapshot1"],
+        parse(try_from_str = parse_wasi_version)
+    )]
+    wasi_version: Option<WasiVersion>,
+}
+
+/// The profilers `--profile` can run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Profiler {
+    /// Samples the stack and writes collapsed stacks for flamegraph tools.
+    Flamegraph,
+}
+
+/// Parses the name of a profiler given to `--profile`.
+fn parse_profiler(profiler: &str) -> Result<Profiler> {
+    match profiler {
+        "flamegraph" => Ok(Profiler::Flamegraph),
+        _ => bail!("The profiler `{}` does not exist.", profiler),
+    }
+}
+
+/// Parses the name of a WASI version given to `--wasi-version`.
+fn parse_wasi_version(version: &str) -> Result<WasiVersion> {
+    match version {
+        "snapshot0" => Ok(WasiVersion::Snapshot0),
+        "snapshot1" => Ok(WasiVersion::Snapshot1),
+        _ => bail!("The WASI version `{}` does not exist.", version),
+    }
 }
 
-#[allow(dead_code)]
 impl Wasi {
+    /// Creates a builder to configure WASI without parsing command line
+    /// arguments, to run WASI modules from other tools.
+    ///
+    /// ```no_run
+    /// # use wasmer::Module;
+    /// # use wasmer_cli::commands::Wasi;
+    /// # fn run(module: Module) -> anyhow::Result<()> {
+    /// Wasi::builder()
+    ///     .dir(".")
+    ///     .mapdir("data", "/var/data")
+    ///     .env("GREETING", "hello")
+    ///     .allow_multiple_wasi_versions(true)
+    ///     .build()
+    ///     .execute(module, "hello".to_string(), vec!["--verbose".to_string()], false)?;
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn builder() -> WasiBuilder {
+        WasiBuilder::default()
+    }
+
     /// Gets the WASI version (if any) for the provided module
     pub fn get_versions(module: &Module) -> Option<BTreeSet<WasiVersion>> {
         // Get the wasi version in strict mode, so no other imports are
@@ -45,6 +346,60 @@ impl Wasi {
         get_wasi_versions(&module, true)
     }
 
+    /// Gets the WASI versions imported by the module if all of its other
+    /// imports come from the preloaded modules.
+    pub(crate) fn get_linked_versions(&self, module: &Module) -> Option<BTreeSet<WasiVersion>> {
+        if self.preloads.is_empty() {
+            return Self::get_versions(module);
+        }
+        let versions = get_wasi_versions(module, false).filter(|v| !v.is_empty())?;
+        let linked = module.imports().all(|import| {
+            self.is_preloaded(import.module())
+                || versions
+                    .iter()
+                    .any(|v| v.get_namespace_str() == import.module())
+        });
+        if linked {
+            Some(versions)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a copy of these options running the module with the given
+    /// preloaded modules.
+    pub(crate) fn with_preloads(&self, preloads: Vec<(String, Module)>) -> Self {
+        let mut wasi = self.clone();
+        wasi.preloads = preloads;
+        wasi
+    }
+
+    /// Returns a copy of these options which doesn't print warnings if
+    /// `quiet` is set.
+    pub(crate) fn with_quiet(mut self, quiet: bool) -> Self {
+        self.quiet = quiet;
+        self
+    }
+
+    /// Returns a copy of these options writing the module's memory to
+    /// `path` once `_start` returns, if given.
+    pub(crate) fn with_memory_dump(mut self, path: Option<PathBuf>) -> Self {
+        self.memory_dump = path;
+        self
+    }
+
+    /// Returns a copy of these options copying `input` into the module's
+    /// memory before `_start` runs, if given.
+    pub(crate) fn with_input(mut self, input: Option<MemoryInput>) -> Self {
+        self.input = input;
+        self
+    }
+
+    /// Checks if the imports from `namespace` come from a preloaded module.
+    fn is_preloaded(&self, namespace: &str) -> bool {
+        self.preloads.iter().any(|(name, _)| name == namespace)
+    }
+
     /// Checks if a given module has any WASI imports at all.
     pub fn has_wasi_imports(module: &Module) -> bool {
         // Get the wasi version in non-strict mode, so no other imports
@@ -52,46 +407,1397 @@ impl Wasi {
         get_wasi_versions(&module, false).is_some()
     }
 
+    /// The directories from `--dir`, `--pre-open-current-dir` and
+    /// `--mapdir` by guest path, and the variables from `--env` which have
+    /// a value, for running an Emscripten module with the same options.
+    ///
+    /// Emscripten modules see the host's environment, so the variables
+    /// without a value are already there.
+    #[cfg(feature = "emscripten")]
+    pub(crate) fn emscripten_options(
+        &self,
+    ) -> Result<(HashMap<String, PathBuf>, Vec<(String, String)>)> {
+        let mut mapped_dirs = HashMap::new();
+        for dir in self.pre_opened_directories.iter() {
+            mapped_dirs.insert(dir.to_string_lossy().into_owned(), dir.clone());
+        }
+        if self.pre_open_current_dir {
+            let current_dir =
+                std::env::current_dir().context("failed to get the current directory")?;
+            mapped_dirs.insert(".".to_string(), current_dir);
+        }
+        for (alias, host_dir, _) in self.mapped_dirs.iter() {
+            mapped_dirs.insert(alias.clone(), host_dir.clone());
+        }
+        for host_dir in mapped_dirs.values() {
+            check_host_dir(host_dir)?;
+        }
+        let env_vars = self
+            .env_vars
+            .iter()
+            .filter_map(|(key, value)| Some((key.clone(), value.clone()?)))
+            .collect();
+        Ok((mapped_dirs, env_vars))
+    }
+
+    /// Fails with a list of the offending imports if the module imports
+    /// WASI along with functions that neither WASI nor the preloaded
+    /// modules provide.
+    ///
+    /// Only the first few imports are listed unless debug logging is
+    /// enabled, e.g. with `--log-level debug`.
+    pub fn check_non_wasi_imports(&self, module: &Module) -> Result<()> {
+        if self.get_linked_versions(module).is_some() || !Self::has_wasi_imports(module) {
+            return Ok(());
+        }
+        let wasi_namespaces = [
+            WasiVersion::Snapshot0.get_namespace_str(),
+            WasiVersion::Snapshot1.get_namespace_str(),
+        ];
+        let non_wasi_imports = module
+            .imports()
+            .functions()
+            .filter(|import| {
+                !wasi_namespaces.contains(&import.module()) && !self.is_preloaded(import.module())
+            })
+            .map(|import| format!("`{}`.`{}`", import.module(), import.name()))
+            .collect::<Vec<_>>();
+        if non_wasi_imports.is_empty() {
+            return Ok(());
+        }
+
+        let limit = if log::log_enabled!(log::Level::Debug) {
+            non_wasi_imports.len()
+        } else {
+            NON_WASI_IMPORTS_SHOWN
+        };
+
+        let mut message = format!(
+            "This module imports WASI along with {} function(s) that WASI doesn't provide:",
+            non_wasi_imports.len()
+        );
+        for import in non_wasi_imports.iter().take(limit) {
+            message.push_str("\n    ");
+            message.push_str(import);
+        }
+        if non_wasi_imports.len() > limit {
+            message.push_str(&format!(
+                "\n    ... and {} more",
+                non_wasi_imports.len() - limit
+            ));
+            message.push_str(" (pass `--log-level debug` to list them all)");
+        }
+        bail!(
+            "{}\nMake sure the module was built for a WASI target such as `wasm32-wasi`.",
+            message
+        )
+    }
+
     /// Helper function for executing Wasi from the `Run` command.
-    pub fn execute(&self, module: Module, program_name: String, args: Vec<String>) -> Result<()> {
+    ///
+    /// With `dry_run`, the module is only instantiated and its WASI versions
+    /// and entry point are reported instead of calling it.
+    ///
+    /// With a stack size or a thread name set, the module runs on a new
+    /// thread with that stack and name, and this waits for it to finish. A
+    /// panic on that thread, e.g. in a host function, is then returned as
+    /// an error.
+    ///
+    /// If the module exits with `proc_exit`, the process exits with the
+    /// same code.
+    pub fn execute(
+        &self,
+        module: Module,
+        program_name: String,
+        args: Vec<String>,
+        dry_run: bool,
+    ) -> Result<()> {
+        match self.execute_with_exit_code(module, program_name, args, dry_run)? {
+            0 => Ok(()),
+            exit_code => std::process::exit(exit_code as _),
+        }
+    }
+
+    /// Like [`Wasi::execute`], but returns the exit code of the module,
+    /// which is 0 unless it exits with `proc_exit`, instead of exiting the
+    /// process.
+    pub fn execute_with_exit_code(
+        &self,
+        module: Module,
+        program_name: String,
+        args: Vec<String>,
+        dry_run: bool,
+    ) -> Result<u32> {
+        self.check_profile_options()?;
+        if self.stack_size.is_none() && self.thread_name.is_none() {
+            return self.execute_on_current_thread(module, program_name, args, dry_run, None);
+        }
+        let name = self
+            .thread_name
+            .clone()
+            .unwrap_or_else(|| DEFAULT_THREAD_NAME.to_string());
+        let mut builder = std::thread::Builder::new().name(name.clone());
+        if let Some(stack_size) = self.stack_size {
+            builder = builder.stack_size(stack_size);
+        }
+        let wasi = self.clone();
+        let result = builder
+            .spawn(move || {
+                wasi.execute_on_current_thread(module, program_name, args, dry_run, None)
+            })
+            .with_context(|| format!("failed to spawn the `{}` thread", name))?
+            .join();
+        match result {
+            Ok(result) => result,
+            Err(panic) => {
+                let message = if let Some(message) = panic.downcast_ref::<&str>() {
+                    *message
+                } else if let Some(message) = panic.downcast_ref::<String>() {
+                    message.as_str()
+                } else {
+                    "unknown panic"
+                };
+                bail!("The `{}` thread panicked: {}", name, message)
+            }
+        }
+    }
+
+    /// Like [`Wasi::execute_with_exit_code`], but also provides the module
+    /// with the host functions and other imports in `extra_imports`, for
+    /// modules which import from the embedder along with WASI.
+    ///
+    /// The extra imports can't provide anything WASI does. As they can't be
+    /// sent to another thread, the module always runs on the current one,
+    /// so a stack size or a thread name can't be set.
+    pub fn execute_with_imports(
+        &self,
+        module: Module,
+        program_name: String,
+        args: Vec<String>,
+        extra_imports: ImportObject,
+    ) -> Result<u32> {
+        self.check_profile_options()?;
+        if self.stack_size.is_some() || self.thread_name.is_some() {
+            bail!("Extra imports can't be provided to a module running on its own thread, as with a stack size or a thread name set");
+        }
+        self.execute_on_current_thread(module, program_name, args, false, Some(&extra_imports))
+    }
+
+    /// Checks that each standard stream is bound to a guest file at most
+    /// once, and not to a host file too.
+    fn check_stdio_files(&self) -> Result<()> {
+        for (index, (fd, _)) in self.stdio_files.iter().enumerate() {
+            let stream = match fd {
+                0 => "stdin",
+                1 => "stdout",
+                2 => "stderr",
+                _ => bail!("file descriptor {} is not a standard stream", fd),
+            };
+            if self.stdio_files[..index]
+                .iter()
+                .any(|(other, _)| other == fd)
+            {
+                bail!("`--preopen-stdio-as-files` binds {} more than once", stream);
+            }
+            if *fd == 0 && (self.stdin_string.is_some() || self.stdin_file.is_some()) {
+                bail!("`--preopen-stdio-as-files` can't bind stdin along with `--stdin` or `--stdin-string`");
+            }
+        }
+        Ok(())
+    }
+
+    /// Fails if the profiling options given can't be used together or on
+    /// this platform.
+    fn check_profile_options(&self) -> Result<()> {
+        if self.profile_out.is_some() && !self.log_syscall_timing && self.profiler.is_none() {
+            bail!("`--profile-out` needs `--log-syscall-timing` or `--profile`");
+        }
+        #[cfg(not(unix))]
+        if self.profiler.is_some() {
+            bail!("`--profile` is only supported on Unix");
+        }
+        Ok(())
+    }
+
+    /// The host variables `--wasi-env-inherit-path` passes, leaving out
+    /// those given to `--env`.
+    fn inherited_path_env(&self) -> Vec<&str> {
+        if !self.wasi_env_inherit_path {
+            return Vec::new();
+        }
+        let names = if self.wasi_env_inherit_set.is_empty() {
+            INHERITED_PATH_ENV.to_vec()
+        } else {
+            self.wasi_env_inherit_set
+                .iter()
+                .map(String::as_str)
+                .collect()
+        };
+        names
+            .into_iter()
+            .filter(|name| !self.env_vars.iter().any(|(key, _)| key == name))
+            .collect()
+    }
+
+    /// The host variables `--env-passthrough-prefix` passes, by the name
+    /// the module sees, sorted by it. Leaves out those given to `--env` or
+    /// already passed by `--wasi-env-inherit-path`.
+    fn passthrough_env(&self, inherited: &[&str]) -> Vec<(String, String)> {
+        if self.env_passthrough_prefixes.is_empty() {
+            return Vec::new();
+        }
+        let mut vars = std::env::vars_os()
+            .filter_map(|(key, value)| Some((key.into_string().ok()?, value.into_string().ok()?)))
+            .filter_map(|(key, value)| {
+                let prefix = self
+                    .env_passthrough_prefixes
+                    .iter()
+                    .find(|prefix| key.starts_with(prefix.as_str()))?;
+                let name = if self.env_strip_prefix {
+                    key[prefix.len()..].to_string()
+                } else {
+                    key
+                };
+                Some((name, value))
+            })
+            .filter(|(name, _)| {
+                !name.is_empty()
+                    && !inherited.contains(&name.as_str())
+                    && !self.env_vars.iter().any(|(key, _)| key == name)
+            })
+            .collect::<Vec<_>>();
+        vars.sort();
+        vars.dedup_by(|a, b| a.0 == b.0);
+        vars
+    }
+
+    fn execute_on_current_thread(
+        &self,
+        module: Module,
+        program_name: String,
+        args: Vec<String>,
+        dry_run: bool,
+        extra_imports: Option<&ImportObject>,
+    ) -> Result<u32> {
         let args = args.iter().cloned().map(|arg| arg.into_bytes());
 
-        let mut wasi_state_builder = WasiState::new(program_name);
+        let mut wasi_state_builder = WasiState::new(&program_name);
+        wasi_state_builder.args(args);
+        let inherited = self.inherited_path_env();
+        for &key in inherited.iter() {
+            if let Ok(value) = std::env::var(key) {
+                wasi_state_builder.env(key, value);
+            }
+        }
+        for (key, value) in self.passthrough_env(&inherited) {
+            wasi_state_builder.env(key, value);
+        }
+        for (key, value) in self.env_vars.iter() {
+            match value {
+                Some(value) => {
+                    wasi_state_builder.env(key, value);
+                }
+                None => {
+                    if let Ok(value) = std::env::var(key) {
+                        wasi_state_builder.env(key, value);
+                    }
+                }
+            }
+        }
+        let current_dir = if self.pre_open_current_dir {
+            Some(std::env::current_dir().context("failed to get the current directory")?)
+        } else {
+            None
+        };
+        // The guest sees the directories from `--dir` under their host path,
+        // but with forward slashes on Windows too.
+        let dir_names = self
+            .pre_opened_directories
+            .iter()
+            .map(|dir| normalize_guest_path(&dir.to_string_lossy()))
+            .collect::<Vec<_>>();
+        let mut preopens = Vec::new();
+        for (dir, name) in self.pre_opened_directories.iter().zip(dir_names.iter()) {
+            let alias = if cfg!(windows) {
+                Some(name.as_str())
+            } else {
+                None
+            };
+            preopens.push((alias, dir.as_path(), MapDirRights::default()));
+        }
+        if let Some(current_dir) = &current_dir {
+            preopens.push((Some("."), current_dir.as_path(), MapDirRights::default()));
+        }
+        for (alias, host_dir, rights) in self.mapped_dirs.iter() {
+            preopens.push((Some(alias.as_str()), host_dir.as_path(), *rights));
+        }
+        if self.fs_readonly {
+            for (_, _, rights) in preopens.iter_mut() {
+                rights.write = false;
+                rights.create = false;
+            }
+        }
+        for (_, host_dir, rights) in preopens.iter() {
+            if self.create_dirs && !host_dir.exists() && (rights.write || rights.create) {
+                std::fs::create_dir_all(host_dir)
+                    .with_context(|| format!("failed to create `{}`", host_dir.display()))?;
+            }
+            check_host_dir(host_dir)?;
+        }
+        let preopens = self.dedup_preopens(preopens)?;
+        for (alias, host_dir, rights) in self.order_preopens(preopens)? {
+            wasi_state_builder.preopen(|p| {
+                p.directory(host_dir)
+                    .read(rights.read)
+                    .write(rights.write)
+                    .create(rights.create);
+                if let Some(alias) = alias {
+                    p.alias(alias);
+                }
+                p
+            })?;
+        }
+
+        if let Some(text) = &self.stdin_string {
+            let mut stdin = Pipe::new();
+            stdin.write_all(text.as_bytes())?;
+            wasi_state_builder.stdin(Box::new(stdin));
+        } else if let Some(path) = &self.stdin_file {
+            // Only stat the file: opening a FIFO would block.
+            if !path.exists() {
+                bail!("the stdin file `{}` does not exist", path.display());
+            }
+            wasi_state_builder.stdin(Box::new(LazyHostFile::new(path.clone())));
+        }
+        self.check_stdio_files()?;
+
         wasi_state_builder
-            .args(args)
-            .envs(self.env_vars.clone())
-            .preopen_dirs(self.pre_opened_directories.clone())?
-            .map_dirs(self.mapped_dirs.clone())?;
+            .strict_symlink_follow(self.no_follow_symlinks)
+            .allow_symlink_escape(self.allow_symlink_escape);
+        if let Some(max) = self.max_open_files {
+            wasi_state_builder.max_open_files(max);
+        }
+        if let Some(seed) = self.random_seed {
+            wasi_state_builder.random_seed(seed);
+        } else if self.deterministic {
+            wasi_state_builder.random_seed(0);
+        }
+        if let Some(time) = self.freeze_time {
+            wasi_state_builder.freeze_time(time);
+        } else if self.deterministic {
+            wasi_state_builder.freeze_time(0);
+        }
+        if let Some(offset) = self.clock_offset {
+            wasi_state_builder.clock_offset(offset.saturating_mul(1_000_000_000));
+        }
 
+        let mut archives = self
+            .fs_archives
+            .iter()
+            .map(|(alias, archive)| Ok((alias.clone(), read_fs_archive(archive)?)))
+            .collect::<Result<Vec<_>>>()?;
+        let mut mapped_files = BTreeMap::<_, Vec<_>>::new();
+        for (alias, path, host_file) in self.mapped_files.iter() {
+            let contents = read_mapped_file(host_file)?;
+            mapped_files
+                .entry(alias.clone())
+                .or_default()
+                .push((path.clone(), contents));
+        }
+        archives.extend(mapped_files);
+        let stdio_files = self.stdio_files.clone();
         #[cfg(feature = "experimental-io-devices")]
-        {
-            if self.enable_experimental_io_devices {
-                wasi_state_builder
-                    .setup_fs(Box::new(wasmer_wasi_experimental_io_devices::initialize));
+        let enable_experimental_io_devices = self.enable_experimental_io_devices;
+        wasi_state_builder.setup_fs(Box::new(move |fs: &mut WasiFs| {
+            for (alias, files) in archives.iter() {
+                fs.preopen_in_memory_dir(alias.clone(), files)?;
             }
-        }
+            // After the directories in memory, which can hold the files.
+            fs.open_stdio_at_guest_paths(&stdio_files)?;
+            #[cfg(feature = "experimental-io-devices")]
+            {
+                if enable_experimental_io_devices {
+                    wasmer_wasi_experimental_io_devices::initialize(fs)?;
+                }
+            }
+            Ok(())
+        }));
 
         let mut wasi_env = wasi_state_builder.finalize()?;
-        let resolver = wasi_env.import_object_for_all_wasi_versions(&module)?;
+        let profile = if self.log_syscall_timing {
+            Some(wasi_env.enable_syscall_profiling())
+        } else {
+            None
+        };
+        let syscall_timeout = self
+            .host_function_timeout
+            .map(|timeout| wasi_env.set_syscall_timeout(timeout));
+        let instantiate_span = tracing::info_span!("instantiate");
+        let instantiating = instantiate_span.enter();
+        let instantiation_start = Instant::now();
+        let resolver: Box<dyn NamedResolver> = match self.wasi_version {
+            Some(version) => {
+                Self::check_wasi_version(&module, version)?;
+                Box::new(generate_import_object_from_env(
+                    module.store(),
+                    wasi_env.clone(),
+                    version,
+                ))
+            }
+            None => wasi_env.import_object_for_all_wasi_versions(&module)?,
+        };
+        let resolver: Box<dyn NamedResolver> = match extra_imports {
+            Some(extra_imports) => {
+                check_import_collisions(&*resolver, extra_imports)?;
+                Box::new(resolver.chain_back(extra_imports.clone()))
+            }
+            None => resolver,
+        };
+        let resolver: Box<dyn NamedResolver> = if self.preloads.is_empty() {
+            resolver
+        } else {
+            // WASI comes first, so a preloaded module can't shadow it.
+            let resolver = resolver.chain_back(instantiate_preloads(&self.preloads)?);
+            let providers = format!("WASI and {}", describe(&self.preloads));
+            check_unresolved_imports(&module, &resolver, &providers)?;
+            Box::new(resolver)
+        };
         let instance = Instance::new(&module, &resolver)?;
+        tracing::info!(
+            "Instantiated the module with WASI {} in {:?}",
+            describe_wasi_versions(&module),
+            instantiation_start.elapsed()
+        );
+        drop(instantiating);
+
+        if dry_run {
+            return Self::report_dry_run(&module, &instance).map(|()| 0);
+        }
 
+        if let Some(input) = &self.input {
+            load_input(&instance, input)?;
+        }
         let start = instance.exports.get_function("_start")?;
+        // Without a timeout to watch, Ctrl-C keeps killing the process.
+        let watcher = if self.timeout.is_some() || syscall_timeout.is_some() {
+            Some(InterruptWatcher::start(
+                wasi_env.interrupt_handle(),
+                self.timeout,
+                syscall_timeout,
+            ))
+        } else {
+            None
+        };
+        #[cfg(unix)]
+        let sampler = self.profiler.map(|Profiler::Flamegraph| Sampler::start());
+        let start_span = tracing::info_span!("start");
+        let running = start_span.enter();
+        let execution_start = Instant::now();
         let result = start.call(&[]);
+        drop(watcher);
+        tracing::info!(
+            "`_start` {} after {:?}",
+            if result.is_ok() { "returned" } else { "failed" },
+            execution_start.elapsed()
+        );
+        drop(running);
+        if let Some(profile) = &profile {
+            self.report_syscall_timing(profile)?;
+        }
+        #[cfg(unix)]
+        if let (Some(sampler), Some(path)) = (sampler, &self.profile_out) {
+            sampler.finish().write_collapsed(path)?;
+        }
+        // The memory is dumped even if the module traps, but the trap is
+        // reported first.
+        let dumped = match &self.memory_dump {
+            Some(path) => dump_memory(&instance, path),
+            None => Ok(()),
+        };
 
         match result {
-            Ok(_) => Ok(()),
+            Ok(_) => dumped.map(|()| 0),
             Err(err) => {
                 let err: anyhow::Error = match err.downcast::<WasiError>() {
-                    Ok(WasiError::Exit(exit_code)) => {
-                        // We should exit with the provided exit code
-                        std::process::exit(exit_code as _);
-                    }
+                    Ok(WasiError::Exit(exit_code)) => return dumped.map(|()| exit_code),
+                    Ok(WasiError::SyscallTimeout(syscall)) => anyhow!(
+                        "The WASI syscall `{}` took longer than the `--host-function-timeout` of {}",
+                        syscall,
+                        humantime::format_duration(self.host_function_timeout.unwrap_or_default())
+                    ),
                     Ok(err) => err.into(),
-                    Err(err) => err.into(),
+                    Err(err) => {
+                        if let Some(path) = &self.coredump {
+                            let module_name = module.name().unwrap_or(&program_name);
+                            let memory = instance.exports.get_memory("memory").ok();
+                            write_coredump(path, &program_name, module_name, memory, &err)?;
+                        }
+                        err.into()
+                    }
                 };
                 Err(err)
             }
         }
         .with_context(|| "failed to run WASI `_start` function")
     }
+
+    /// Fails if the module imports a version of WASI other than the one
+    /// forced with `--wasi-version`.
+    fn check_wasi_version(module: &Module, version: WasiVersion) -> Result<()> {
+        let versions = get_wasi_versions(module, false).unwrap_or_default();
+        if let Some(other) = versions.iter().find(|v| **v != version) {
+            bail!(
+                "This module imports WASI `{}` but `--wasi-version` only provides `{}`",
+                other.get_namespace_str(),
+                version.get_namespace_str()
+            );
+        }
+        Ok(())
+    }
+
+    /// Drops the directories pre-opened more than once, keeping the first
+    /// occurrence, and warns about them and about the directories
+    /// pre-opened inside another one. With `--strict-preopens`, fails
+    /// instead.
+    ///
+    /// Host paths are compared once canonicalized, so `data`, `./data` and
+    /// a symlink to it are the same directory.
+    fn dedup_preopens<'a>(&self, preopens: Vec<Preopen<'a>>) -> Result<Vec<Preopen<'a>>> {
+        let mut kept: Vec<(PathBuf, Preopen<'a>)> = Vec::new();
+        let mut problems = Vec::new();
+        for preopen in preopens {
+            let canonical = preopen
+                .1
+                .canonicalize()
+                .with_context(|| format!("failed to canonicalize `{}`", preopen.1.display()))?;
+            if let Some((_, first)) = kept.iter().find(|(path, _)| *path == canonical) {
+                problems.push(format!(
+                    "`{}` is the same host directory as `{}`, so it is not pre-opened again",
+                    guest_path(&preopen),
+                    guest_path(first)
+                ));
+                continue;
+            }
+            for (path, other) in kept.iter() {
+                let (inner, outer) = if canonical.starts_with(path) {
+                    (&preopen, other)
+                } else if path.starts_with(&canonical) {
+                    (other, &preopen)
+                } else {
+                    continue;
+                };
+                problems.push(format!(
+                    "`{}` is inside the host directory of `{}`, which is also pre-opened",
+                    guest_path(inner),
+                    guest_path(outer)
+                ));
+            }
+            kept.push((canonical, preopen));
+        }
+        if self.strict_preopens && !problems.is_empty() {
+            bail!("{}\n(`--strict-preopens` is enabled)", problems.join("\n"));
+        }
+        if !self.quiet {
+            for problem in problems {
+                warning!("{}", problem);
+            }
+        }
+        Ok(kept.into_iter().map(|(_, preopen)| preopen).collect())
+    }
+
+    /// Orders the directories to pre-open so that each one pinned with
+    /// `--preopen-fd` gets its file descriptor. WASI hands out descriptors
+    /// in the order the directories are pre-opened, starting at
+    /// [`FIRST_PREOPEN_FD`], and the unpinned directories keep their
+    /// relative order.
+    fn order_preopens<'a>(&self, preopens: Vec<Preopen<'a>>) -> Result<Vec<Preopen<'a>>> {
+        let mut slots: Vec<Option<Preopen<'a>>> = vec![None; preopens.len()];
+        let mut unpinned = Vec::new();
+        for preopen in preopens {
+            let path = guest_path(&preopen);
+            let fd = self
+                .preopen_fds
+                .iter()
+                .find(|(pinned, _)| pinned.trim_start_matches('/') == path)
+                .map(|(_, fd)| *fd);
+            let fd = match fd {
+                Some(fd) => fd,
+                None => {
+                    unpinned.push(preopen);
+                    continue;
+                }
+            };
+            let slot = fd
+                .checked_sub(FIRST_PREOPEN_FD)
+                .and_then(|index| slots.get_mut(index as usize));
+            match slot {
+                Some(slot @ None) => *slot = Some(preopen),
+                Some(Some(other)) => bail!(
+                    "Both `{}` and `{}` are pinned to the file descriptor {}",
+                    guest_path(other),
+                    path,
+                    fd
+                ),
+                None => bail!(
+                    "Can't pin `{}` to the file descriptor {}; pre-opened directories get the descriptors {} to {}",
+                    path,
+                    fd,
+                    FIRST_PREOPEN_FD,
+                    FIRST_PREOPEN_FD as usize + slots.len() - 1
+                ),
+            }
+        }
+        for (pinned, _) in self.preopen_fds.iter() {
+            let pinned = pinned.trim_start_matches('/');
+            let found = slots
+                .iter()
+                .flatten()
+                .any(|preopen| guest_path(preopen) == pinned);
+            if !found {
+                bail!(
+                    "`{}` is pinned with `--preopen-fd` but is not pre-opened",
+                    pinned
+                );
+            }
+        }
+        let mut unpinned = unpinned.into_iter();
+        Ok(slots
+            .into_iter()
+            .map(|slot| slot.or_else(|| unpinned.next()).unwrap())
+            .collect())
+    }
+
+    /// Prints the calls made to each syscall, or writes them as CSV to the
+    /// `--profile-out` file.
+    fn report_syscall_timing(&self, profile: &SyscallProfile) -> Result<()> {
+        let stats = profile.stats();
+        if let Some(path) = &self.profile_out {
+            let mut csv = String::from("syscall,calls,nanoseconds\n");
+            for (syscall, stats) in stats.iter() {
+                csv.push_str(&format!(
+                    "{},{},{}\n",
+                    syscall,
+                    stats.calls,
+                    stats.time.as_nanos()
+                ));
+            }
+            return std::fs::write(path, csv)
+                .with_context(|| format!("failed to write `{}`", path.display()));
+        }
+        eprintln!("{:<24} {:>10} {:>16}", "syscall", "calls", "time");
+        for (syscall, stats) in stats.iter() {
+            let time = format!("{:?}", stats.time);
+            eprintln!("{:<24} {:>10} {:>16}", syscall, stats.calls, time);
+        }
+        Ok(())
+    }
+
+    /// Prints the WASI versions imported by the module and the kind of its
+    /// entry point, failing if it has none.
+    fn report_dry_run(module: &Module, instance: &Instance) -> Result<()> {
+        let entry_point = if instance.exports.get_function("_start").is_ok() {
+            "`_start` (command)"
+        } else if instance.exports.get_function("_initialize").is_ok() {
+            "`_initialize` (reactor)"
+        } else {
+            bail!("The module exports neither a `_start` nor an `_initialize` function");
+        };
+        println!("WASI versions: {}", describe_wasi_versions(module));
+        println!("Entry point: {}", entry_point);
+        Ok(())
+    }
+}
+
+/// Lists the WASI versions `module` imports for messages.
+fn describe_wasi_versions(module: &Module) -> String {
+    get_wasi_versions(module, false)
+        .unwrap_or_default()
+        .iter()
+        .map(|v| format!("`{}`", v.get_namespace_str()))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Fails with a list of the imports in `extra_imports` which `wasi`
+/// already provides.
+fn check_import_collisions(wasi: &dyn NamedResolver, extra_imports: &ImportObject) -> Result<()> {
+    let mut collisions = extra_imports
+        .clone()
+        .into_iter()
+        .filter(|((module, name), _)| wasi.resolve_by_name(module, name).is_some())
+        .map(|((module, name), _)| format!("`{}`.`{}`", module, name))
+        .collect::<Vec<_>>();
+    if collisions.is_empty() {
+        return Ok(());
+    }
+    collisions.sort();
+    bail!(
+        "{} extra import(s) collide with WASI:\n    {}\nWASI functions can't be overridden.",
+        collisions.len(),
+        collisions.join("\n    ")
+    )
+}
+
+/// The path under which the module sees a pre-opened directory, without
+/// the leading `/`.
+fn guest_path(preopen: &Preopen<'_>) -> String {
+    match preopen.0 {
+        Some(alias) => alias,
+        None => preopen.1.to_str().unwrap_or_default(),
+    }
+    .trim_start_matches('/')
+    .to_string()
+}
+
+/// Builder for [`Wasi`], with methods mirroring the `wasmer run` options.
+#[derive(Debug, Default)]
+pub struct WasiBuilder {
+    wasi: Wasi,
+}
+
+impl WasiBuilder {
+    /// Pre-open a host directory, like `--dir`.
+    pub fn dir(&mut self, dir: impl Into<PathBuf>) -> &mut Self {
+        self.wasi.pre_opened_directories.push(dir.into());
+
+        self
+    }
+
+    /// Pre-open the current working directory as `.`, like
+    /// `--pre-open-current-dir`.
+    pub fn pre_open_current_dir(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.pre_open_current_dir = toggle;
+
+        self
+    }
+
+    /// Map a host directory to `alias` with all rights, like `--mapdir`.
+    pub fn mapdir(&mut self, alias: impl Into<String>, dir: impl Into<PathBuf>) -> &mut Self {
+        self.mapdir_with_rights(alias, dir, MapDirRights::default())
+    }
+
+    /// Map a host directory to `alias` with the given rights, like
+    /// `--mapdir GUEST_DIR:HOST_DIR:RIGHTS`.
+    pub fn mapdir_with_rights(
+        &mut self,
+        alias: impl Into<String>,
+        dir: impl Into<PathBuf>,
+        rights: MapDirRights,
+    ) -> &mut Self {
+        self.wasi
+            .mapped_dirs
+            .push((alias.into(), dir.into(), rights));
+
+        self
+    }
+
+    /// Give the pre-opened directory at `path` the file descriptor `fd`,
+    /// like `--preopen-fd`.
+    pub fn preopen_fd(&mut self, path: impl Into<String>, fd: u32) -> &mut Self {
+        self.wasi.preopen_fds.push((path.into(), fd));
+
+        self
+    }
+
+    /// Fail instead of warning when a host directory is pre-opened twice or
+    /// inside another one, like `--strict-preopens`.
+    pub fn strict_preopens(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.strict_preopens = toggle;
+
+        self
+    }
+
+    /// Take the `write` and `create` rights away from every pre-opened
+    /// directory, like `wasmer run --fs-readonly`.
+    pub fn fs_readonly(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.fs_readonly = toggle;
+
+        self
+    }
+
+    /// Write the module's memory to `path` once `_start` returns or traps,
+    /// like `wasmer run --memory-dump`.
+    pub fn memory_dump(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.wasi.memory_dump = Some(path.into());
+
+        self
+    }
+
+    /// Copy the contents of `path` into the module's memory at `addr`
+    /// before `_start` runs, and write its size at `len_addr`, if given,
+    /// like `wasmer run --input`.
+    pub fn input(
+        &mut self,
+        path: impl Into<PathBuf>,
+        addr: u32,
+        len_addr: Option<u32>,
+    ) -> &mut Self {
+        self.wasi.input = Some(MemoryInput {
+            path: path.into(),
+            addr,
+            len_addr,
+        });
+
+        self
+    }
+
+    /// Don't print warnings, like `wasmer run --quiet`.
+    pub fn quiet(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.quiet = toggle;
+
+        self
+    }
+
+    /// Create the missing host directories given to [`WasiBuilder::dir`]
+    /// and [`WasiBuilder::mapdir`] if they are writable, like
+    /// `--create-dirs`.
+    pub fn create_dirs(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.create_dirs = toggle;
+
+        self
+    }
+
+    /// Mount a tar archive as a read-only directory at `alias`, like `--fs`.
+    pub fn fs_archive(
+        &mut self,
+        alias: impl Into<String>,
+        archive: impl Into<PathBuf>,
+    ) -> &mut Self {
+        self.wasi.fs_archives.push((alias.into(), archive.into()));
+
+        self
+    }
+
+    /// Map the single host file `host_file` to `path` within the
+    /// directory `alias`, like `--mapfile ALIAS/PATH=HOST_FILE`.
+    pub fn mapfile(
+        &mut self,
+        alias: impl Into<String>,
+        path: impl Into<PathBuf>,
+        host_file: impl Into<PathBuf>,
+    ) -> &mut Self {
+        self.wasi
+            .mapped_files
+            .push((alias.into(), path.into(), host_file.into()));
+
+        self
+    }
+
+    /// Pass an environment variable, like `--env`.
+    pub fn env(&mut self, key: impl Into<String>, value: impl Into<String>) -> &mut Self {
+        self.wasi.env_vars.push((key.into(), Some(value.into())));
+
+        self
+    }
+
+    /// Pass the host's environment variable `key`, if it is set, like
+    /// `--env KEY`.
+    pub fn inherit_env(&mut self, key: impl Into<String>) -> &mut Self {
+        self.wasi.env_vars.push((key.into(), None));
+
+        self
+    }
+
+    /// Pass the host's variables commonly expected by command-line
+    /// programs, like `--wasi-env-inherit-path`.
+    pub fn inherit_path_env(&mut self) -> &mut Self {
+        self.wasi.wasi_env_inherit_path = true;
+
+        self
+    }
+
+    /// Pass the given host variables rather than the default ones when
+    /// inheriting them with [`inherit_path_env`](Self::inherit_path_env),
+    /// like `--wasi-env-inherit-set`.
+    pub fn inherit_env_set<I, S>(&mut self, names: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.wasi.wasi_env_inherit_set = names.into_iter().map(Into::into).collect();
+
+        self
+    }
+
+    /// Pass every host variable whose name starts with `prefix`, like
+    /// `--env-passthrough-prefix`.
+    pub fn env_passthrough_prefix(&mut self, prefix: impl Into<String>) -> &mut Self {
+        self.wasi.env_passthrough_prefixes.push(prefix.into());
+
+        self
+    }
+
+    /// Strip the prefix from the names of the variables passed with
+    /// [`env_passthrough_prefix`](Self::env_passthrough_prefix), like
+    /// `--env-strip-prefix`.
+    pub fn env_strip_prefix(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.env_strip_prefix = toggle;
+
+        self
+    }
+
+    /// Feed the given text to the module as its standard input, like
+    /// `--stdin-string`.
+    pub fn stdin_string(&mut self, text: impl Into<String>) -> &mut Self {
+        self.wasi.stdin_string = Some(text.into());
+
+        self
+    }
+
+    /// Read the module's standard input from a host file, opened when it's
+    /// first read, like `--stdin`.
+    pub fn stdin_file(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.wasi.stdin_file = Some(path.into());
+
+        self
+    }
+
+    /// Bind the standard stream with the file descriptor `fd` to the file
+    /// at `guest_path` in a pre-opened directory, like
+    /// `--preopen-stdio-as-files`.
+    pub fn preopen_stdio_as_file(&mut self, fd: u32, guest_path: impl Into<String>) -> &mut Self {
+        self.wasi.stdio_files.push((fd, guest_path.into()));
+
+        self
+    }
+
+    /// Limit how many files the module may have open at once, like
+    /// `--max-open-files`.
+    pub fn max_open_files(&mut self, max: u32) -> &mut Self {
+        self.wasi.max_open_files = Some(max);
+
+        self
+    }
+
+    /// Refuse to open symlinks the module doesn't ask to follow, like
+    /// `--no-follow-symlinks`.
+    pub fn no_follow_symlinks(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.no_follow_symlinks = toggle;
+
+        self
+    }
+
+    /// Allow symlinks to point outside of pre-opened directories, like
+    /// `--allow-symlink-escape`.
+    pub fn allow_symlink_escape(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.allow_symlink_escape = toggle;
+
+        self
+    }
+
+    /// Make clocks and random bytes reproducible, like `--deterministic`.
+    pub fn deterministic(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.deterministic = toggle;
+
+        self
+    }
+
+    /// Seed the random bytes returned to the module, like `--random-seed`.
+    pub fn random_seed(&mut self, seed: u64) -> &mut Self {
+        self.wasi.random_seed = Some(seed);
+
+        self
+    }
+
+    /// Shift the wall clock seen by the module by `seconds`, like
+    /// `--clock-offset`.
+    pub fn clock_offset(&mut self, seconds: i64) -> &mut Self {
+        self.wasi.clock_offset = Some(seconds);
+
+        self
+    }
+
+    /// Freeze all the clocks seen by the module at `time`, in nanoseconds
+    /// since the Unix epoch, like `--freeze-time`.
+    pub fn freeze_time(&mut self, time: u64) -> &mut Self {
+        self.wasi.freeze_time = Some(time);
+
+        self
+    }
+
+    /// Stop the module once it has run for `timeout`, like `--timeout`.
+    pub fn timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.wasi.timeout = Some(timeout);
+
+        self
+    }
+
+    /// Bound the time each WASI syscall may take, like
+    /// `wasmer run --host-function-timeout`.
+    pub fn host_function_timeout(&mut self, timeout: Duration) -> &mut Self {
+        self.wasi.host_function_timeout = Some(timeout);
+
+        self
+    }
+
+    /// Run the module on a new thread with a stack of `bytes`, like
+    /// `--stack-size`.
+    pub fn stack_size(&mut self, bytes: usize) -> &mut Self {
+        self.wasi.stack_size = Some(bytes);
+
+        self
+    }
+
+    /// Run the module on a new thread called `name`, turning panics on it
+    /// into errors. There is no matching option.
+    pub fn thread_name(&mut self, name: impl Into<String>) -> &mut Self {
+        self.wasi.thread_name = Some(name.into());
+
+        self
+    }
+
+    /// Instantiate `module` before the module being run and provide its
+    /// exports under the `name` namespace, like `wasmer run --preload`,
+    /// except that `module` is already compiled, in the same store.
+    pub fn preload(&mut self, name: impl Into<String>, module: Module) -> &mut Self {
+        self.wasi.preloads.push((name.into(), module));
+
+        self
+    }
+
+    /// Write a coredump to `path` if the module traps, like `--coredump`.
+    pub fn coredump(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.wasi.coredump = Some(path.into());
+
+        self
+    }
+
+    /// Print the time spent in each syscall, like `--log-syscall-timing`.
+    pub fn log_syscall_timing(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.log_syscall_timing = toggle;
+
+        self
+    }
+
+    /// Write the syscall timings to `path` as CSV, like `--profile-out`.
+    pub fn profile_out(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.wasi.log_syscall_timing = true;
+        self.wasi.profiler = None;
+        self.wasi.profile_out = Some(path.into());
+
+        self
+    }
+
+    /// Sample the Wasm functions on the stack and write them to `path` as
+    /// collapsed stacks, like `--profile flamegraph --profile-out`. This
+    /// replaces the syscall timings.
+    pub fn flamegraph(&mut self, path: impl Into<PathBuf>) -> &mut Self {
+        self.wasi.log_syscall_timing = false;
+        self.wasi.profiler = Some(Profiler::Flamegraph);
+        self.wasi.profile_out = Some(path.into());
+
+        self
+    }
+
+    /// Allow importing multiple versions of WASI without a warning, like
+    /// `--allow-multiple-wasi-versions`.
+    pub fn allow_multiple_wasi_versions(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.allow_multiple_wasi_versions = toggle;
+
+        self
+    }
+
+    /// Require modules to import a single version of WASI, like
+    /// `--deny-multiple-wasi-versions`.
+    pub fn deny_multiple_wasi_versions(&mut self, toggle: bool) -> &mut Self {
+        self.wasi.deny_multiple_wasi_versions = toggle;
+
+        self
+    }
+
+    /// Provide only the given version of WASI, like `--wasi-version`.
+    pub fn wasi_version(&mut self, version: WasiVersion) -> &mut Self {
+        self.wasi.wasi_version = Some(version);
+
+        self
+    }
+
+    /// Produce the configured [`Wasi`].
+    pub fn build(&self) -> Wasi {
+        self.wasi.clone()
+    }
+}
+
+/// Reads the regular files of a tar archive, returning their paths within
+/// the archive along with their contents.
+///
+/// Empty archives and entries which are neither files nor directories, or
+/// whose path could point outside of the archive, are rejected.
+fn read_fs_archive(archive_path: &Path) -> Result<Vec<(PathBuf, Vec<u8>)>> {
+    let file = File::open(archive_path)
+        .with_context(|| format!("failed to open archive `{}`", archive_path.display()))?;
+    let mut archive = tar::Archive::new(file);
+    let mut files = Vec::new();
+    let entries = archive
+        .entries()
+        .with_context(|| format!("failed to read archive `{}`", archive_path.display()))?;
+    for entry in entries {
+        let mut entry = entry
+            .with_context(|| format!("failed to read archive `{}`", archive_path.display()))?;
+        let entry_path = entry.path()?.into_owned();
+        let mut path = PathBuf::new();
+        for component in entry_path.components() {
+            match component {
+                Component::Normal(name) => path.push(name),
+                Component::CurDir => (),
+                _ => bail!(
+                    "archive `{}` contains an entry outside of its root: `{}`",
+                    archive_path.display(),
+                    entry_path.display()
+                ),
+            }
+        }
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_dir() {
+            continue;
+        } else if !entry_type.is_file() {
+            bail!(
+                "archive `{}` contains `{}` which is not a regular file or directory",
+                archive_path.display(),
+                entry_path.display()
+            );
+        }
+        let mut contents = Vec::new();
+        entry.read_to_end(&mut contents)?;
+        files.push((path, contents));
+    }
+    if files.is_empty() {
+        bail!("archive `{}` contains no files", archive_path.display());
+    }
+    Ok(files)
+}
+
+/// Reads a host file given to `--mapfile`.
+///
+/// Symbolic links are resolved on the host, so the module sees the contents
+/// of the file they point to, as a regular file.
+fn read_mapped_file(host_file: &Path) -> Result<Vec<u8>> {
+    let target = std::fs::canonicalize(host_file).with_context(|| {
+        format!(
+            "failed to resolve the mapped file `{}`; does it exist?",
+            host_file.display()
+        )
+    })?;
+    if !target.is_file() {
+        bail!(
+            "the mapped file `{}` is not a regular file",
+            host_file.display()
+        );
+    }
+    std::fs::read(&target)
+        .with_context(|| format!("failed to read the mapped file `{}`", host_file.display()))
+}
+
+#[cfg(all(test, feature = "compiler", feature = "engine", feature = "wat"))]
+mod tests {
+    use super::Wasi;
+    use crate::store::StoreOptions;
+    use clap::Clap;
+    use std::fmt;
+    use std::sync::atomic::{AtomicI32, Ordering};
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use tracing::field::{Field, Visit};
+    use tracing::span::{Attributes, Id, Record};
+    use tracing::{Dispatch, Event, Metadata, Subscriber};
+    use wasmer::{imports, Function, Instance, Module, Store};
+    use wasmer_wasi::{WasiError, WasiState};
+
+    const LOG_WAT: &str = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (import "env" "log" (func $log (param i32)))
+  (memory (export "memory") 1)
+  (func (export "_start") (call $log (i32.const 42))))"#;
+
+    static LOGGED: AtomicI32 = AtomicI32::new(0);
+
+    fn log(value: i32) {
+        LOGGED.store(value, Ordering::SeqCst);
+    }
+
+    fn store() -> Store {
+        StoreOptions::parse_from(&["wasmer"]).get_store().unwrap().0
+    }
+
+    #[test]
+    fn test_execute_with_imports() {
+        let store = store();
+        let module = Module::new(&store, LOG_WAT).unwrap();
+        let extra_imports = imports! {
+            "env" => { "log" => Function::new_native(&store, log) },
+        };
+        let exit_code = Wasi::builder()
+            .build()
+            .execute_with_imports(module, "log".to_string(), vec![], extra_imports)
+            .unwrap();
+        assert_eq!(exit_code, 0);
+        assert_eq!(LOGGED.load(Ordering::SeqCst), 42);
+    }
+
+    #[test]
+    fn test_execute_with_imports_returns_exit_code() {
+        let store = store();
+        let wat = LOG_WAT.replace(
+            "(call $log (i32.const 42))",
+            "(call $proc_exit (i32.const 7))",
+        );
+        let module = Module::new(&store, wat).unwrap();
+        let extra_imports = imports! {
+            "env" => { "log" => Function::new_native(&store, log) },
+        };
+        let exit_code = Wasi::builder()
+            .build()
+            .execute_with_imports(module, "log".to_string(), vec![], extra_imports)
+            .unwrap();
+        assert_eq!(exit_code, 7);
+    }
+
+    #[test]
+    fn test_execute_with_colliding_imports() {
+        let store = store();
+        let module = Module::new(&store, LOG_WAT).unwrap();
+        let extra_imports = imports! {
+            "env" => { "log" => Function::new_native(&store, log) },
+            "wasi_snapshot_preview1" => { "proc_exit" => Function::new_native(&store, log) },
+        };
+        let error = Wasi::builder()
+            .build()
+            .execute_with_imports(module, "log".to_string(), vec![], extra_imports)
+            .unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "1 extra import(s) collide with WASI:\n    `wasi_snapshot_preview1`.`proc_exit`\nWASI functions can't be overridden."
+        );
+    }
+
+    #[test]
+    fn test_syscall_timeout_is_clear_after_proc_exit() {
+        let store = store();
+        let module = Module::new(
+            &store,
+            r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (func (export "_start") (call $proc_exit (i32.const 0))))"#,
+        )
+        .unwrap();
+        let mut wasi_env = WasiState::new("exit").finalize().unwrap();
+        // Any syscall outlasts a zero timeout, so `proc_exit` would be
+        // reported as expired if it were still considered running.
+        let timeout = wasi_env.set_syscall_timeout(Duration::from_secs(0));
+        let import_object = wasi_env.import_object(&module).unwrap();
+        let instance = Instance::new(&module, &import_object).unwrap();
+        let error = instance
+            .exports
+            .get_function("_start")
+            .unwrap()
+            .call(&[])
+            .unwrap_err();
+        assert!(matches!(
+            error.downcast::<WasiError>(),
+            Ok(WasiError::Exit(0))
+        ));
+        assert_eq!(timeout.expired(), None);
+    }
+
+    /// Records each event with the name of the span it is logged in.
+    #[derive(Default)]
+    struct SpanRecorder {
+        /// The name of each span, by its id minus one.
+        spans: Mutex<Vec<&'static str>>,
+        entered: Mutex<Vec<u64>>,
+        events: Mutex<Vec<(Option<&'static str>, String)>>,
+    }
+
+    impl Subscriber for SpanRecorder {
+        fn enabled(&self, _metadata: &Metadata<'_>) -> bool {
+            true
+        }
+
+        fn new_span(&self, span: &Attributes<'_>) -> Id {
+            let mut spans = self.spans.lock().unwrap();
+            spans.push(span.metadata().name());
+            Id::from_u64(spans.len() as u64)
+        }
+
+        fn record(&self, _span: &Id, _values: &Record<'_>) {}
+
+        fn record_follows_from(&self, _span: &Id, _follows: &Id) {}
+
+        fn event(&self, event: &Event<'_>) {
+            let mut message = Message::default();
+            event.record(&mut message);
+            let span = self
+                .entered
+                .lock()
+                .unwrap()
+                .last()
+                .map(|id| self.spans.lock().unwrap()[*id as usize - 1]);
+            self.events.lock().unwrap().push((span, message.0));
+        }
+
+        fn enter(&self, span: &Id) {
+            self.entered.lock().unwrap().push(span.into_u64());
+        }
+
+        fn exit(&self, _span: &Id) {
+            self.entered.lock().unwrap().pop();
+        }
+    }
+
+    #[derive(Default)]
+    struct Message(String);
+
+    impl Visit for Message {
+        fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+            if field.name() == "message" {
+                self.0 = format!("{:?}", value);
+            }
+        }
+    }
+
+    #[test]
+    fn test_execute_logs_in_spans() {
+        let store = store();
+        let module = Module::new(
+            &store,
+            r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")))"#,
+        )
+        .unwrap();
+        let dispatch = Dispatch::new(SpanRecorder::default());
+        let exit_code = tracing::dispatcher::with_default(&dispatch, || {
+            Wasi::builder().build().execute_with_exit_code(
+                module,
+                "spans".to_string(),
+                vec![],
+                false,
+            )
+        })
+        .unwrap();
+        assert_eq!(exit_code, 0);
+
+        let recorder = dispatch.downcast_ref::<SpanRecorder>().unwrap();
+        let events = recorder.events.lock().unwrap();
+        let logged_in = |span: &str, prefix: &str| {
+            events
+                .iter()
+                .any(|(logged, message)| *logged == Some(span) && message.starts_with(prefix))
+        };
+        assert!(
+            logged_in(
+                "instantiate",
+                "Instantiated the module with WASI `wasi_snapshot_preview1`"
+            ),
+            "{:?}",
+            events
+        );
+        assert!(
+            logged_in("start", "`_start` returned after"),
+            "{:?}",
+            events
+        );
+    }
 }
@@ -1,9 +1,12 @@
 //! Common module with common used structures across different
 //! commands.
 use crate::VERSION;
+use anyhow::{bail, Error, Result};
 use clap::Clap;
 use std::env;
 use std::path::PathBuf;
+use std::str::FromStr;
+use wasmer::Features;
 
 #[derive(Debug, Clap, Clone)]
 /// The WebAssembly features that can be passed through the
@@ -32,6 +35,71 @@ pub struct WasmFeatures {
     /// Enable support for all pre-standard proposals.
     #[clap(long = "enable-all")]
     pub all: bool,
+
+    /// Enable or disable proposals, applied after the other flags, as a
+    /// comma-separated list of names prefixed by `+` or `-`, e.g.
+    /// `+simd,-threads`
+    #[clap(long = "wasm-features", name = "FEATURES", allow_hyphen_values = true)]
+    pub toggles: Option<WasmFeatureToggles>,
+}
+
+/// The proposals which can be toggled with `--wasm-features`.
+const TOGGLEABLE_FEATURES: &[&str] = &[
+    "simd",
+    "threads",
+    "reference-types",
+    "bulk-memory",
+    "multi-value",
+    "tail-call",
+];
+
+/// A list of proposals to enable or disable, in order.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WasmFeatureToggles(Vec<(String, bool)>);
+
+impl WasmFeatureToggles {
+    /// Enables or disables the proposals in `features`.
+    pub fn apply(&self, features: &mut Features) {
+        for (name, enable) in self.0.iter() {
+            match name.as_str() {
+                "simd" => features.simd(*enable),
+                "threads" => features.threads(*enable),
+                "reference-types" => features.reference_types(*enable),
+                "bulk-memory" => features.bulk_memory(*enable),
+                "multi-value" => features.multi_value(*enable),
+                "tail-call" => features.tail_call(*enable),
+                _ => unreachable!("unknown features are rejected when parsing"),
+            };
+        }
+    }
+}
+
+impl FromStr for WasmFeatureToggles {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self> {
+        let mut toggles = Vec::new();
+        for toggle in s.split(',').map(str::trim) {
+            let (enable, name) = if let Some(name) = toggle.strip_prefix('+') {
+                (true, name)
+            } else if let Some(name) = toggle.strip_prefix('-') {
+                (false, name)
+            } else {
+                bail!(
+                    "Wasm feature `{}` must be prefixed by `+` to enable it or `-` to disable it",
+                    toggle
+                );
+            };
+            if !TOGGLEABLE_FEATURES.contains(&name) {
+                bail!(
+                    "Unknown Wasm feature `{}`; expected one of {}",
+                    name,
+                    TOGGLEABLE_FEATURES.join(", ")
+                );
+            }
+            toggles.push((name.to_string(), enable));
+        }
+        Ok(Self(toggles))
+    }
 }
 
 /// Get the cache dir
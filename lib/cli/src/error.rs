@@ -1,8 +1,11 @@
 //! Implements `PretyError` to print pretty errors in the CLI (when they happen)
 
-use anyhow::{Chain, Error};
+use anyhow::{bail, Chain, Error};
 use colored::*;
+use std::error::Error as StdError;
 use std::fmt::{self, Debug, Write};
+use std::str::FromStr;
+use wasmer::{InstantiationError, RuntimeError};
 
 /// A `PrettyError` for printing `anyhow::Error` nicely.
 pub struct PrettyError {
@@ -18,17 +21,61 @@ macro_rules! warning {
     })
 }
 
+/// When to print errors and warnings in color.
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum ColorChoice {
+    /// Only when stderr is a terminal and `NO_COLOR` isn't set
+    Auto,
+    /// Always
+    Always,
+    /// Never
+    Never,
+}
+
+impl ColorChoice {
+    /// Makes the errors and warnings printed from now on follow this
+    /// choice.
+    pub fn apply(self) {
+        match self {
+            Self::Always => control::set_override(true),
+            Self::Never => control::set_override(false),
+            Self::Auto => {
+                let no_color =
+                    std::env::var_os("NO_COLOR").map_or(false, |value| !value.is_empty());
+                if no_color || !atty::is(atty::Stream::Stderr) {
+                    control::set_override(false);
+                }
+            }
+        }
+    }
+}
+
+impl FromStr for ColorChoice {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Error> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            choice => bail!("The `{}` color choice does not exist.", choice),
+        }
+    }
+}
+
 impl PrettyError {
     /// Process a `Result` printing any errors and exiting
     /// the process after
     pub fn report<T>(result: Result<T, Error>) -> ! {
-        std::process::exit(match result {
-            Ok(_t) => 0,
-            Err(error) => {
-                eprintln!("{:?}", PrettyError { error });
-                1
-            }
-        });
+        match result {
+            Ok(_t) => std::process::exit(0),
+            Err(error) => Self::exit_with(error, 1),
+        }
+    }
+
+    /// Print `error` and exit the process with `exit_code`
+    pub fn exit_with(error: Error, exit_code: i32) -> ! {
+        eprintln!("{:?}", PrettyError { error });
+        std::process::exit(exit_code);
     }
 }
 
@@ -40,7 +87,11 @@ impl Debug for PrettyError {
             return Debug::fmt(&error, f);
         }
 
-        write!(f, "{}", format!("{}: {}", "error".red(), error).bold())?;
+        write!(
+            f,
+            "{}",
+            format!("{}: {}", "error".red(), message(error.as_ref())).bold()
+        )?;
         // write!(f, "{}", error)?;
 
         if let Some(cause) = error.source() {
@@ -55,13 +106,51 @@ impl Debug for PrettyError {
                     is_last: n == total_errors - 1,
                     started: false,
                 };
-                write!(indented, "{}", error)?;
+                write!(indented, "{}", message(error))?;
             }
         }
+
+        let trap = error.chain().find_map(|cause| runtime_error(cause));
+        let trace = trap.map_or(&[][..], RuntimeError::trace);
+        if !trace.is_empty() {
+            write!(f, "\n{}", "Wasm backtrace:".bold())?;
+        }
+        for (n, frame) in trace.iter().enumerate() {
+            let location = format!(
+                "{}[{}]:0x{:x}",
+                frame.module_name(),
+                frame.func_index(),
+                frame.module_offset()
+            );
+            write!(
+                f,
+                "\n{: >5}: {} {}",
+                n,
+                frame.function_name().unwrap_or("<unnamed>").bold(),
+                format!("({})", location).dimmed()
+            )?;
+        }
         Ok(())
     }
 }
 
+/// The message of `error`, without the Wasm backtrace of a trap, which is
+/// printed on its own after all the errors.
+fn message(error: &(dyn StdError + 'static)) -> String {
+    match runtime_error(error) {
+        Some(trap) => format!("RuntimeError: {}", trap.message()),
+        None => error.to_string(),
+    }
+}
+
+/// The runtime error `error` is, including one raised by a start function.
+pub(crate) fn runtime_error(error: &(dyn StdError + 'static)) -> Option<&RuntimeError> {
+    match error.downcast_ref::<InstantiationError>() {
+        Some(InstantiationError::Start(error)) => Some(error),
+        _ => error.downcast_ref::<RuntimeError>(),
+    }
+}
+
 struct Indented<'a, D> {
     inner: &'a mut D,
     number: Option<usize>,
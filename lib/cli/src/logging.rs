@@ -1,10 +1,18 @@
-//! Logging functions for the debug feature.
+//! Logging what Wasmer does, e.g. with `wasmer run --log-level`.
 use crate::utils::wasmer_should_print_color;
 use fern::colors::{Color, ColoredLevelConfig};
+use log::LevelFilter;
 use std::time;
 
-/// Subroutine to instantiate the loggers
-pub fn set_up_logging() -> Result<(), String> {
+/// The level given by `RUST_LOG`, if it is set to a plain level such as
+/// `info`.
+pub fn level_from_env() -> Option<LevelFilter> {
+    std::env::var("RUST_LOG").ok()?.parse().ok()
+}
+
+/// Subroutine to instantiate the loggers, writing the messages up to
+/// `level` to stderr, so they don't mix with the output of the module
+pub fn set_up_logging(level: LevelFilter) -> Result<(), String> {
     let colors_line = ColoredLevelConfig::new()
         .error(Color::Red)
         .warn(Color::Yellow)
@@ -13,7 +21,7 @@ pub fn set_up_logging() -> Result<(), String> {
 
     let colors_level = colors_line.info(Color::Green);
     let dispatch = fern::Dispatch::new()
-        .level(log::LevelFilter::Debug)
+        .level(level)
         .chain({
             let base = if should_color {
                 fern::Dispatch::new().format(move |out, message, record| {
@@ -51,7 +59,7 @@ pub fn set_up_logging() -> Result<(), String> {
                 .filter(|metadata| {
                     metadata.target().starts_with("wasmer")
                 })
-                .chain(std::io::stdout())
+                .chain(std::io::stderr())
         });
 
     dispatch.apply().map_err(|e| format!("{}", e))?;
@@ -113,6 +113,9 @@ impl CompilerOptions {
         if self.features.reference_types || self.features.all {
             features.reference_types(true);
         }
+        if let Some(toggles) = &self.features.toggles {
+            toggles.apply(&mut features);
+        }
         Ok(features)
     }
 
@@ -397,6 +400,14 @@ impl StoreOptions {
         Ok((store, engine_type, compiler_type))
     }
 
+    /// Gets the Wasm features the store from [`get_store`](Self::get_store)
+    /// compiles modules with.
+    pub fn get_features(&self) -> Result<Features> {
+        let (compiler_config, _) = self.compiler.get_compiler_config()?;
+        let defaults = compiler_config.default_features_for_target(&Target::default());
+        self.compiler.get_features(defaults)
+    }
+
     fn get_engine_with_compiler(
         &self,
         target: Target,
@@ -1,7 +1,9 @@
 //! Utility functions for the WebAssembly module
 use anyhow::{bail, Result};
 use std::env;
-use std::path::PathBuf;
+use std::path::{Component, Path, PathBuf};
+use std::time::UNIX_EPOCH;
+use wasmer::{Pages, WASM_MAX_PAGES, WASM_PAGE_SIZE};
 
 /// Whether or not Wasmer should print with color
 pub fn wasmer_should_print_color() -> bool {
@@ -11,45 +13,207 @@ pub fn wasmer_should_print_color() -> bool {
         .unwrap_or_else(|| atty::is(atty::Stream::Stdout))
 }
 
-fn retrieve_alias_pathbuf(alias: &str, real_dir: &str) -> Result<(String, PathBuf)> {
-    let pb = PathBuf::from(&real_dir);
-    if let Ok(pb_metadata) = pb.metadata() {
-        if !pb_metadata.is_dir() {
-            bail!("\"{}\" exists, but it is not a directory", &real_dir);
+/// Rights granted to a directory mapped with `--mapdir`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MapDirRights {
+    /// Whether the guest can read files in the directory.
+    pub read: bool,
+    /// Whether the guest can write to files in the directory.
+    pub write: bool,
+    /// Whether the guest can create files in the directory.
+    pub create: bool,
+}
+
+impl Default for MapDirRights {
+    fn default() -> Self {
+        Self {
+            read: true,
+            write: true,
+            create: true,
         }
-    } else {
-        bail!("Directory \"{}\" does not exist", &real_dir);
     }
-    Ok((alias.to_string(), pb))
 }
 
-/// Parses a mapdir from a string
-pub fn parse_mapdir(entry: &str) -> Result<(String, PathBuf)> {
-    // We try first splitting by `::`
-    if let [alias, real_dir] = entry.split("::").collect::<Vec<&str>>()[..] {
-        retrieve_alias_pathbuf(alias, real_dir)
+/// Parses a comma-separated list of rights, e.g. `read,write`.
+fn parse_mapdir_rights(rights: &str) -> Result<MapDirRights> {
+    let mut parsed = MapDirRights {
+        read: false,
+        write: false,
+        create: false,
+    };
+    for right in rights.split(',').map(str::trim) {
+        match right {
+            "read" => parsed.read = true,
+            "write" => parsed.write = true,
+            "create" => parsed.create = true,
+            _ => bail!(
+                "Unknown directory right `{}`; expected a comma-separated list of `read`, `write` or `create`",
+                right
+            ),
+        }
     }
-    // And then we try splitting by `:` (for compatibility with previous API)
-    else if let [alias, real_dir] = entry.split(':').collect::<Vec<&str>>()[..] {
-        retrieve_alias_pathbuf(alias, real_dir)
+    Ok(parsed)
+}
+
+/// Checks that a directory to pre-open exists on the host.
+pub fn check_host_dir(dir: &Path) -> Result<()> {
+    if let Ok(metadata) = dir.metadata() {
+        if !metadata.is_dir() {
+            bail!("\"{}\" exists, but it is not a directory", dir.display());
+        }
     } else {
-        bail!(
-            "Directory mappings must consist of two paths separate by a `::` or `:`. Found {}",
+        bail!("Directory \"{}\" does not exist", dir.display());
+    }
+    Ok(())
+}
+
+/// Turns a guest path given on the command line into the form the guest
+/// sees, which uses forward slashes whatever the host uses.
+pub fn normalize_guest_path(path: &str) -> String {
+    if cfg!(windows) {
+        path.replace('\\', "/")
+    } else {
+        path.to_string()
+    }
+}
+
+/// Splits a mapping such as `GUEST_DIR:HOST_DIR` on `separator`.
+///
+/// On Windows, a `:` separator is ambiguous with the drive of an absolute
+/// host path, so it doesn't split a single letter followed by a slash or
+/// a backslash off the rest of the path, as in `C:\dir`. The first piece
+/// is the guest path, so `c:\dir` is still the guest directory `c` mapped
+/// to `\dir`.
+fn split_mapping<'a>(entry: &'a str, separator: &str) -> Vec<&'a str> {
+    split_mapping_with_drives(entry, separator, cfg!(windows))
+}
+
+/// [`split_mapping`], telling whether to look for drives.
+fn split_mapping_with_drives<'a>(entry: &'a str, separator: &str, drives: bool) -> Vec<&'a str> {
+    let mut pieces = Vec::new();
+    let mut start = 0;
+    for (position, _) in entry.match_indices(separator) {
+        let piece = &entry[start..position];
+        let rest = &entry[position + separator.len()..];
+        let is_drive = drives
+            && separator == ":"
+            && !pieces.is_empty()
+            && piece.len() == 1
+            && piece.as_bytes()[0].is_ascii_alphabetic()
+            && (rest.starts_with('\\') || rest.starts_with('/'));
+        if !is_drive {
+            pieces.push(piece);
+            start = position + separator.len();
+        }
+    }
+    pieces.push(&entry[start..]);
+    pieces
+}
+
+/// Parses a mapdir from a string.
+///
+/// The mapping has the form `GUEST_DIR:HOST_DIR[:RIGHTS]`, where `RIGHTS`
+/// is a comma-separated list of `read`, `write` and `create`. When the
+/// rights are omitted, the directory is mapped with all of them. The host
+/// directory is only checked with [`check_host_dir`] when the module runs,
+/// since `--create-dirs` may create it.
+///
+/// On Windows, the host directory may start with a drive, as in
+/// `guest:C:\dir`, see [`split_mapping`].
+pub fn parse_mapdir(entry: &str) -> Result<(String, PathBuf, MapDirRights)> {
+    // We split by `::` if present, and by `:` otherwise (for
+    // compatibility with previous API)
+    let separator = if entry.contains("::") { "::" } else { ":" };
+    match split_mapping(entry, separator)[..] {
+        [alias, real_dir] => Ok((
+            normalize_guest_path(alias),
+            PathBuf::from(real_dir),
+            MapDirRights::default(),
+        )),
+        [alias, real_dir, rights] => Ok((
+            normalize_guest_path(alias),
+            PathBuf::from(real_dir),
+            parse_mapdir_rights(rights)?,
+        )),
+        _ => bail!(
+            "Directory mappings must consist of two paths separate by a `::` or `:`, optionally followed by a list of rights. Found {}",
+            &entry
+        ),
+    }
+}
+
+/// Parses an archive to mount from a string.
+///
+/// The mount has the form `GUEST_DIR:ARCHIVE`, where `ARCHIVE` is the path
+/// to a tar file on the host.
+pub fn parse_fs_archive(entry: &str) -> Result<(String, PathBuf)> {
+    let separator = if entry.contains("::") { "::" } else { ":" };
+    match split_mapping(entry, separator)[..] {
+        [alias, archive] if !alias.is_empty() && !archive.is_empty() => {
+            Ok((normalize_guest_path(alias), PathBuf::from(archive)))
+        }
+        _ => bail!(
+            "Archive mounts must consist of a guest directory and an archive path separated by a `::` or `:`. Found {}",
             &entry
-        )
+        ),
     }
 }
 
+/// Parses an RFC 3339 date in UTC, e.g. `2001-02-03T04:05:06Z`, into
+/// nanoseconds since the Unix epoch.
+pub fn parse_timestamp(entry: &str) -> Result<u64> {
+    let time = match humantime::parse_rfc3339(entry) {
+        Ok(time) => time,
+        Err(e) => bail!("Invalid RFC 3339 date `{}`: {}", entry, e),
+    };
+    match time.duration_since(UNIX_EPOCH) {
+        Ok(duration) => Ok(duration.as_nanos() as u64),
+        Err(_) => bail!(
+            "Dates before the Unix epoch are not supported; got `{}`",
+            entry
+        ),
+    }
+}
+
+/// The smallest stack size accepted by [`parse_stack_size`].
+const MIN_STACK_SIZE: usize = 256 * 1024;
+
+/// The largest stack size accepted by [`parse_stack_size`].
+const MAX_STACK_SIZE: usize = 1024 * 1024 * 1024;
+
+/// Parses a stack size in bytes, rejecting sizes too small to run anything
+/// or larger than 1 GiB.
+pub fn parse_stack_size(entry: &str) -> Result<usize> {
+    let size = match entry.parse::<usize>() {
+        Ok(size) => size,
+        Err(e) => bail!("Invalid stack size `{}`: {}", entry, e),
+    };
+    if !(MIN_STACK_SIZE..=MAX_STACK_SIZE).contains(&size) {
+        bail!(
+            "The stack size must be between {} and {} bytes; got {}",
+            MIN_STACK_SIZE,
+            MAX_STACK_SIZE,
+            size
+        );
+    }
+    Ok(size)
+}
+
 /// Parses an environment variable.
-pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
+///
+/// A lone `<name>` has no value, meaning it is inherited from the host
+/// environment when the module runs.
+pub fn parse_envvar(entry: &str) -> Result<(String, Option<String>)> {
     let entry = entry.trim();
 
     match entry.find('=') {
-        None => bail!(
-            "Environment variable must be of the form `<name>=<value>`; found `{}`",
+        None if entry.is_empty() => bail!(
+            "Environment variable must be of the form `<name>=<value>` or `<name>`; found `{}`",
             &entry
         ),
 
+        None => Ok((entry.into(), None)),
+
         Some(0) => bail!(
             "Environment variable is not well formed, the `name` is missing in `<name>=<value>`; got `{}`",
             &entry
@@ -60,20 +224,161 @@ pub fn parse_envvar(entry: &str) -> Result<(String, String)> {
             &entry
         ),
 
-        Some(position) => Ok((entry[..position].into(), entry[position + 1..].into())),
+        Some(position) => Ok((
+            entry[..position].into(),
+            Some(entry[position + 1..].into()),
+        )),
+    }
+}
+
+/// Parses a memory size given either as a number of 64 KiB Wasm pages, or
+/// as a number of bytes with a unit, e.g. `64MiB`.
+pub fn parse_memory_size(entry: &str) -> Result<Pages> {
+    let units = [
+        ("GiB", 1 << 30),
+        ("MiB", 1 << 20),
+        ("KiB", 1 << 10),
+        ("B", 1),
+    ];
+    let pages = match units.iter().find(|(unit, _)| entry.ends_with(unit)) {
+        None => match entry.parse::<u64>() {
+            Ok(pages) => pages,
+            Err(e) => bail!("Invalid memory size `{}`: {}", entry, e),
+        },
+        Some((unit, multiplier)) => {
+            let bytes = match entry[..entry.len() - unit.len()].trim().parse::<u64>() {
+                Ok(count) => count.saturating_mul(*multiplier),
+                Err(e) => bail!("Invalid memory size `{}`: {}", entry, e),
+            };
+            if bytes % WASM_PAGE_SIZE as u64 != 0 {
+                bail!(
+                    "The memory size must be a multiple of the 64 KiB Wasm page size; got `{}`",
+                    entry
+                );
+            }
+            bytes / WASM_PAGE_SIZE as u64
+        }
+    };
+    if pages > u64::from(WASM_MAX_PAGES) {
+        bail!(
+            "The memory size can be at most {} pages (4 GiB); got `{}`",
+            WASM_MAX_PAGES,
+            entry
+        );
+    }
+    Ok(Pages(pages as u32))
+}
+
+/// Parses a preopened directory pinned to a file descriptor, of the form
+/// `GUEST_PATH=FD`.
+pub fn parse_preopen_fd(entry: &str) -> Result<(String, u32)> {
+    let (path, fd) = match entry.rfind('=') {
+        Some(position) if position > 0 => (&entry[..position], &entry[position + 1..]),
+        _ => bail!(
+            "Pinned preopens must be of the form `<guest path>=<fd>`; found `{}`",
+            entry
+        ),
+    };
+    match fd.parse::<u32>() {
+        Ok(fd) => Ok((path.to_string(), fd)),
+        Err(e) => bail!("Invalid file descriptor `{}`: {}", fd, e),
+    }
+}
+
+/// Parses a standard stream bound to a file of the guest filesystem, of
+/// the form `STREAM=GUEST_PATH`, where `STREAM` is `stdin`, `stdout` or
+/// `stderr`. The stream is returned as its file descriptor.
+pub fn parse_stdio_file(entry: &str) -> Result<(u32, String)> {
+    let (stream, path) = match entry.find('=') {
+        Some(position) if position + 1 < entry.len() => {
+            (&entry[..position], &entry[position + 1..])
+        }
+        _ => bail!(
+            "Standard stream files must be of the form `<stream>=<guest path>`; found `{}`",
+            entry
+        ),
+    };
+    let fd = match stream {
+        "stdin" => 0,
+        "stdout" => 1,
+        "stderr" => 2,
+        _ => bail!(
+            "Unknown standard stream `{}`; expected `stdin`, `stdout` or `stderr`",
+            stream
+        ),
+    };
+    Ok((fd, path.to_string()))
+}
+
+/// Parses a single host file to map, of the form `GUEST_PATH=HOST_FILE`.
+///
+/// The first component of `GUEST_PATH` names the directory the file is
+/// mounted in and the rest is the path of the file within it, so the
+/// result is the directory alias, the path in it and the host file.
+pub fn parse_mapfile(entry: &str) -> Result<(String, PathBuf, PathBuf)> {
+    let (guest_path, host_file) = match entry.find('=') {
+        Some(position) if position > 0 && position + 1 < entry.len() => {
+            (&entry[..position], &entry[position + 1..])
+        }
+        _ => bail!(
+            "Mapped files must be of the form `<guest path>=<host file>`; found `{}`",
+            entry
+        ),
+    };
+    let mut components = Vec::new();
+    for component in Path::new(guest_path).components() {
+        match component {
+            Component::Normal(name) => components.push(name),
+            _ => bail!(
+                "Mapped file guest paths must be relative and may not contain `.` or `..`; found `{}`",
+                guest_path
+            ),
+        }
+    }
+    if components.len() < 2 {
+        bail!(
+            "Mapped file guest paths must name a directory and a file, e.g. `etc/app.toml`; found `{}`",
+            guest_path
+        );
+    }
+    let alias = components[0].to_string_lossy().into_owned();
+    let path = components[1..].iter().collect();
+    Ok((alias, path, PathBuf::from(host_file)))
+}
+
+/// Parses a module to preload, of the form `NAME=MODULE`, where `NAME` is
+/// the import namespace its exports are provided under.
+pub fn parse_preload(entry: &str) -> Result<(String, PathBuf)> {
+    match entry.find('=') {
+        Some(position) if position > 0 && position + 1 < entry.len() => Ok((
+            entry[..position].to_string(),
+            PathBuf::from(&entry[position + 1..]),
+        )),
+        _ => bail!(
+            "Preloaded modules must be of the form `<name>=<module>`; found `{}`",
+            entry
+        ),
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::parse_envvar;
+    use super::{
+        parse_envvar, parse_fs_archive, parse_mapdir, parse_mapfile, parse_memory_size,
+        parse_preload, parse_preopen_fd, parse_stack_size, parse_stdio_file, parse_timestamp,
+        split_mapping_with_drives, MapDirRights,
+    };
+    use std::path::PathBuf;
+    use wasmer::Pages;
 
     #[test]
     fn test_parse_envvar() {
         assert_eq!(
-            parse_envvar("A").unwrap_err().to_string(),
-            "Environment variable must be of the form `<name>=<value>`; found `A`"
+            parse_envvar("").unwrap_err().to_string(),
+            "Environment variable must be of the form `<name>=<value>` or `<name>`; found ``"
         );
+        assert_eq!(parse_envvar("A").unwrap(), ("A".into(), None));
+        assert_eq!(parse_envvar("  A ").unwrap(), ("A".into(), None));
         assert_eq!(
             parse_envvar("=A").unwrap_err().to_string(),
             "Environment variable is not well formed, the `name` is missing in `<name>=<value>`; got `=A`"
@@ -82,11 +387,229 @@ mod tests {
             parse_envvar("A=").unwrap_err().to_string(),
             "Environment variable is not well formed, the `value` is missing in `<name>=<value>`; got `A=`"
         );
-        assert_eq!(parse_envvar("A=B").unwrap(), ("A".into(), "B".into()));
-        assert_eq!(parse_envvar("   A=B\t").unwrap(), ("A".into(), "B".into()));
+        assert_eq!(parse_envvar("A=B").unwrap(), ("A".into(), Some("B".into())));
+        assert_eq!(
+            parse_envvar("   A=B\t").unwrap(),
+            ("A".into(), Some("B".into()))
+        );
         assert_eq!(
             parse_envvar("A=B=C=D").unwrap(),
-            ("A".into(), "B=C=D".into())
+            ("A".into(), Some("B=C=D".into()))
+        );
+    }
+
+    #[test]
+    fn test_parse_mapdir() {
+        assert_eq!(
+            parse_mapdir("guest:.").unwrap(),
+            ("guest".into(), PathBuf::from("."), MapDirRights::default())
+        );
+        assert_eq!(
+            parse_mapdir("guest::.::read").unwrap(),
+            (
+                "guest".into(),
+                PathBuf::from("."),
+                MapDirRights {
+                    read: true,
+                    write: false,
+                    create: false,
+                }
+            )
+        );
+        assert_eq!(
+            parse_mapdir("guest:.:read,create").unwrap(),
+            (
+                "guest".into(),
+                PathBuf::from("."),
+                MapDirRights {
+                    read: true,
+                    write: false,
+                    create: true,
+                }
+            )
+        );
+        assert_eq!(
+            parse_mapdir("guest:.:read,exec").unwrap_err().to_string(),
+            "Unknown directory right `exec`; expected a comma-separated list of `read`, `write` or `create`"
+        );
+    }
+
+    #[test]
+    fn test_split_mapping_with_drives() {
+        assert_eq!(
+            split_mapping_with_drives("guest:C:\\host\\dir", ":", true),
+            ["guest", "C:\\host\\dir"]
+        );
+        assert_eq!(
+            split_mapping_with_drives("guest:d:/host:read", ":", true),
+            ["guest", "d:/host", "read"]
+        );
+        // The guest path comes first, so it is never taken for a drive.
+        assert_eq!(
+            split_mapping_with_drives("C:\\dir", ":", true),
+            ["C", "\\dir"]
+        );
+        // A drive-relative path can't be told apart from the rights.
+        assert_eq!(
+            split_mapping_with_drives("guest:C:dir", ":", true),
+            ["guest", "C", "dir"]
+        );
+        assert_eq!(
+            split_mapping_with_drives("guest::C:\\dir::read", "::", true),
+            ["guest", "C:\\dir", "read"]
+        );
+        assert_eq!(
+            split_mapping_with_drives("guest:C:/dir", ":", false),
+            ["guest", "C", "/dir"]
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_parse_mapdir_windows() {
+        assert_eq!(
+            parse_mapdir("guest:C:\\host\\dir").unwrap(),
+            (
+                "guest".into(),
+                PathBuf::from("C:\\host\\dir"),
+                MapDirRights::default()
+            )
+        );
+        assert_eq!(
+            parse_mapdir("guest\\sub:C:\\host:read").unwrap(),
+            (
+                "guest/sub".into(),
+                PathBuf::from("C:\\host"),
+                MapDirRights {
+                    read: true,
+                    write: false,
+                    create: false,
+                }
+            )
+        );
+        // A single-letter guest directory followed by a drive.
+        assert_eq!(
+            parse_mapdir("c:D:\\host").unwrap(),
+            (
+                "c".into(),
+                PathBuf::from("D:\\host"),
+                MapDirRights::default()
+            )
+        );
+        assert_eq!(
+            parse_fs_archive("guest:C:\\fixtures.tar").unwrap(),
+            ("guest".into(), PathBuf::from("C:\\fixtures.tar"))
+        );
+    }
+
+    #[test]
+    fn test_parse_fs_archive() {
+        assert_eq!(
+            parse_fs_archive("guest:fixtures.tar").unwrap(),
+            ("guest".into(), PathBuf::from("fixtures.tar"))
+        );
+        assert_eq!(
+            parse_fs_archive("guest::fixtures.tar").unwrap(),
+            ("guest".into(), PathBuf::from("fixtures.tar"))
+        );
+        assert!(parse_fs_archive("fixtures.tar").is_err());
+        assert!(parse_fs_archive(":fixtures.tar").is_err());
+    }
+
+    #[test]
+    fn test_parse_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00Z").unwrap(), 0);
+        assert_eq!(
+            parse_timestamp("2001-02-03T04:05:06Z").unwrap(),
+            981_173_106_000_000_000
+        );
+        assert!(parse_timestamp("2001-02-03").is_err());
+        assert!(parse_timestamp("1969-12-31T23:59:59Z").is_err());
+    }
+
+    #[test]
+    fn test_parse_stack_size() {
+        assert_eq!(parse_stack_size("8388608").unwrap(), 8 * 1024 * 1024);
+        assert!(parse_stack_size("8M").is_err());
+        assert!(parse_stack_size("1024").is_err());
+        assert!(parse_stack_size("2147483648").is_err());
+    }
+
+    #[test]
+    fn test_parse_memory_size() {
+        assert_eq!(parse_memory_size("16").unwrap(), Pages(16));
+        assert_eq!(parse_memory_size("64MiB").unwrap(), Pages(1024));
+        assert_eq!(parse_memory_size("128KiB").unwrap(), Pages(2));
+        assert_eq!(parse_memory_size("4GiB").unwrap(), Pages(65536));
+        assert!(parse_memory_size("1000B").is_err());
+        assert!(parse_memory_size("8GiB").is_err());
+        assert!(parse_memory_size("65537").is_err());
+        assert!(parse_memory_size("lots").is_err());
+    }
+
+    #[test]
+    fn test_parse_preopen_fd() {
+        assert_eq!(parse_preopen_fd("data=4").unwrap(), ("data".into(), 4));
+        assert_eq!(parse_preopen_fd("a=b=5").unwrap(), ("a=b".into(), 5));
+        assert!(parse_preopen_fd("data").is_err());
+        assert!(parse_preopen_fd("=4").is_err());
+        assert!(parse_preopen_fd("data=four").is_err());
+    }
+
+    #[test]
+    fn test_parse_stdio_file() {
+        assert_eq!(
+            parse_stdio_file("stdin=in/data").unwrap(),
+            (0, "in/data".into())
+        );
+        assert_eq!(
+            parse_stdio_file("stdout=/out/log=1").unwrap(),
+            (1, "/out/log=1".into())
+        );
+        assert_eq!(parse_stdio_file("stderr=log").unwrap(), (2, "log".into()));
+        assert!(parse_stdio_file("stdout").is_err());
+        assert!(parse_stdio_file("stdout=").is_err());
+        assert!(parse_stdio_file("=log").is_err());
+        assert!(parse_stdio_file("1=log").is_err());
+    }
+
+    #[test]
+    fn test_parse_mapfile() {
+        assert_eq!(
+            parse_mapfile("etc/app.toml=/home/user/app.toml").unwrap(),
+            (
+                "etc".into(),
+                PathBuf::from("app.toml"),
+                PathBuf::from("/home/user/app.toml")
+            )
+        );
+        assert_eq!(
+            parse_mapfile("etc/app/config.toml=a=b.toml").unwrap(),
+            (
+                "etc".into(),
+                PathBuf::from("app/config.toml"),
+                PathBuf::from("a=b.toml")
+            )
+        );
+        assert!(parse_mapfile("app.toml=app.toml").is_err());
+        assert!(parse_mapfile("../etc/app.toml=app.toml").is_err());
+        assert!(parse_mapfile("/etc/app.toml=app.toml").is_err());
+        assert!(parse_mapfile("etc/app.toml=").is_err());
+        assert!(parse_mapfile("=app.toml").is_err());
+    }
+
+    #[test]
+    fn test_parse_preload() {
+        assert_eq!(
+            parse_preload("math=lib/math.wasm").unwrap(),
+            ("math".into(), PathBuf::from("lib/math.wasm"))
+        );
+        assert_eq!(
+            parse_preload("env=a=b.wasm").unwrap(),
+            ("env".into(), PathBuf::from("a=b.wasm"))
         );
+        assert!(parse_preload("math").is_err());
+        assert!(parse_preload("=math.wasm").is_err());
+        assert!(parse_preload("math=").is_err());
     }
 }
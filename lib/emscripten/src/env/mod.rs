@@ -72,24 +72,7 @@ pub fn _times(ctx: &EmEnv, buffer: u32) -> u32 {
 #[allow(clippy::cast_ptr_alignment)]
 pub fn ___build_environment(ctx: &EmEnv, environ: c_int) {
     debug!("emscripten::___build_environment {}", environ);
-    const MAX_ENV_VALUES: u32 = 64;
-    const TOTAL_ENV_SIZE: u32 = 1024;
-    let environment = emscripten_memory_pointer!(ctx.memory(0), environ) as *mut c_int;
-    let (mut pool_offset, env_ptr, mut pool_ptr) = unsafe {
-        let (pool_offset, _pool_slice): (u32, &mut [u8]) =
-            allocate_on_stack(ctx, TOTAL_ENV_SIZE as u32);
-        let (env_offset, _env_slice): (u32, &mut [u8]) =
-            allocate_on_stack(ctx, (MAX_ENV_VALUES * 4) as u32);
-        let env_ptr = emscripten_memory_pointer!(ctx.memory(0), env_offset) as *mut c_int;
-        let pool_ptr = emscripten_memory_pointer!(ctx.memory(0), pool_offset) as *mut u8;
-        *env_ptr = pool_offset as i32;
-        *environment = env_offset as i32;
-
-        (pool_offset, env_ptr, pool_ptr)
-    };
-
-    // *env_ptr = 0;
-    let default_vars = vec![
+    let default_vars = [
         ["USER", "web_user"],
         ["LOGNAME", "web_user"],
         ["PATH", "/"],
@@ -98,30 +81,57 @@ pub fn ___build_environment(ctx: &EmEnv, environ: c_int) {
         ["LANG", "C.UTF-8"],
         ["_", "thisProgram"],
     ];
-    let mut strings = vec![];
-    let mut total_size = 0;
-    for [key, val] in &default_vars {
-        let line = key.to_string() + "=" + val;
-        total_size += line.len();
-        strings.push(line);
-    }
-    if total_size as u32 > TOTAL_ENV_SIZE {
-        panic!("Environment size exceeded TOTAL_ENV_SIZE!");
+    let mut vars: Vec<(String, String)> = default_vars
+        .iter()
+        .map(|[key, val]| (key.to_string(), val.to_string()))
+        .collect();
+    // The variables given to `EmEnv::set_env_vars` override the defaults.
+    let env_vars = get_emscripten_data(ctx).env_vars.clone();
+    for (key, val) in env_vars {
+        vars.retain(|(other, _)| *other != key);
+        vars.push((key, val));
     }
+    let strings: Vec<String> = vars
+        .iter()
+        .map(|(key, val)| format!("{}={}", key, val))
+        .collect();
+    let pool_size: usize = strings.iter().map(|s| s.len() + 1).sum();
+
     unsafe {
-        for (i, s) in strings.iter().enumerate() {
-            for (j, c) in s.chars().enumerate() {
-                debug_assert!(c < u8::max_value() as char);
-                *pool_ptr.add(j) = c as u8;
-            }
-            *env_ptr.add(i * 4) = pool_offset as i32;
-            pool_offset += s.len() as u32 + 1;
-            pool_ptr = pool_ptr.add(s.len() + 1);
+        let (env_offset, env): (u32, &mut [i32]) = allocate_on_stack(ctx, strings.len() as u32 + 1);
+        let (pool_offset, pool): (u32, &mut [u8]) = allocate_on_stack(ctx, pool_size as u32);
+        let mut offset = 0;
+        for (s, entry) in strings.iter().zip(env.iter_mut()) {
+            pool[offset..offset + s.len()].copy_from_slice(s.as_bytes());
+            pool[offset + s.len()] = 0;
+            *entry = (pool_offset + offset as u32) as i32;
+            offset += s.len() + 1;
         }
-        *env_ptr.add(strings.len() * 4) = 0;
+        env[strings.len()] = 0;
+        let environment = emscripten_memory_pointer!(ctx.memory(0), environ) as *mut c_int;
+        *environment = env_offset as i32;
     }
 }
 
+/// The value of `name` among the variables given to
+/// [`EmEnv::set_env_vars`], if any.
+pub(crate) fn guest_env_var(ctx: &EmEnv, name: &str) -> Option<String> {
+    get_emscripten_data(ctx)
+        .env_vars
+        .iter()
+        .rev()
+        .find(|(key, _)| key == name)
+        .map(|(_, val)| val.clone())
+}
+
+/// Drops `name` from the variables given to [`EmEnv::set_env_vars`], once
+/// the module sets or unsets it in the process environment itself.
+pub(crate) fn forget_env_var(ctx: &EmEnv, name: &str) {
+    get_emscripten_data(ctx)
+        .env_vars
+        .retain(|(key, _)| key != name);
+}
+
 pub fn ___assert_fail(_ctx: &EmEnv, _a: c_int, _b: c_int, _c: c_int, _d: c_int) {
     debug!("emscripten::___assert_fail {} {} {} {}", _a, _b, _c, _d);
     // TODO: Implement like emscripten expects regarding memory/page size
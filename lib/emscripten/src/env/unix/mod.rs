@@ -4,11 +4,13 @@ use libc::{
     unsetenv,
 };
 use std::cell::Cell;
-use std::ffi::CStr;
+use std::ffi::{CStr, CString};
 use std::mem;
 use std::os::raw::c_char;
 
-use crate::env::{call_malloc, call_malloc_with_cast, EmAddrInfo, EmSockAddr};
+use crate::env::{
+    call_malloc, call_malloc_with_cast, forget_env_var, guest_env_var, EmAddrInfo, EmSockAddr,
+};
 use crate::ptr::{Array, WasmPtr};
 use crate::utils::{copy_cstr_into_wasm, copy_terminated_array_of_cstrs};
 use crate::EmEnv;
@@ -20,8 +22,13 @@ pub fn _getenv(ctx: &EmEnv, name: i32) -> u32 {
 
     let name_addr = emscripten_memory_pointer!(ctx.memory(0), name) as *const c_char;
 
-    debug!("=> name({:?})", unsafe { CStr::from_ptr(name_addr) });
+    let name = unsafe { CStr::from_ptr(name_addr) };
+    debug!("=> name({:?})", name);
 
+    if let Some(value) = guest_env_var(ctx, &name.to_string_lossy()) {
+        let value = CString::new(value).unwrap();
+        return unsafe { copy_cstr_into_wasm(ctx, value.as_ptr()) };
+    }
     let c_str = unsafe { getenv(name_addr) };
     if c_str.is_null() {
         return 0;
@@ -37,9 +44,17 @@ pub fn _setenv(ctx: &EmEnv, name: c_int, value: c_int, overwrite: c_int) -> c_in
     let name_addr = emscripten_memory_pointer!(ctx.memory(0), name) as *const c_char;
     let value_addr = emscripten_memory_pointer!(ctx.memory(0), value) as *const c_char;
 
-    debug!("=> name({:?})", unsafe { CStr::from_ptr(name_addr) });
+    let name = unsafe { CStr::from_ptr(name_addr) };
+    debug!("=> name({:?})", name);
     debug!("=> value({:?})", unsafe { CStr::from_ptr(value_addr) });
 
+    let name = name.to_string_lossy();
+    if guest_env_var(ctx, &name).is_some() {
+        if overwrite == 0 {
+            return 0;
+        }
+        forget_env_var(ctx, &name);
+    }
     unsafe { setenv(name_addr, value_addr, overwrite) }
 }
 
@@ -49,8 +64,11 @@ pub fn _putenv(ctx: &EmEnv, name: c_int) -> c_int {
 
     let name_addr = emscripten_memory_pointer!(ctx.memory(0), name) as *const c_char;
 
-    debug!("=> name({:?})", unsafe { CStr::from_ptr(name_addr) });
+    let string = unsafe { CStr::from_ptr(name_addr) };
+    debug!("=> name({:?})", string);
 
+    let string = string.to_string_lossy();
+    forget_env_var(ctx, string.split('=').next().unwrap_or_default());
     unsafe { putenv(name_addr as _) }
 }
 
@@ -60,8 +78,10 @@ pub fn _unsetenv(ctx: &EmEnv, name: c_int) -> c_int {
 
     let name_addr = emscripten_memory_pointer!(ctx.memory(0), name) as *const c_char;
 
-    debug!("=> name({:?})", unsafe { CStr::from_ptr(name_addr) });
+    let name = unsafe { CStr::from_ptr(name_addr) };
+    debug!("=> name({:?})", name);
 
+    forget_env_var(ctx, &name.to_string_lossy());
     unsafe { unsetenv(name_addr) }
 }
 
@@ -5,7 +5,7 @@ use std::ffi::CString;
 use std::mem;
 use std::os::raw::c_char;
 
-use crate::env::{call_malloc, EmAddrInfo};
+use crate::env::{call_malloc, forget_env_var, guest_env_var, EmAddrInfo};
 use crate::ptr::WasmPtr;
 use crate::utils::{copy_cstr_into_wasm, read_string_from_wasm};
 use crate::EmEnv;
@@ -22,6 +22,10 @@ pub fn _getenv(ctx: &EmEnv, name: u32) -> u32 {
     let memory = ctx.memory(0);
     let name_string = read_string_from_wasm(&memory, name);
     debug!("=> name({:?})", name_string);
+    if let Some(value) = guest_env_var(ctx, &name_string) {
+        let value = CString::new(value).unwrap();
+        return unsafe { copy_cstr_into_wasm(ctx, value.as_ptr()) };
+    }
     let c_str = unsafe { getenv(name_string.as_ptr() as *const libc::c_char) };
     if c_str.is_null() {
         return 0;
@@ -41,6 +45,7 @@ pub fn _setenv(ctx: &EmEnv, name: u32, value: u32, _overwrite: u32) -> c_int {
     let putenv_raw_ptr = putenv_cstring.as_ptr();
     debug!("=> name({:?})", name);
     debug!("=> value({:?})", value);
+    forget_env_var(ctx, &name);
     unsafe { putenv(putenv_raw_ptr) }
 }
 
@@ -49,9 +54,10 @@ pub fn _putenv(ctx: &EmEnv, name: c_int) -> c_int {
     debug!("emscripten::_putenv");
     let memory = ctx.memory(0);
     let name_addr = emscripten_memory_pointer!(&memory, name) as *const c_char;
-    debug!("=> name({:?})", unsafe {
-        std::ffi::CStr::from_ptr(name_addr)
-    });
+    let string = unsafe { std::ffi::CStr::from_ptr(name_addr) };
+    debug!("=> name({:?})", string);
+    let string = string.to_string_lossy();
+    forget_env_var(ctx, string.split('=').next().unwrap_or_default());
     unsafe { putenv(name_addr) }
 }
 
@@ -65,6 +71,7 @@ pub fn _unsetenv(ctx: &EmEnv, name: u32) -> c_int {
     let unsetenv_cstring = CString::new(unsetenv_string).unwrap();
     let unsetenv_raw_ptr = unsetenv_cstring.as_ptr();
     debug!("=> name({:?})", name);
+    forget_env_var(ctx, &name);
     unsafe { putenv(unsetenv_raw_ptr) }
 }
 
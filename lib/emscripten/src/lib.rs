@@ -91,6 +91,13 @@ impl EmEnv {
         }
     }
 
+    /// Make the module see the variables in `env_vars`, in its `environ`
+    /// and through `getenv`, without adding them to the environment of the
+    /// process.
+    pub fn set_env_vars(&mut self, env_vars: Vec<(String, String)>) {
+        self.data.lock().unwrap().env_vars = env_vars;
+    }
+
     pub fn set_memory(&mut self, memory: Memory) {
         let mut w = self.memory.write().unwrap();
         *w = Some(memory);
@@ -273,6 +280,9 @@ pub struct EmscriptenData {
     #[wasmer(export(name = "setThrew", alias = "_setThrew", optional = true))]
     pub set_threw: LazyInit<NativeFunc<(i32, i32)>>,
     pub mapped_dirs: HashMap<String, PathBuf>,
+    /// Environment variables the module sees on top of those of the
+    /// process, see [`EmEnv::set_env_vars`].
+    pub env_vars: Vec<(String, String)>,
 }
 
 impl EmscriptenData {
@@ -14,29 +14,39 @@
 
 #[macro_use]
 mod macros;
+mod profile;
 mod ptr;
 mod state;
 mod syscalls;
+mod timeout;
 mod utils;
 
+use crate::profile::ProfiledSyscall;
 use crate::syscalls::*;
 
+pub use crate::profile::{SyscallProfile, SyscallStats};
 pub use crate::state::{
-    Fd, Pipe, Stderr, Stdin, Stdout, WasiFile, WasiFs, WasiFsError, WasiState, WasiStateBuilder,
-    WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
+    Fd, LazyHostFile, Pipe, Stderr, Stdin, Stdout, WasiFile, WasiFs, WasiFsError, WasiState,
+    WasiStateBuilder, WasiStateCreationError, ALL_RIGHTS, VIRTUAL_ROOT_FD,
 };
 pub use crate::syscalls::types;
+pub use crate::timeout::SyscallTimeout;
 pub use crate::utils::{get_wasi_version, get_wasi_versions, is_wasi_module, WasiVersion};
 
 use thiserror::Error;
 use wasmer::{
     imports, ChainableNamedResolver, Function, ImportObject, LazyInit, Memory, Module,
-    NamedResolver, Store, WasmerEnv,
+    NamedResolver, RuntimeError, Store, WasmerEnv,
 };
 #[cfg(all(target_os = "macos", target_arch = "aarch64",))]
 use wasmer::{FunctionType, ValType};
 
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::{Duration, Instant};
+
+/// How often a sleeping syscall checks whether the module was interrupted.
+const INTERRUPT_POLL_INTERVAL: Duration = Duration::from_millis(10);
 
 /// This is returned in `RuntimeError`.
 /// Use `downcast` or `downcast_ref` to retrieve the `ExitCode`.
@@ -46,6 +56,27 @@ pub enum WasiError {
     Exit(syscalls::types::__wasi_exitcode_t),
     #[error("The WASI version could not be determined")]
     UnknownWasiVersion,
+    #[error("WASI execution was interrupted")]
+    Interrupted,
+    #[error("The WASI syscall `{0}` took longer than its timeout")]
+    SyscallTimeout(&'static str),
+}
+
+/// Stops the module running with a [`WasiEnv`] from another thread.
+///
+/// WASI can only stop the module when it calls into it, so the request is
+/// checked by the syscalls a busy module keeps calling, currently
+/// `sched_yield`, which then fail with [`WasiError::Interrupted`].  A
+/// module sleeping in `poll_oneoff` is woken up early instead, which
+/// returns `EINTR`.
+#[derive(Debug, Clone)]
+pub struct InterruptHandle(Arc<AtomicBool>);
+
+impl InterruptHandle {
+    /// Asks the module to stop at its next check.
+    pub fn interrupt(&self) {
+        self.0.store(true, Ordering::SeqCst);
+    }
 }
 
 /// The environment provided to the WASI imports.
@@ -60,6 +91,12 @@ pub struct WasiEnv {
     pub state: Arc<Mutex<WasiState>>,
     #[wasmer(export)]
     memory: LazyInit<Memory>,
+    /// When set, the syscalls record their calls in this profile.
+    profile: Option<Arc<SyscallProfile>>,
+    /// When set, the syscalls fail once they take longer than its timeout.
+    syscall_timeout: Option<Arc<SyscallTimeout>>,
+    /// Set by an [`InterruptHandle`] to stop the module.
+    interrupted: Arc<AtomicBool>,
 }
 
 impl WasiEnv {
@@ -67,9 +104,70 @@ impl WasiEnv {
         Self {
             state: Arc::new(Mutex::new(state)),
             memory: LazyInit::new(),
+            profile: None,
+            syscall_timeout: None,
+            interrupted: Arc::new(AtomicBool::new(false)),
         }
     }
 
+    /// Record the number of calls to each syscall and the time spent in
+    /// them in the returned profile.
+    ///
+    /// Only the imports created afterwards record their calls, and the
+    /// imports created while profiling is disabled don't pay for it.
+    pub fn enable_syscall_profiling(&mut self) -> Arc<SyscallProfile> {
+        self.profile
+            .get_or_insert_with(|| Arc::new(SyscallProfile::default()))
+            .clone()
+    }
+
+    /// Make the syscalls which take longer than `timeout` fail, and return
+    /// the watch to check from another thread for those which don't
+    /// return at all.
+    ///
+    /// Like profiling, this only applies to the imports created afterwards.
+    pub fn set_syscall_timeout(&mut self, timeout: Duration) -> Arc<SyscallTimeout> {
+        let syscall_timeout = Arc::new(SyscallTimeout::new(timeout));
+        self.syscall_timeout = Some(syscall_timeout.clone());
+        syscall_timeout
+    }
+
+    /// Get a handle which stops the module from another thread.
+    pub fn interrupt_handle(&self) -> InterruptHandle {
+        InterruptHandle(self.interrupted.clone())
+    }
+
+    /// Fails with [`WasiError::Interrupted`], unwinding the module, if an
+    /// [`InterruptHandle`] asked it to stop.
+    pub(crate) fn check_interrupted(&self) {
+        if self.interrupted.load(Ordering::SeqCst) {
+            self.raise(WasiError::Interrupted);
+        }
+    }
+
+    /// Sleeps for `duration` unless an [`InterruptHandle`] asks the module
+    /// to stop first, in which case it returns `false` early.
+    pub(crate) fn sleep(&self, duration: Duration) -> bool {
+        let deadline = Instant::now() + duration;
+        while !self.interrupted.load(Ordering::SeqCst) {
+            let now = Instant::now();
+            if now >= deadline {
+                return true;
+            }
+            std::thread::sleep((deadline - now).min(INTERRUPT_POLL_INTERVAL));
+        }
+        false
+    }
+
+    /// Unwinds the module with `error` from within a syscall, which then
+    /// never returns to the syscall wrapper.
+    pub(crate) fn raise(&self, error: WasiError) -> ! {
+        if let Some(timeout) = &self.syscall_timeout {
+            timeout.abandon();
+        }
+        RuntimeError::raise(Box::new(error))
+    }
+
     /// Get an `ImportObject` for a specific version of WASI detected in the module.
     pub fn import_object(&mut self, module: &Module) -> Result<ImportObject, WasiError> {
         let wasi_version = get_wasi_version(module, false).ok_or(WasiError::UnknownWasiVersion)?;
@@ -153,11 +251,23 @@ pub fn generate_import_object_from_env(
     }
 }
 
+/// Creates the import for a syscall, recording its calls if profiling is
+/// enabled in the environment and bounding their time if it has a timeout.
+macro_rules! syscall {
+    ($store:expr, $env:expr, $syscall:expr) => {
+        if $env.profile.is_some() || $env.syscall_timeout.is_some() {
+            ProfiledSyscall::new_profiled($syscall, $store, $env.clone())
+        } else {
+            Function::new_native_with_env($store, $env.clone(), $syscall)
+        }
+    };
+}
+
 // Note: we use this wrapper because native functions with more than 9 params
 // fail on Apple Silicon (with Cranelift).
 fn get_path_open_for_store(store: &Store, env: WasiEnv) -> Function {
     #[cfg(not(all(target_os = "macos", target_arch = "aarch64",)))]
-    let path_open = Function::new_native_with_env(store, env, path_open);
+    let path_open = syscall!(store, &env, path_open);
     #[cfg(all(target_os = "macos", target_arch = "aarch64",))]
     let path_open = Function::new_with_env(
         store,
@@ -185,51 +295,51 @@ fn get_path_open_for_store(store: &Store, env: WasiEnv) -> Function {
 fn generate_import_object_snapshot0(store: &Store, env: WasiEnv) -> ImportObject {
     imports! {
         "wasi_unstable" => {
-            "args_get" => Function::new_native_with_env(store, env.clone(), args_get),
-            "args_sizes_get" => Function::new_native_with_env(store, env.clone(), args_sizes_get),
-            "clock_res_get" => Function::new_native_with_env(store, env.clone(), clock_res_get),
-            "clock_time_get" => Function::new_native_with_env(store, env.clone(), clock_time_get),
-            "environ_get" => Function::new_native_with_env(store, env.clone(), environ_get),
-            "environ_sizes_get" => Function::new_native_with_env(store, env.clone(), environ_sizes_get),
-            "fd_advise" => Function::new_native_with_env(store, env.clone(), fd_advise),
-            "fd_allocate" => Function::new_native_with_env(store, env.clone(), fd_allocate),
-            "fd_close" => Function::new_native_with_env(store, env.clone(), fd_close),
-            "fd_datasync" => Function::new_native_with_env(store, env.clone(), fd_datasync),
-            "fd_fdstat_get" => Function::new_native_with_env(store, env.clone(), fd_fdstat_get),
-            "fd_fdstat_set_flags" => Function::new_native_with_env(store, env.clone(), fd_fdstat_set_flags),
-            "fd_fdstat_set_rights" => Function::new_native_with_env(store, env.clone(), fd_fdstat_set_rights),
-            "fd_filestat_get" => Function::new_native_with_env(store, env.clone(), legacy::snapshot0::fd_filestat_get),
-            "fd_filestat_set_size" => Function::new_native_with_env(store, env.clone(), fd_filestat_set_size),
-            "fd_filestat_set_times" => Function::new_native_with_env(store, env.clone(), fd_filestat_set_times),
-            "fd_pread" => Function::new_native_with_env(store, env.clone(), fd_pread),
-            "fd_prestat_get" => Function::new_native_with_env(store, env.clone(), fd_prestat_get),
-            "fd_prestat_dir_name" => Function::new_native_with_env(store, env.clone(), fd_prestat_dir_name),
-            "fd_pwrite" => Function::new_native_with_env(store, env.clone(), fd_pwrite),
-            "fd_read" => Function::new_native_with_env(store, env.clone(), fd_read),
-            "fd_readdir" => Function::new_native_with_env(store, env.clone(), fd_readdir),
-            "fd_renumber" => Function::new_native_with_env(store, env.clone(), fd_renumber),
-            "fd_seek" => Function::new_native_with_env(store, env.clone(), legacy::snapshot0::fd_seek),
-            "fd_sync" => Function::new_native_with_env(store, env.clone(), fd_sync),
-            "fd_tell" => Function::new_native_with_env(store, env.clone(), fd_tell),
-            "fd_write" => Function::new_native_with_env(store, env.clone(), fd_write),
-            "path_create_directory" => Function::new_native_with_env(store, env.clone(), path_create_directory),
-            "path_filestat_get" => Function::new_native_with_env(store, env.clone(), legacy::snapshot0::path_filestat_get),
-            "path_filestat_set_times" => Function::new_native_with_env(store, env.clone(), path_filestat_set_times),
-            "path_link" => Function::new_native_with_env(store, env.clone(), path_link),
+            "args_get" => syscall!(store, env, args_get),
+            "args_sizes_get" => syscall!(store, env, args_sizes_get),
+            "clock_res_get" => syscall!(store, env, clock_res_get),
+            "clock_time_get" => syscall!(store, env, clock_time_get),
+            "environ_get" => syscall!(store, env, environ_get),
+            "environ_sizes_get" => syscall!(store, env, environ_sizes_get),
+            "fd_advise" => syscall!(store, env, fd_advise),
+            "fd_allocate" => syscall!(store, env, fd_allocate),
+            "fd_close" => syscall!(store, env, fd_close),
+            "fd_datasync" => syscall!(store, env, fd_datasync),
+            "fd_fdstat_get" => syscall!(store, env, fd_fdstat_get),
+            "fd_fdstat_set_flags" => syscall!(store, env, fd_fdstat_set_flags),
+            "fd_fdstat_set_rights" => syscall!(store, env, fd_fdstat_set_rights),
+            "fd_filestat_get" => syscall!(store, env, legacy::snapshot0::fd_filestat_get),
+            "fd_filestat_set_size" => syscall!(store, env, fd_filestat_set_size),
+            "fd_filestat_set_times" => syscall!(store, env, fd_filestat_set_times),
+            "fd_pread" => syscall!(store, env, fd_pread),
+            "fd_prestat_get" => syscall!(store, env, fd_prestat_get),
+            "fd_prestat_dir_name" => syscall!(store, env, fd_prestat_dir_name),
+            "fd_pwrite" => syscall!(store, env, fd_pwrite),
+            "fd_read" => syscall!(store, env, fd_read),
+            "fd_readdir" => syscall!(store, env, fd_readdir),
+            "fd_renumber" => syscall!(store, env, fd_renumber),
+            "fd_seek" => syscall!(store, env, legacy::snapshot0::fd_seek),
+            "fd_sync" => syscall!(store, env, fd_sync),
+            "fd_tell" => syscall!(store, env, fd_tell),
+            "fd_write" => syscall!(store, env, fd_write),
+            "path_create_directory" => syscall!(store, env, path_create_directory),
+            "path_filestat_get" => syscall!(store, env, legacy::snapshot0::path_filestat_get),
+            "path_filestat_set_times" => syscall!(store, env, path_filestat_set_times),
+            "path_link" => syscall!(store, env, path_link),
             "path_open" => get_path_open_for_store(store, env.clone()),
-            "path_readlink" => Function::new_native_with_env(store, env.clone(), path_readlink),
-            "path_remove_directory" => Function::new_native_with_env(store, env.clone(), path_remove_directory),
-            "path_rename" => Function::new_native_with_env(store, env.clone(), path_rename),
-            "path_symlink" => Function::new_native_with_env(store, env.clone(), path_symlink),
-            "path_unlink_file" => Function::new_native_with_env(store, env.clone(), path_unlink_file),
-            "poll_oneoff" => Function::new_native_with_env(store, env.clone(), legacy::snapshot0::poll_oneoff),
-            "proc_exit" => Function::new_native_with_env(store, env.clone(), proc_exit),
-            "proc_raise" => Function::new_native_with_env(store, env.clone(), proc_raise),
-            "random_get" => Function::new_native_with_env(store, env.clone(), random_get),
-            "sched_yield" => Function::new_native_with_env(store, env.clone(), sched_yield),
-            "sock_recv" => Function::new_native_with_env(store, env.clone(), sock_recv),
-            "sock_send" => Function::new_native_with_env(store, env.clone(), sock_send),
-            "sock_shutdown" => Function::new_native_with_env(store, env.clone(), sock_shutdown),
+            "path_readlink" => syscall!(store, env, path_readlink),
+            "path_remove_directory" => syscall!(store, env, path_remove_directory),
+            "path_rename" => syscall!(store, env, path_rename),
+            "path_symlink" => syscall!(store, env, path_symlink),
+            "path_unlink_file" => syscall!(store, env, path_unlink_file),
+            "poll_oneoff" => syscall!(store, env, legacy::snapshot0::poll_oneoff),
+            "proc_exit" => syscall!(store, env, proc_exit),
+            "proc_raise" => syscall!(store, env, proc_raise),
+            "random_get" => syscall!(store, env, random_get),
+            "sched_yield" => syscall!(store, env, sched_yield),
+            "sock_recv" => syscall!(store, env, sock_recv),
+            "sock_send" => syscall!(store, env, sock_send),
+            "sock_shutdown" => syscall!(store, env, sock_shutdown),
         },
     }
 }
@@ -238,51 +348,51 @@ fn generate_import_object_snapshot0(store: &Store, env: WasiEnv) -> ImportObject
 fn generate_import_object_snapshot1(store: &Store, env: WasiEnv) -> ImportObject {
     imports! {
         "wasi_snapshot_preview1" => {
-            "args_get" => Function::new_native_with_env(store, env.clone(), args_get),
-            "args_sizes_get" => Function::new_native_with_env(store, env.clone(), args_sizes_get),
-            "clock_res_get" => Function::new_native_with_env(store, env.clone(), clock_res_get),
-            "clock_time_get" => Function::new_native_with_env(store, env.clone(), clock_time_get),
-            "environ_get" => Function::new_native_with_env(store, env.clone(), environ_get),
-            "environ_sizes_get" => Function::new_native_with_env(store, env.clone(), environ_sizes_get),
-            "fd_advise" => Function::new_native_with_env(store, env.clone(), fd_advise),
-            "fd_allocate" => Function::new_native_with_env(store, env.clone(), fd_allocate),
-            "fd_close" => Function::new_native_with_env(store, env.clone(), fd_close),
-            "fd_datasync" => Function::new_native_with_env(store, env.clone(), fd_datasync),
-            "fd_fdstat_get" => Function::new_native_with_env(store, env.clone(), fd_fdstat_get),
-            "fd_fdstat_set_flags" => Function::new_native_with_env(store, env.clone(), fd_fdstat_set_flags),
-            "fd_fdstat_set_rights" => Function::new_native_with_env(store, env.clone(), fd_fdstat_set_rights),
-            "fd_filestat_get" => Function::new_native_with_env(store, env.clone(), fd_filestat_get),
-            "fd_filestat_set_size" => Function::new_native_with_env(store, env.clone(), fd_filestat_set_size),
-            "fd_filestat_set_times" => Function::new_native_with_env(store, env.clone(), fd_filestat_set_times),
-            "fd_pread" => Function::new_native_with_env(store, env.clone(), fd_pread),
-            "fd_prestat_get" => Function::new_native_with_env(store, env.clone(), fd_prestat_get),
-            "fd_prestat_dir_name" => Function::new_native_with_env(store, env.clone(), fd_prestat_dir_name),
-            "fd_pwrite" => Function::new_native_with_env(store, env.clone(), fd_pwrite),
-            "fd_read" => Function::new_native_with_env(store, env.clone(), fd_read),
-            "fd_readdir" => Function::new_native_with_env(store, env.clone(), fd_readdir),
-            "fd_renumber" => Function::new_native_with_env(store, env.clone(), fd_renumber),
-            "fd_seek" => Function::new_native_with_env(store, env.clone(), fd_seek),
-            "fd_sync" => Function::new_native_with_env(store, env.clone(), fd_sync),
-            "fd_tell" => Function::new_native_with_env(store, env.clone(), fd_tell),
-            "fd_write" => Function::new_native_with_env(store, env.clone(), fd_write),
-            "path_create_directory" => Function::new_native_with_env(store, env.clone(), path_create_directory),
-            "path_filestat_get" => Function::new_native_with_env(store, env.clone(), path_filestat_get),
-            "path_filestat_set_times" => Function::new_native_with_env(store, env.clone(), path_filestat_set_times),
-            "path_link" => Function::new_native_with_env(store, env.clone(), path_link),
+            "args_get" => syscall!(store, env, args_get),
+            "args_sizes_get" => syscall!(store, env, args_sizes_get),
+            "clock_res_get" => syscall!(store, env, clock_res_get),
+            "clock_time_get" => syscall!(store, env, clock_time_get),
+            "environ_get" => syscall!(store, env, environ_get),
+            "environ_sizes_get" => syscall!(store, env, environ_sizes_get),
+            "fd_advise" => syscall!(store, env, fd_advise),
+            "fd_allocate" => syscall!(store, env, fd_allocate),
+            "fd_close" => syscall!(store, env, fd_close),
+            "fd_datasync" => syscall!(store, env, fd_datasync),
+            "fd_fdstat_get" => syscall!(store, env, fd_fdstat_get),
+            "fd_fdstat_set_flags" => syscall!(store, env, fd_fdstat_set_flags),
+            "fd_fdstat_set_rights" => syscall!(store, env, fd_fdstat_set_rights),
+            "fd_filestat_get" => syscall!(store, env, fd_filestat_get),
+            "fd_filestat_set_size" => syscall!(store, env, fd_filestat_set_size),
+            "fd_filestat_set_times" => syscall!(store, env, fd_filestat_set_times),
+            "fd_pread" => syscall!(store, env, fd_pread),
+            "fd_prestat_get" => syscall!(store, env, fd_prestat_get),
+            "fd_prestat_dir_name" => syscall!(store, env, fd_prestat_dir_name),
+            "fd_pwrite" => syscall!(store, env, fd_pwrite),
+            "fd_read" => syscall!(store, env, fd_read),
+            "fd_readdir" => syscall!(store, env, fd_readdir),
+            "fd_renumber" => syscall!(store, env, fd_renumber),
+            "fd_seek" => syscall!(store, env, fd_seek),
+            "fd_sync" => syscall!(store, env, fd_sync),
+            "fd_tell" => syscall!(store, env, fd_tell),
+            "fd_write" => syscall!(store, env, fd_write),
+            "path_create_directory" => syscall!(store, env, path_create_directory),
+            "path_filestat_get" => syscall!(store, env, path_filestat_get),
+            "path_filestat_set_times" => syscall!(store, env, path_filestat_set_times),
+            "path_link" => syscall!(store, env, path_link),
             "path_open" => get_path_open_for_store(store, env.clone()),
-            "path_readlink" => Function::new_native_with_env(store, env.clone(), path_readlink),
-            "path_remove_directory" => Function::new_native_with_env(store, env.clone(), path_remove_directory),
-            "path_rename" => Function::new_native_with_env(store, env.clone(), path_rename),
-            "path_symlink" => Function::new_native_with_env(store, env.clone(), path_symlink),
-            "path_unlink_file" => Function::new_native_with_env(store, env.clone(), path_unlink_file),
-            "poll_oneoff" => Function::new_native_with_env(store, env.clone(), poll_oneoff),
-            "proc_exit" => Function::new_native_with_env(store, env.clone(), proc_exit),
-            "proc_raise" => Function::new_native_with_env(store, env.clone(), proc_raise),
-            "random_get" => Function::new_native_with_env(store, env.clone(), random_get),
-            "sched_yield" => Function::new_native_with_env(store, env.clone(), sched_yield),
-            "sock_recv" => Function::new_native_with_env(store, env.clone(), sock_recv),
-            "sock_send" => Function::new_native_with_env(store, env.clone(), sock_send),
-            "sock_shutdown" => Function::new_native_with_env(store, env.clone(), sock_shutdown),
+            "path_readlink" => syscall!(store, env, path_readlink),
+            "path_remove_directory" => syscall!(store, env, path_remove_directory),
+            "path_rename" => syscall!(store, env, path_rename),
+            "path_symlink" => syscall!(store, env, path_symlink),
+            "path_unlink_file" => syscall!(store, env, path_unlink_file),
+            "poll_oneoff" => syscall!(store, env, poll_oneoff),
+            "proc_exit" => syscall!(store, env, proc_exit),
+            "proc_raise" => syscall!(store, env, proc_raise),
+            "random_get" => syscall!(store, env, random_get),
+            "sched_yield" => syscall!(store, env, sched_yield),
+            "sock_recv" => syscall!(store, env, sock_recv),
+            "sock_send" => syscall!(store, env, sock_send),
+            "sock_shutdown" => syscall!(store, env, sock_shutdown),
         }
     }
 }
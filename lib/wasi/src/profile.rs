@@ -0,0 +1,96 @@
+//! Recording the number of calls to each WASI syscall and the time spent
+//! in them, see [`WasiEnv::enable_syscall_profiling`].
+
+use crate::WasiEnv;
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wasmer::{FromToNativeWasmType, Function, Store, WasmTypeList};
+
+/// The calls made to a syscall.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct SyscallStats {
+    /// The number of calls which returned.
+    pub calls: u64,
+    /// The total time spent in these calls.
+    pub time: Duration,
+}
+
+/// The calls made to each WASI syscall.
+#[derive(Debug, Default)]
+pub struct SyscallProfile {
+    stats: Mutex<BTreeMap<&'static str, SyscallStats>>,
+}
+
+impl SyscallProfile {
+    fn record(&self, syscall: &'static str, time: Duration) {
+        let mut stats = self.stats.lock().unwrap();
+        let stats = stats.entry(syscall).or_default();
+        stats.calls += 1;
+        stats.time += time;
+    }
+
+    /// Returns the calls made so far, by syscall name. Syscalls which
+    /// were never called are left out.
+    pub fn stats(&self) -> BTreeMap<&'static str, SyscallStats> {
+        self.stats.lock().unwrap().clone()
+    }
+}
+
+/// A syscall whose calls can be recorded in the [`SyscallProfile`] of its
+/// [`WasiEnv`].
+pub(crate) trait ProfiledSyscall<Args, Rets> {
+    /// Creates a host function calling this syscall, recording the time
+    /// spent in it and checking it against the syscall timeout.
+    fn new_profiled(self, store: &Store, env: WasiEnv) -> Function;
+}
+
+/// Returns the name of the syscall implemented by `F`, e.g. `fd_read`.
+fn syscall_name<F>() -> &'static str {
+    let name = std::any::type_name::<F>();
+    name.rsplit("::").next().unwrap_or(name)
+}
+
+macro_rules! impl_profiled_syscall {
+    ( $( $x:ident ),* ) => {
+        #[allow(non_snake_case)]
+        impl<F, $( $x, )* Rets> ProfiledSyscall<( $( $x, )* ), Rets> for F
+        where
+            F: Fn(&WasiEnv, $( $x ),*) -> Rets + Copy + Send + 'static,
+            $( $x: FromToNativeWasmType, )*
+            Rets: WasmTypeList,
+        {
+            fn new_profiled(self, store: &Store, env: WasiEnv) -> Function {
+                // Syscalls are function items, so the closure stays
+                // zero-sized as required by `new_native_with_env`.
+                let syscall = self;
+                Function::new_native_with_env(store, env, move |env: &WasiEnv, $( $x: $x ),*| {
+                    let start = Instant::now();
+                    if let Some(timeout) = &env.syscall_timeout {
+                        timeout.enter(syscall_name::<F>(), start);
+                    }
+                    let result = syscall(env, $( $x ),*);
+                    let elapsed = start.elapsed();
+                    if let Some(profile) = &env.profile {
+                        profile.record(syscall_name::<F>(), elapsed);
+                    }
+                    if let Some(timeout) = &env.syscall_timeout {
+                        timeout.leave(syscall_name::<F>(), elapsed);
+                    }
+                    result
+                })
+            }
+        }
+    };
+}
+
+impl_profiled_syscall!();
+impl_profiled_syscall!(A1);
+impl_profiled_syscall!(A1, A2);
+impl_profiled_syscall!(A1, A2, A3);
+impl_profiled_syscall!(A1, A2, A3, A4);
+impl_profiled_syscall!(A1, A2, A3, A4, A5);
+impl_profiled_syscall!(A1, A2, A3, A4, A5, A6);
+impl_profiled_syscall!(A1, A2, A3, A4, A5, A6, A7);
+impl_profiled_syscall!(A1, A2, A3, A4, A5, A6, A7, A8);
+impl_profiled_syscall!(A1, A2, A3, A4, A5, A6, A7, A8, A9);
@@ -1,7 +1,9 @@
 //! Builder system for configuring a [`WasiState`] and creating it.
 
-use crate::state::{WasiFile, WasiFs, WasiFsError, WasiState};
-use crate::syscalls::types::{__WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO};
+use crate::state::{SeededRng, WasiFile, WasiFs, WasiFsError, WasiState};
+use crate::syscalls::types::{
+    __wasi_timestamp_t, __WASI_STDERR_FILENO, __WASI_STDIN_FILENO, __WASI_STDOUT_FILENO,
+};
 use crate::WasiEnv;
 use std::path::{Path, PathBuf};
 use thiserror::Error;
@@ -18,6 +20,11 @@ pub(crate) fn create_wasi_state(program_name: &str) -> WasiStateBuilder {
 
 /// Convenient builder API for configuring WASI via [`WasiState`].
 ///
+/// Pre-opened directories get file descriptors in the order they are
+/// added, starting right after the virtual root at
+/// [`crate::VIRTUAL_ROOT_FD`]. Directories added in
+/// [`WasiStateBuilder::setup_fs`] come after all of them.
+///
 /// Usage:
 /// ```no_run
 /// # use wasmer_wasi::{WasiState, WasiStateCreationError};
@@ -42,6 +49,12 @@ pub struct WasiStateBuilder {
     stdout_override: Option<Box<dyn WasiFile>>,
     stderr_override: Option<Box<dyn WasiFile>>,
     stdin_override: Option<Box<dyn WasiFile>>,
+    random_seed: Option<u64>,
+    frozen_time: Option<__wasi_timestamp_t>,
+    clock_offset: i64,
+    deny_symlink_escape: bool,
+    strict_symlink_follow: bool,
+    max_open_files: Option<u32>,
 }
 
 impl std::fmt::Debug for WasiStateBuilder {
@@ -54,6 +67,12 @@ impl std::fmt::Debug for WasiStateBuilder {
             .field("stdout_override exists", &self.stdout_override.is_some())
             .field("stderr_override exists", &self.stderr_override.is_some())
             .field("stdin_override exists", &self.stdin_override.is_some())
+            .field("random_seed", &self.random_seed)
+            .field("frozen_time", &self.frozen_time)
+            .field("clock_offset", &self.clock_offset)
+            .field("deny_symlink_escape", &self.deny_symlink_escape)
+            .field("strict_symlink_follow", &self.strict_symlink_follow)
+            .field("max_open_files", &self.max_open_files)
             .finish()
     }
 }
@@ -282,6 +301,67 @@ impl WasiStateBuilder {
         self
     }
 
+    /// Make `random_get` return a reproducible stream of bytes derived
+    /// from `seed` instead of reading from the host's entropy source.
+    ///
+    /// The generated bytes are not suitable for cryptographic purposes.
+    pub fn random_seed(&mut self, seed: u64) -> &mut Self {
+        self.random_seed = Some(seed);
+
+        self
+    }
+
+    /// Make `clock_time_get` report `time`, in nanoseconds, for every
+    /// clock instead of reading the host clocks.
+    pub fn freeze_time(&mut self, time: __wasi_timestamp_t) -> &mut Self {
+        self.frozen_time = Some(time);
+
+        self
+    }
+
+    /// Shift the realtime clock reported by `clock_time_get` by `offset`
+    /// nanoseconds. Has no effect on the other clocks, nor when the time
+    /// is frozen with [`WasiStateBuilder::freeze_time`].
+    pub fn clock_offset(&mut self, offset: i64) -> &mut Self {
+        self.clock_offset = offset;
+
+        self
+    }
+
+    /// Set whether symlinks inside a preopened directory may point outside
+    /// of it. Resolving such a symlink fails with `ENOTCAPABLE` when this
+    /// is disabled.
+    ///
+    /// Symlinks can escape by default.
+    pub fn allow_symlink_escape(&mut self, toggle: bool) -> &mut Self {
+        self.deny_symlink_escape = !toggle;
+
+        self
+    }
+
+    /// Set whether `path_open` refuses, with `ELOOP`, to open a symlink
+    /// when the guest doesn't pass the follow flag, rather than following
+    /// the symlink anyway.
+    ///
+    /// Symlinks are followed anyway by default.
+    pub fn strict_symlink_follow(&mut self, toggle: bool) -> &mut Self {
+        self.strict_symlink_follow = toggle;
+
+        self
+    }
+
+    /// Limit how many files the guest may have open at once to `max`.
+    /// `path_open` fails with `EMFILE` once the limit is reached, until the
+    /// guest closes a file.
+    ///
+    /// The standard streams and the preopened directories don't count
+    /// towards the limit. There is no limit by default.
+    pub fn max_open_files(&mut self, max: u32) -> &mut Self {
+        self.max_open_files = Some(max);
+
+        self
+    }
+
     /// Setup the WASI filesystem before running
     // TODO: improve ergonomics on this function
     pub fn setup_fs(
@@ -365,6 +445,9 @@ impl WasiStateBuilder {
         #[allow(deprecated)]
         let mut wasi_fs = WasiFs::new_with_preopen(&self.preopens)
             .map_err(WasiStateCreationError::WasiFsCreationError)?;
+        wasi_fs.allow_symlink_escape = !self.deny_symlink_escape;
+        wasi_fs.strict_symlink_follow = self.strict_symlink_follow;
+        wasi_fs.max_open_files = self.max_open_files;
         // set up the file system, overriding base files and calling the setup function
         if let Some(stdin_override) = self.stdin_override.take() {
             wasi_fs
@@ -399,6 +482,9 @@ impl WasiStateBuilder {
                     env
                 })
                 .collect(),
+            rng: self.random_seed.map(SeededRng::new),
+            frozen_time: self.frozen_time,
+            clock_offset: self.clock_offset,
         })
     }
 
@@ -557,6 +643,22 @@ mod test {
         );
     }
 
+    #[test]
+    fn seeded_random_bytes_are_reproducible() {
+        let fill = |seed| {
+            let mut state = create_wasi_state("test_prog")
+                .random_seed(seed)
+                .build()
+                .unwrap();
+            let mut bytes = [0u8; 20];
+            state.rng.as_mut().unwrap().fill_bytes(&mut bytes);
+            bytes
+        };
+
+        assert_eq!(fill(42), fill(42));
+        assert_ne!(fill(42), fill(43));
+    }
+
     #[test]
     fn nul_character_in_args() {
         let output = create_wasi_state("test_prog").arg("--h\0elp").build();
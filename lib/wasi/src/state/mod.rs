@@ -30,7 +30,7 @@ use std::{
     cell::Cell,
     fs,
     io::Write,
-    path::{Path, PathBuf},
+    path::{Component, Path, PathBuf},
     time::SystemTime,
 };
 use tracing::debug;
@@ -163,6 +163,15 @@ pub struct WasiFs {
     inode_counter: Cell<u64>,
     /// for fds still open after the file has been deleted
     pub orphan_fds: HashMap<Inode, InodeVal>,
+    /// Whether symlinks in a preopened directory may resolve to a location
+    /// outside of that directory
+    pub(crate) allow_symlink_escape: bool,
+    /// Whether `path_open` refuses to open a symlink the guest didn't ask
+    /// to follow, instead of following it anyway
+    pub(crate) strict_symlink_follow: bool,
+    /// How many files the guest may have open at once, not counting the
+    /// standard streams and the preopened directories
+    pub(crate) max_open_files: Option<u32>,
 }
 
 impl WasiFs {
@@ -409,6 +418,9 @@ impl WasiFs {
                 }
                 assert!(existing_entry.is_none())
             }
+            if let Some(alias) = &alias {
+                wasi_fs.link_nested_preopen(root_inode, alias, inode)?;
+            }
             wasi_fs.preopen_fds.push(fd);
         }
 
@@ -428,6 +440,9 @@ impl WasiFs {
             next_fd: Cell::new(3),
             inode_counter: Cell::new(1024),
             orphan_fds: HashMap::new(),
+            allow_symlink_escape: true,
+            strict_symlink_follow: false,
+            max_open_files: None,
         };
         wasi_fs.create_stdin();
         wasi_fs.create_stdout();
@@ -655,6 +670,180 @@ impl WasiFs {
         }
     }
 
+    /// Pre-opens a read-only directory named `alias` which lives entirely in
+    /// memory and contains the given `files`.
+    ///
+    /// Each file is given as a path relative to the directory and its
+    /// contents; intermediate directories are created as needed.  Paths
+    /// must only consist of normal components, i.e. no `..`, `.` or root.
+    pub fn preopen_in_memory_dir(
+        &mut self,
+        alias: String,
+        files: &[(PathBuf, Vec<u8>)],
+    ) -> Result<__wasi_fd_t, String> {
+        let root_inode = self
+            .get_fd(VIRTUAL_ROOT_FD)
+            .map_err(|e| format!("Could not find the root fd: {}", e))?
+            .inode;
+        let dir_stat = __wasi_filestat_t {
+            st_filetype: __WASI_FILETYPE_DIRECTORY,
+            ..__wasi_filestat_t::default()
+        };
+        let kind = Kind::Dir {
+            parent: Some(root_inode),
+            path: PathBuf::new(),
+            entries: HashMap::new(),
+        };
+        let dir_inode = self.create_inode_with_stat(kind, true, alias.clone(), dir_stat);
+
+        for (path, contents) in files {
+            let mut cur_inode = dir_inode;
+            let mut components = path.components().peekable();
+            while let Some(component) = components.next() {
+                let name = match component {
+                    Component::Normal(name) => name.to_string_lossy().into_owned(),
+                    _ => return Err(format!("Invalid in-memory file path {:?}", path)),
+                };
+                let existing = match &self.inodes[cur_inode].kind {
+                    Kind::Dir { entries, .. } => entries.get(&name).cloned(),
+                    _ => return Err(format!("{:?} is not a directory", path)),
+                };
+                let is_file = components.peek().is_none();
+                cur_inode = match existing {
+                    Some(_) if is_file => {
+                        return Err(format!("Duplicate in-memory file path {:?}", path));
+                    }
+                    Some(inode) => inode,
+                    None => {
+                        let (kind, stat) = if is_file {
+                            let kind = Kind::Buffer {
+                                buffer: contents.clone(),
+                            };
+                            let stat = __wasi_filestat_t {
+                                st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                                st_size: contents.len() as u64,
+                                ..__wasi_filestat_t::default()
+                            };
+                            (kind, stat)
+                        } else {
+                            let kind = Kind::Dir {
+                                parent: Some(cur_inode),
+                                path: PathBuf::new(),
+                                entries: HashMap::new(),
+                            };
+                            (kind, dir_stat)
+                        };
+                        let inode = self.create_inode_with_stat(kind, false, name.clone(), stat);
+                        if let Kind::Dir { entries, .. } = &mut self.inodes[cur_inode].kind {
+                            entries.insert(name, inode);
+                        }
+                        inode
+                    }
+                };
+            }
+        }
+
+        let rights = __WASI_RIGHT_FD_ADVISE
+            | __WASI_RIGHT_FD_TELL
+            | __WASI_RIGHT_FD_SEEK
+            | __WASI_RIGHT_FD_READ
+            | __WASI_RIGHT_PATH_OPEN
+            | __WASI_RIGHT_FD_READDIR
+            | __WASI_RIGHT_PATH_READLINK
+            | __WASI_RIGHT_PATH_FILESTAT_GET
+            | __WASI_RIGHT_FD_FILESTAT_GET
+            | __WASI_RIGHT_POLL_FD_READWRITE;
+        let fd = self
+            .create_fd(rights, rights, 0, Fd::READ, dir_inode)
+            .map_err(|e| format!("Could not open fd for in-memory dir `{}`: {}", alias, e))?;
+        if let Kind::Root { entries } = &mut self.inodes[root_inode].kind {
+            if entries.insert(alias.clone(), dir_inode).is_some() {
+                return Err(format!("Found duplicate entry for alias `{}`", alias));
+            }
+        }
+        self.link_nested_preopen(root_inode, &alias, dir_inode)?;
+        self.preopen_fds.push(fd);
+        Ok(fd)
+    }
+
+    /// Makes the preopened directory `inode` reachable from the root by
+    /// the components of its `alias` when there are several, e.g. `app`
+    /// then `data` for `/app/data`, creating the directories leading to it
+    /// in memory where nothing else is preopened.  The guest can then
+    /// reach it through the root or list `/app` even though only
+    /// `/app/data` is preopened.
+    ///
+    /// Aliases with a single component or going up with `..` are only
+    /// reachable by their whole name, as before.
+    fn link_nested_preopen(
+        &mut self,
+        root_inode: Inode,
+        alias: &str,
+        inode: Inode,
+    ) -> Result<(), String> {
+        let not_a_directory = || {
+            format!(
+                "Can't preopen `{}` under something which isn't a directory",
+                alias
+            )
+        };
+        let mut names = Vec::new();
+        for component in Path::new(alias).components() {
+            match component {
+                Component::Normal(name) => names.push(name.to_string_lossy().into_owned()),
+                Component::ParentDir => return Ok(()),
+                _ => (),
+            }
+        }
+        let (last, parents) = match names.split_last() {
+            Some((last, parents)) if !parents.is_empty() => (last, parents),
+            _ => return Ok(()),
+        };
+
+        let mut cur_inode = root_inode;
+        for name in parents {
+            let existing = match &self.inodes[cur_inode].kind {
+                Kind::Root { entries } | Kind::Dir { entries, .. } => entries.get(name).cloned(),
+                _ => return Err(not_a_directory()),
+            };
+            cur_inode = match existing {
+                Some(inode) => inode,
+                None => {
+                    let kind = Kind::Dir {
+                        parent: Some(cur_inode),
+                        path: PathBuf::new(),
+                        entries: HashMap::new(),
+                    };
+                    let stat = __wasi_filestat_t {
+                        st_filetype: __WASI_FILETYPE_DIRECTORY,
+                        ..__wasi_filestat_t::default()
+                    };
+                    let dir_inode = self.create_inode_with_stat(kind, false, name.clone(), stat);
+                    match &mut self.inodes[cur_inode].kind {
+                        Kind::Root { entries } | Kind::Dir { entries, .. } => {
+                            entries.insert(name.clone(), dir_inode);
+                        }
+                        _ => unreachable!("the entries of a non-directory were looked up"),
+                    }
+                    dir_inode
+                }
+            };
+        }
+        match &mut self.inodes[cur_inode].kind {
+            Kind::Root { entries } | Kind::Dir { entries, .. } => {
+                if entries.insert(last.clone(), inode).is_some() {
+                    return Err(format!("Found duplicate entry for alias `{}`", alias));
+                }
+            }
+            _ => return Err(not_a_directory()),
+        }
+        // `..` goes back up the way the guest came down.
+        if let Kind::Dir { parent, .. } = &mut self.inodes[inode].kind {
+            *parent = Some(cur_inode);
+        }
+        Ok(())
+    }
+
     /// Change the backing of a given file descriptor
     /// Returns the old backing
     /// TODO: add examples
@@ -691,6 +880,124 @@ impl WasiFs {
         Ok(ret)
     }
 
+    /// Rebinds standard streams to files of the guest filesystem, each
+    /// given by its descriptor and its path as the guest sees it, e.g.
+    /// `out/log.txt` for `log.txt` in the directory pre-opened as `out`.
+    ///
+    /// Paths are resolved within the pre-opened directories with the rights
+    /// these were given: stdin needs read access, and stdout and stderr
+    /// need write access, plus the right to create the file if it doesn't
+    /// exist yet.  Output files are truncated and then appended to, so
+    /// stdout and stderr can share one.  Only regular files can be bound.
+    ///
+    /// Every path is resolved before any file is opened, so a path which
+    /// can't be bound leaves the files alone, and the streams are only
+    /// rebound once all of the files are open.
+    pub fn open_stdio_at_guest_paths(
+        &mut self,
+        paths: &[(__wasi_fd_t, String)],
+    ) -> Result<(), String> {
+        let mut targets = Vec::with_capacity(paths.len());
+        for (fd, path) in paths {
+            let target = self
+                .resolve_stdio_target(*fd, path)
+                .map_err(|e| format!("Could not bind {} to `{}`: {}", stdio_name(*fd), path, e))?;
+            targets.push((*fd, path, target));
+        }
+        let mut files = Vec::with_capacity(targets.len());
+        for (fd, path, target) in targets {
+            let file = target
+                .open()
+                .map_err(|e| format!("Could not open `{}` as {}: {}", path, stdio_name(fd), e))?;
+            files.push((fd, file));
+        }
+        for (fd, file) in files {
+            self.swap_file(fd, file).map_err(|e| e.to_string())?;
+        }
+        Ok(())
+    }
+
+    /// Finds the file the standard stream `fd` is to be bound to, at the
+    /// guest path `path`, without opening or creating it.
+    fn resolve_stdio_target(&mut self, fd: __wasi_fd_t, path: &str) -> Result<StdioTarget, String> {
+        let write = fd != __WASI_STDIN_FILENO;
+        let (base, relative_path) = self
+            .preopen_for_guest_path(Path::new(path))
+            .ok_or("it isn't in a pre-opened directory")?;
+        let rights = self.get_fd(base).map_err(|e| e.to_string())?.rights;
+        let (needed, access) = if write {
+            (__WASI_RIGHT_FD_WRITE, "writing")
+        } else {
+            (__WASI_RIGHT_FD_READ, "reading")
+        };
+        if rights & needed == 0 {
+            return Err(format!("its directory isn't pre-opened for {}", access));
+        }
+        if relative_path.as_os_str().is_empty() {
+            return Err("it is a pre-opened directory".to_string());
+        }
+        let relative_path = relative_path.to_string_lossy();
+        let errno = match self.get_inode_at_path(base, &relative_path, true) {
+            Ok(inode) => {
+                return match &self.inodes[inode].kind {
+                    Kind::File { path, .. } if path.is_file() => Ok(StdioTarget::HostFile {
+                        path: path.clone(),
+                        write,
+                        create: false,
+                    }),
+                    Kind::Buffer { buffer } if !write => Ok(StdioTarget::Buffer(buffer.clone())),
+                    Kind::Buffer { .. } => Err("it lives in memory and is read-only".to_string()),
+                    Kind::Dir { .. } | Kind::Root { .. } => Err("it is a directory".to_string()),
+                    Kind::File { .. } | Kind::Symlink { .. } => {
+                        Err("it isn't a regular file".to_string())
+                    }
+                }
+            }
+            Err(errno) => errno,
+        };
+        // A missing output file is created if the directory allows it.
+        if write && rights & __WASI_RIGHT_PATH_CREATE_FILE != 0 {
+            if let Ok((parent, name)) =
+                self.get_parent_inode_at_path(base, Path::new(&*relative_path), true)
+            {
+                match &self.inodes[parent].kind {
+                    // directories in memory have no host path to create in
+                    Kind::Dir { path, .. } if !path.as_os_str().is_empty() => {
+                        let path = path.join(&name);
+                        if name != ".." && name != "." && path.symlink_metadata().is_err() {
+                            return Ok(StdioTarget::HostFile {
+                                path,
+                                write,
+                                create: true,
+                            });
+                        }
+                    }
+                    _ => (),
+                }
+            }
+        }
+        Err(format!("it can't be opened: WASI error code {}", errno))
+    }
+
+    /// Splits a guest path into the pre-opened directory it is in, picking
+    /// the most nested one, and the rest of the path.
+    fn preopen_for_guest_path(&self, path: &Path) -> Option<(__wasi_fd_t, PathBuf)> {
+        let components = guest_path_components(path);
+        let mut found: Option<(usize, __wasi_fd_t)> = None;
+        for po_fd in &self.preopen_fds {
+            let po_inode = self.fd_map[po_fd].inode;
+            if let Kind::Root { .. } = self.inodes[po_inode].kind {
+                continue;
+            }
+            let prefix = guest_path_components(Path::new(&self.inodes[po_inode].name));
+            let is_longer = found.map_or(true, |(len, _)| prefix.len() > len);
+            if is_longer && components.starts_with(&prefix) {
+                found = Some((prefix.len(), *po_fd));
+            }
+        }
+        found.map(|(len, fd)| (fd, components[len..].iter().collect()))
+    }
+
     /// refresh size from filesystem
     pub(crate) fn filestat_resync_size(
         &mut self,
@@ -749,7 +1056,7 @@ impl WasiFs {
             // loading inodes as necessary
             'symlink_resolution: while symlink_count < MAX_SYMLINKS {
                 match &mut self.inodes[cur_inode].kind {
-                    Kind::Buffer { .. } => unimplemented!("state::get_inode_at_path for buffers"),
+                    Kind::Buffer { .. } => return Err(__WASI_ENOTDIR),
                     Kind::Dir {
                         ref mut entries,
                         ref path,
@@ -774,6 +1081,9 @@ impl WasiFs {
                             entries.get(component.as_os_str().to_string_lossy().as_ref())
                         {
                             cur_inode = *entry;
+                        } else if path.as_os_str().is_empty() {
+                            // the directory lives in memory; there is nothing to look up on the host
+                            return Err(__WASI_ENOENT);
                         } else {
                             let file = {
                                 let mut cd = path.clone();
@@ -808,6 +1118,8 @@ impl WasiFs {
 
                                 let (pre_open_dir_fd, relative_path) = if link_value.is_relative() {
                                     self.path_into_pre_open_and_relative_path(&file)?
+                                } else if !self.allow_symlink_escape {
+                                    return Err(__WASI_ENOTCAPABLE);
                                 } else {
                                     unimplemented!("Absolute symlinks are not yet supported");
                                 };
@@ -931,6 +1243,10 @@ impl WasiFs {
                             base.push(relative_path);
                             base.to_string_lossy().to_string()
                         };
+                        if !self.allow_symlink_escape && !path_stays_within_base(&new_path) {
+                            debug!("Symlink escapes its preopened directory");
+                            return Err(__WASI_ENOTCAPABLE);
+                        }
                         debug!("Following symlink recursively");
                         let symlink_inode = self.get_inode_at_path_inner(
                             new_base_dir,
@@ -974,7 +1290,9 @@ impl WasiFs {
             let po_inode = self.fd_map[po_fd].inode;
             let po_path = match &self.inodes[po_inode].kind {
                 Kind::Dir { path, .. } => &**path,
-                Kind::Root { .. } => Path::new("/"),
+                // symlinks are never relative to the virtual root, which
+                // would otherwise match every absolute host path
+                Kind::Root { .. } => continue,
                 _ => unreachable!("Preopened FD that's not a directory or the root"),
             };
             // stem path based on it
@@ -1148,7 +1466,7 @@ impl WasiFs {
 
         Ok(__wasi_fdstat_t {
             fs_filetype: match self.inodes[fd.inode].kind {
-                Kind::File { .. } => __WASI_FILETYPE_REGULAR_FILE,
+                Kind::File { .. } | Kind::Buffer { .. } => __WASI_FILETYPE_REGULAR_FILE,
                 Kind::Dir { .. } => __WASI_FILETYPE_DIRECTORY,
                 Kind::Symlink { .. } => __WASI_FILETYPE_SYMBOLIC_LINK,
                 _ => __WASI_FILETYPE_UNKNOWN,
@@ -1261,6 +1579,22 @@ impl WasiFs {
         })
     }
 
+    /// Fails with `EMFILE` if the guest already has as many files open as
+    /// [`WasiStateBuilder::max_open_files`] allows.
+    pub(crate) fn check_open_file_limit(&self) -> Result<(), __wasi_errno_t> {
+        if let Some(max) = self.max_open_files {
+            let open_files = self
+                .fd_map
+                .keys()
+                .filter(|fd| **fd > VIRTUAL_ROOT_FD && !self.preopen_fds.contains(fd))
+                .count();
+            if open_files >= max as usize {
+                return Err(__WASI_EMFILE);
+            }
+        }
+        Ok(())
+    }
+
     pub fn create_fd(
         &mut self,
         rights: __wasi_rights_t,
@@ -1397,7 +1731,20 @@ impl WasiFs {
                 }
                 None => path.metadata().ok()?,
             },
+            Kind::Dir { path, .. } if path.as_os_str().is_empty() => {
+                return Some(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_DIRECTORY,
+                    ..__wasi_filestat_t::default()
+                })
+            }
             Kind::Dir { path, .. } => path.metadata().ok()?,
+            Kind::Buffer { buffer } => {
+                return Some(__wasi_filestat_t {
+                    st_filetype: __WASI_FILETYPE_REGULAR_FILE,
+                    st_size: buffer.len() as u64,
+                    ..__wasi_filestat_t::default()
+                })
+            }
             Kind::Symlink {
                 base_po_dir,
                 path_to_symlink,
@@ -1540,6 +1887,15 @@ pub struct WasiState {
     pub fs: WasiFs,
     pub args: Vec<Vec<u8>>,
     pub envs: Vec<Vec<u8>>,
+    /// When set, `random_get` draws its bytes from this generator instead
+    /// of the host's entropy source.
+    pub(crate) rng: Option<SeededRng>,
+    /// When set, `clock_time_get` reports this timestamp, in nanoseconds,
+    /// instead of reading the host clocks.
+    pub(crate) frozen_time: Option<__wasi_timestamp_t>,
+    /// Nanoseconds added to the realtime clock reported by
+    /// `clock_time_get`, when the time isn't frozen.
+    pub(crate) clock_offset: i64,
 }
 
 impl WasiState {
@@ -1561,6 +1917,118 @@ impl WasiState {
     }
 }
 
+/// Checks that a relative path never goes above the directory it is
+/// relative to, e.g. `a/../b` does but `a/../../b` doesn't.
+fn path_stays_within_base(path: impl AsRef<Path>) -> bool {
+    let mut depth = 0usize;
+    for component in path.as_ref().components() {
+        match component {
+            Component::Normal(_) => depth += 1,
+            Component::CurDir => (),
+            Component::ParentDir => match depth.checked_sub(1) {
+                Some(new_depth) => depth = new_depth,
+                None => return false,
+            },
+            Component::RootDir | Component::Prefix(_) => return false,
+        }
+    }
+    true
+}
+
+/// The components of a guest path which name something, so that `/out`,
+/// `./out` and `out` all name the directory pre-opened as `out`.
+fn guest_path_components(path: &Path) -> Vec<Component<'_>> {
+    path.components()
+        .filter(|component| match component {
+            Component::RootDir | Component::CurDir | Component::Prefix(_) => false,
+            Component::Normal(_) | Component::ParentDir => true,
+        })
+        .collect()
+}
+
+fn stdio_name(fd: __wasi_fd_t) -> &'static str {
+    match fd {
+        __WASI_STDIN_FILENO => "stdin",
+        __WASI_STDOUT_FILENO => "stdout",
+        _ => "stderr",
+    }
+}
+
+/// A file a standard stream is bound to by
+/// [`WasiFs::open_stdio_at_guest_paths`], once its path is resolved.
+enum StdioTarget {
+    HostFile {
+        path: PathBuf,
+        write: bool,
+        /// Whether the file doesn't exist yet.
+        create: bool,
+    },
+    /// The contents of a file in an in-memory directory, for stdin.
+    Buffer(Vec<u8>),
+}
+
+impl StdioTarget {
+    fn open(self) -> std::io::Result<Box<dyn WasiFile>> {
+        match self {
+            StdioTarget::HostFile {
+                path, write: false, ..
+            } => {
+                let file = fs::File::open(&path)?;
+                Ok(Box::new(HostFile::new(file, path, true, false, false)))
+            }
+            StdioTarget::HostFile {
+                path,
+                write: true,
+                create,
+            } => {
+                let file = fs::OpenOptions::new()
+                    .append(true)
+                    .create(create)
+                    .open(&path)?;
+                file.set_len(0)?;
+                Ok(Box::new(HostFile::new(file, path, false, true, true)))
+            }
+            StdioTarget::Buffer(contents) => {
+                let mut pipe = Pipe::new();
+                pipe.write_all(&contents)?;
+                Ok(Box::new(pipe))
+            }
+        }
+    }
+}
+
+/// A deterministic pseudo-random number generator, used to make
+/// `random_get` reproducible across runs.
+///
+/// This is SplitMix64, which is fast and statistically sound but must not
+/// be used where cryptographic randomness is required.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct SeededRng {
+    state: u64,
+}
+
+impl SeededRng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self { state: seed }
+    }
+
+    fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    /// Fill `buf` with pseudo-random bytes.
+    pub(crate) fn fill_bytes(&mut self, buf: &mut [u8]) {
+        for chunk in buf.chunks_mut(8) {
+            let bytes = self.next_u64().to_le_bytes();
+            chunk.copy_from_slice(&bytes[..chunk.len()]);
+        }
+    }
+}
+
 pub fn host_file_type_to_wasi_file_type(file_type: fs::FileType) -> __wasi_filetype_t {
     // TODO: handle other file types
     if file_type.is_dir() {
@@ -1573,3 +2041,18 @@ pub fn host_file_type_to_wasi_file_type(file_type: fs::FileType) -> __wasi_filet
         __WASI_FILETYPE_UNKNOWN
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::path_stays_within_base;
+
+    #[test]
+    fn symlink_targets_within_base() {
+        assert!(path_stays_within_base("a/b"));
+        assert!(path_stays_within_base("./a/../b"));
+        assert!(path_stays_within_base("a/.."));
+        assert!(!path_stays_within_base(".."));
+        assert!(!path_stays_within_base("a/../../b"));
+        assert!(!path_stays_within_base("/etc/passwd"));
+    }
+}
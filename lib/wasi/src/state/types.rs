@@ -971,6 +971,100 @@ impl WasiFile for Stdin {
     }
 }
 
+/// A read-only host file which is only opened when it is first read.
+///
+/// Opening a FIFO blocks until the other end is opened too, so a FIFO used
+/// as stdin would otherwise block before the module even starts; with this
+/// type it's the module's first `fd_read` which blocks, as it would for a
+/// native program.  Until then the file has no raw fd and can't be polled.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LazyHostFile {
+    #[serde(skip)]
+    inner: Option<fs::File>,
+    pub host_path: PathBuf,
+}
+
+impl LazyHostFile {
+    /// Creates a file reading `host_path` once it's used.
+    pub fn new(host_path: PathBuf) -> Self {
+        Self {
+            inner: None,
+            host_path,
+        }
+    }
+
+    fn open(&mut self) -> io::Result<&mut fs::File> {
+        if self.inner.is_none() {
+            self.inner = Some(fs::File::open(&self.host_path)?);
+        }
+        Ok(self.inner.as_mut().unwrap())
+    }
+}
+
+impl Read for LazyHostFile {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.open()?.read(buf)
+    }
+}
+impl Seek for LazyHostFile {
+    fn seek(&mut self, _pos: io::SeekFrom) -> io::Result<u64> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not seek in a lazily opened file",
+        ))
+    }
+}
+impl Write for LazyHostFile {
+    fn write(&mut self, _buf: &[u8]) -> io::Result<usize> {
+        Err(io::Error::new(
+            io::ErrorKind::Other,
+            "can not write to a read-only file",
+        ))
+    }
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+#[typetag::serde]
+impl WasiFile for LazyHostFile {
+    fn last_accessed(&self) -> u64 {
+        0
+    }
+    fn last_modified(&self) -> u64 {
+        0
+    }
+    fn created_time(&self) -> u64 {
+        0
+    }
+    fn size(&self) -> u64 {
+        0
+    }
+    fn set_len(&mut self, _new_size: __wasi_filesize_t) -> Result<(), WasiFsError> {
+        Err(WasiFsError::PermissionDenied)
+    }
+    fn unlink(&mut self) -> Result<(), WasiFsError> {
+        Ok(())
+    }
+
+    fn bytes_available(&self) -> Result<usize, WasiFsError> {
+        match self.get_raw_fd() {
+            Some(host_fd) => host_file_bytes_available(host_fd),
+            None => Ok(0),
+        }
+    }
+
+    #[cfg(unix)]
+    fn get_raw_fd(&self) -> Option<i32> {
+        use std::os::unix::io::AsRawFd;
+        self.inner.as_ref().map(|file| file.as_raw_fd())
+    }
+    #[cfg(not(unix))]
+    fn get_raw_fd(&self) -> Option<i32> {
+        None
+    }
+}
+
 /// For piping stdio. Stores all output / input in a byte-vector.
 #[derive(Debug, Default, Serialize, Deserialize)]
 pub struct Pipe {
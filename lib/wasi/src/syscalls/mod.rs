@@ -233,7 +233,18 @@ pub fn clock_time_get(
     let memory = env.memory();
 
     let out_addr = wasi_try!(time.deref(memory));
-    let result = platform_clock_time_get(clock_id, precision, out_addr);
+    let state = env.state();
+    let result = if let Some(frozen_time) = state.frozen_time {
+        out_addr.set(frozen_time);
+        __WASI_ESUCCESS
+    } else {
+        let result = platform_clock_time_get(clock_id, precision, out_addr);
+        if result == __WASI_ESUCCESS && clock_id == __WASI_CLOCK_REALTIME {
+            let time = out_addr.get() as i64;
+            out_addr.set(time.saturating_add(state.clock_offset).max(0) as __wasi_timestamp_t);
+        }
+        result
+    };
     debug!(
         "time: {} => {}",
         wasi_try!(time.deref(memory)).get(),
@@ -982,28 +993,35 @@ pub fn fd_readdir(
             // we need to support multiple calls,
             // simple and obviously correct implementation for now:
             // maintain consistent order via lexacographic sorting
-            let fs_info = wasi_try!(wasi_try!(std::fs::read_dir(path).map_err(|_| __WASI_EIO))
-                .collect::<Result<Vec<_>, _>>()
-                .map_err(|_| __WASI_EIO));
-            let mut entry_vec = wasi_try!(fs_info
-                .into_iter()
-                .map(|entry| Ok((
-                    entry.file_name().to_string_lossy().to_string(),
-                    host_file_type_to_wasi_file_type(entry.file_type().map_err(|_| __WASI_EIO)?),
-                    0, // TODO: inode
-                )))
-                .collect::<Result<Vec<(String, u8, u64)>, _>>());
+            // directories living in memory have all their entries loaded
+            let in_memory = path.as_os_str().is_empty();
+            let mut entry_vec = if in_memory {
+                Vec::new()
+            } else {
+                let fs_info = wasi_try!(wasi_try!(std::fs::read_dir(path).map_err(|_| __WASI_EIO))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|_| __WASI_EIO));
+                wasi_try!(fs_info
+                    .into_iter()
+                    .map(|entry| Ok((
+                        entry.file_name().to_string_lossy().to_string(),
+                        host_file_type_to_wasi_file_type(
+                            entry.file_type().map_err(|_| __WASI_EIO)?
+                        ),
+                        0, // TODO: inode
+                    )))
+                    .collect::<Result<Vec<(String, u8, u64)>, _>>())
+            };
             entry_vec.extend(
                 entries
                     .iter()
-                    .filter(|(_, inode)| state.fs.inodes[**inode].is_preopened)
+                    .filter(|(_, inode)| in_memory || state.fs.inodes[**inode].is_preopened)
                     .map(|(name, inode)| {
                         let entry = &state.fs.inodes[*inode];
-                        (
-                            entry.name.to_string(),
-                            entry.stat.st_filetype,
-                            entry.stat.st_ino,
-                        )
+                        // a directory preopened under a nested alias is
+                        // named after all of it, but listed by its last part
+                        let name = if in_memory { name } else { &entry.name };
+                        (name.to_string(), entry.stat.st_filetype, entry.stat.st_ino)
                     }),
             );
             entry_vec.sort_by(|a, b| a.0.cmp(&b.0));
@@ -1144,10 +1162,12 @@ pub fn fd_seek(
                     // TODO: check this
                     return __WASI_EINVAL;
                 }
-                Kind::Buffer { .. } => {
-                    // seeking buffers probably makes sense
-                    // TODO: implement this
-                    return __WASI_EINVAL;
+                Kind::Buffer { ref buffer } => {
+                    let end = buffer.len();
+
+                    // reborrow
+                    let fd_entry = wasi_try!(state.fs.fd_map.get_mut(&fd).ok_or(__WASI_EBADF));
+                    fd_entry.offset = (end as i64 + offset) as u64;
                 }
             }
         }
@@ -1688,6 +1708,7 @@ pub fn path_open(
     }
 
     let fd_cell = wasi_try!(fd.deref(memory));
+    wasi_try!(state.fs.check_open_file_limit());
 
     // o_flags:
     // - __WASI_O_CREAT (create if it does not exist)
@@ -1712,6 +1733,16 @@ pub fn path_open(
         path_string,
         dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0,
     );
+    // a symlink is only returned when the guest didn't ask to follow it
+    let maybe_inode = match maybe_inode {
+        Ok(inode) if matches!(state.fs.inodes[inode].kind, Kind::Symlink { .. }) => {
+            if state.fs.strict_symlink_follow {
+                return __WASI_ELOOP;
+            }
+            state.fs.get_inode_at_path(dirfd, path_string, true)
+        }
+        otherwise => otherwise,
+    };
 
     let mut open_flags = 0;
     // TODO: traverse rights of dirs properly
@@ -1776,7 +1807,19 @@ pub fn path_open(
                     false,
                 )));
             }
-            Kind::Buffer { .. } => unimplemented!("wasi::path_open for Buffer type files"),
+            Kind::Buffer { .. } => {
+                // buffers are only created for read-only in-memory directories
+                if o_flags & __WASI_O_DIRECTORY != 0 {
+                    return __WASI_ENOTDIR;
+                }
+                if o_flags & __WASI_O_EXCL != 0 {
+                    return __WASI_EEXIST;
+                }
+                if o_flags & __WASI_O_TRUNC != 0 {
+                    return __WASI_EROFS;
+                }
+                open_flags |= Fd::READ;
+            }
             Kind::Dir { .. } | Kind::Root { .. } => {
                 // TODO: adjust these to be correct
                 if o_flags & __WASI_O_EXCL != 0 && path_arg.exists() {
@@ -1810,6 +1853,7 @@ pub fn path_open(
                 dirflags & __WASI_LOOKUP_SYMLINK_FOLLOW != 0
             ));
             let new_file_host_path = match &state.fs.inodes[parent_inode].kind {
+                Kind::Dir { path, .. } if path.as_os_str().is_empty() => return __WASI_EROFS,
                 Kind::Dir { path, .. } => {
                     let mut new_path = path.clone();
                     new_path.push(&new_entity_name);
@@ -2448,7 +2492,9 @@ pub fn poll_oneoff(
             if remaining_ns > 0 {
                 debug!("Sleeping for {} nanoseconds", remaining_ns);
                 let duration = std::time::Duration::from_nanos(remaining_ns as u64);
-                std::thread::sleep(duration);
+                if !env.sleep(duration) {
+                    return __WASI_EINTR;
+                }
                 total_ns_slept += remaining_ns;
             }
         }
@@ -2523,8 +2569,7 @@ pub fn poll_oneoff(
 
 pub fn proc_exit(env: &WasiEnv, code: __wasi_exitcode_t) {
     debug!("wasi::proc_exit, {}", code);
-    RuntimeError::raise(Box::new(WasiError::Exit(code)));
-    unreachable!();
+    env.raise(WasiError::Exit(code))
 }
 
 pub fn proc_raise(env: &WasiEnv, sig: __wasi_signal_t) -> __wasi_errno_t {
@@ -2545,9 +2590,16 @@ pub fn random_get(env: &WasiEnv, buf: WasmPtr<u8, Array>, buf_len: u32) -> __was
 
     let buf = wasi_try!(buf.deref(memory, 0, buf_len));
 
+    let mut state = env.state();
     let res = unsafe {
         let u8_buffer = &mut *(buf as *const [_] as *mut [_] as *mut [u8]);
-        getrandom::getrandom(u8_buffer)
+        match state.rng.as_mut() {
+            Some(rng) => {
+                rng.fill_bytes(u8_buffer);
+                Ok(())
+            }
+            None => getrandom::getrandom(u8_buffer),
+        }
     };
     match res {
         Ok(()) => __WASI_ESUCCESS,
@@ -2559,6 +2611,7 @@ pub fn random_get(env: &WasiEnv, buf: WasmPtr<u8, Array>, buf_len: u32) -> __was
 /// Yields execution of the thread
 pub fn sched_yield(env: &WasiEnv) -> __wasi_errno_t {
     debug!("wasi::sched_yield");
+    env.check_interrupted();
     ::std::thread::yield_now();
     __WASI_ESUCCESS
 }
@@ -0,0 +1,66 @@
+//! Bounding the time spent in each WASI syscall, see
+//! [`WasiEnv::set_syscall_timeout`].
+
+use crate::WasiError;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use wasmer::RuntimeError;
+
+/// Watches that no WASI syscall runs for longer than a timeout.
+///
+/// A syscall which returns late makes the module trap with
+/// [`WasiError::SyscallTimeout`]. To end one which doesn't return, the
+/// embedder checks [`SyscallTimeout::expired`] from another thread and
+/// stops the module with an [`InterruptHandle`](crate::InterruptHandle),
+/// which wakes up a `poll_oneoff` sleeping.
+#[derive(Debug)]
+pub struct SyscallTimeout {
+    timeout: Duration,
+    /// The syscall running, if any, and when it was called.
+    running: Mutex<Option<(&'static str, Instant)>>,
+}
+
+impl SyscallTimeout {
+    pub(crate) fn new(timeout: Duration) -> Self {
+        Self {
+            timeout,
+            running: Mutex::new(None),
+        }
+    }
+
+    /// Returns the time each syscall is allowed to take.
+    pub fn timeout(&self) -> Duration {
+        self.timeout
+    }
+
+    /// Returns the name of the syscall running for longer than the
+    /// timeout, if any.
+    pub fn expired(&self) -> Option<&'static str> {
+        match *self.running.lock().unwrap() {
+            Some((syscall, start)) if start.elapsed() > self.timeout => Some(syscall),
+            _ => None,
+        }
+    }
+
+    /// Forgets the syscall running, for one which unwinds the module
+    /// instead of returning, so it isn't reported as expired once the
+    /// module has stopped.
+    pub(crate) fn abandon(&self) {
+        // The lock must be released before unwinding, which skips
+        // destructors.
+        self.running.lock().unwrap().take();
+    }
+
+    pub(crate) fn enter(&self, syscall: &'static str, start: Instant) {
+        *self.running.lock().unwrap() = Some((syscall, start));
+    }
+
+    /// Fails with [`WasiError::SyscallTimeout`], unwinding the module, if
+    /// `syscall` took longer than the timeout.
+    pub(crate) fn leave(&self, syscall: &'static str, elapsed: Duration) {
+        self.abandon();
+        if elapsed > self.timeout {
+            RuntimeError::raise(Box::new(WasiError::SyscallTimeout(syscall)));
+        }
+    }
+}
@@ -7,6 +7,9 @@ use wasmer_engine_jit::JIT;
 #[cfg(feature = "test-native")]
 use wasmer_engine_native::Native;
 
+// The compilers keep their default optimization level, so the tests
+// guarding optimizations, such as `memory_redundancy.wast`, check the code
+// modules are usually compiled to.
 pub fn get_compiler(canonicalize_nans: bool) -> impl CompilerConfig {
     cfg_if::cfg_if! {
         if #[cfg(any(
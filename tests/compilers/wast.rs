@@ -96,3 +96,35 @@ pub fn run_wast(wast_path: &str, compiler: &str) -> anyhow::Result<()> {
     let path = Path::new(wast_path);
     wast.run_file(path)
 }
+
+#[cfg(feature = "test-catch-panics")]
+#[test]
+fn wast_panic_names_directive() {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    use wasmer::{imports, Function};
+
+    let store = get_store(Features::default(), false);
+    let import_object = imports! {
+        "host" => {
+            "panic" => Function::new_native(&store, || panic!("host function panicked")),
+        },
+    };
+    let mut wast = Wast::new(store, import_object);
+    let script = br#"(module
+  (import "host" "panic" (func $panic))
+  (func (export "one") (result i32) (i32.const 1))
+  (func (export "panic") (call $panic)))
+(assert_return (invoke "one") (i32.const 1))
+(invoke "panic")
+(assert_return (invoke "one") (i32.const 1))
+"#;
+    let payload = catch_unwind(AssertUnwindSafe(|| wast.run_buffer("panic.wast", script)))
+        .expect_err("the directive's panic should propagate");
+    let message = payload.downcast_ref::<String>().unwrap();
+    assert!(
+        message.contains("directive at panic.wast:6:"),
+        "{}",
+        message
+    );
+    assert!(message.contains("host function panicked"), "{}", message);
+}
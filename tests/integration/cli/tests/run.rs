@@ -0,0 +1,1911 @@
+//! CLI tests for the run subcommand.
+
+use anyhow::bail;
+use std::fs;
+use std::path::Path;
+use std::process::{Command, Output};
+use wasmer_integration_tests_cli::{get_wasmer_path, ASSET_PATH};
+
+/// A WASI module writing the result of two 16-byte `random_get` calls to
+/// stdout.
+const RANDOM_BYTES_WAT: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "random_get" (func $random_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (drop (call $random_get (i32.const 16) (i32.const 16)))
+    (drop (call $random_get (i32.const 32) (i32.const 16)))
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 32))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))
+"#;
+
+/// Write `wat` to a file in `dir` and run it with `wasmer run`.
+fn run_wat(dir: &Path, wat: &str, args: &[&str]) -> anyhow::Result<Output> {
+    let wasm_path = dir.join("module.wat");
+    fs::write(&wasm_path, wat)?;
+    let output = Command::new(get_wasmer_path())
+        .current_dir(dir)
+        .arg("run")
+        .arg(&wasm_path)
+        .args(args)
+        .output()?;
+    Ok(output)
+}
+
+/// Fail with the command's stderr if it did not exit successfully.
+fn check_success(output: &Output) -> anyhow::Result<()> {
+    if !output.status.success() {
+        bail!(
+            "wasmer run failed with: stdout: {}\n\nstderr: {}",
+            String::from_utf8_lossy(&output.stdout),
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn run_random_seed_is_reproducible() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let first = run_wat(temp_dir.path(), RANDOM_BYTES_WAT, &["--random-seed", "42"])?;
+    check_success(&first)?;
+    let second = run_wat(temp_dir.path(), RANDOM_BYTES_WAT, &["--random-seed", "42"])?;
+    check_success(&second)?;
+    let other = run_wat(temp_dir.path(), RANDOM_BYTES_WAT, &["--random-seed", "43"])?;
+    check_success(&other)?;
+
+    assert_eq!(first.stdout.len(), 32);
+    assert_eq!(first.stdout, second.stdout);
+    assert_ne!(first.stdout, other.stdout);
+    Ok(())
+}
+
+#[test]
+fn run_deterministic_is_reproducible() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let first = run_wat(temp_dir.path(), RANDOM_BYTES_WAT, &["--deterministic"])?;
+    check_success(&first)?;
+    let second = run_wat(temp_dir.path(), RANDOM_BYTES_WAT, &["--deterministic"])?;
+    check_success(&second)?;
+
+    assert_eq!(first.stdout, second.stdout);
+    Ok(())
+}
+
+#[test]
+fn run_entry_args_json() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat = r#"(module
+  (func (export "echo") (param i32 i64 f32 f64) (result i32 i64 f32 f64)
+    (local.get 0) (local.get 1) (local.get 2) (local.get 3)))"#;
+    let invoke = |json: &str| {
+        run_wat(
+            temp_dir.path(),
+            wat,
+            &["--invoke", "echo", "--entry-args-json", json],
+        )
+    };
+
+    let output = invoke(r#"[1, -2, 2.5, 3]"#)?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1 -2 2.5 3\n");
+
+    let output = invoke(r#"["0xffffffff", "0x10", "0x3fc00000", "0x4030000000000000"]"#)?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "-1 16 1.5 16\n");
+
+    let output = invoke(r#"[1.5, 2, 3, 4]"#)?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Can't convert argument 0 `1.5` into a i32"));
+    Ok(())
+}
+
+#[test]
+fn run_invoke_all_reports_every_failure() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+             (func (export "test_b") unreachable)
+             (func (export "test_a"))
+             (func (export "test_c") unreachable)
+             (func (export "helper") unreachable))"#,
+        &["--invoke-all", "test_"],
+    )?;
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert_eq!(
+        stdout,
+        "test_a ... ok\ntest_b ... FAILED\ntest_c ... FAILED\n"
+    );
+    assert!(stderr.contains("2 of 3 functions failed"));
+    Ok(())
+}
+
+/// A WASI module exiting with the errno returned when opening `link`, with
+/// the given lookup flags, in the first pre-opened directory (fd 4, right
+/// after the virtual root).
+#[cfg(unix)]
+fn open_link_wat(lookup_flags: u32) -> String {
+    format!(
+        r#"(module
+  (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "link")
+  (func (export "_start")
+    (call $proc_exit
+      (call $path_open (i32.const 4) (i32.const {}) (i32.const 0) (i32.const 4) (i32.const 0)
+                       (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16)))))"#,
+        lookup_flags
+    )
+}
+
+/// Create `pre/link` pointing to `target` and `outside/secret.txt` in `dir`,
+/// returning the `--mapdir` arguments exposing both directories.
+#[cfg(unix)]
+fn create_symlink_fixture(dir: &Path, target: &str) -> anyhow::Result<Vec<String>> {
+    let pre = dir.join("pre");
+    let outside = dir.join("outside");
+    fs::create_dir(&pre)?;
+    fs::create_dir(&outside)?;
+    fs::write(pre.join("inside.txt"), "inside")?;
+    fs::write(outside.join("secret.txt"), "secret")?;
+    std::os::unix::fs::symlink(target, pre.join("link"))?;
+    Ok(vec![
+        "--mapdir".to_string(),
+        format!("pre:{}", pre.display()),
+        "--mapdir".to_string(),
+        format!("outside:{}", outside.display()),
+    ])
+}
+
+#[cfg(unix)]
+#[test]
+fn run_symlink_escape_is_denied_by_default() -> anyhow::Result<()> {
+    const ENOTCAPABLE: i32 = 76;
+    let temp_dir = tempfile::tempdir()?;
+    let mapdirs = create_symlink_fixture(temp_dir.path(), "../outside/secret.txt")?;
+    let mut args = mapdirs.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let output = run_wat(temp_dir.path(), &open_link_wat(1), &args)?;
+    assert_eq!(output.status.code(), Some(ENOTCAPABLE));
+
+    args.push("--allow-symlink-escape");
+    let output = run_wat(temp_dir.path(), &open_link_wat(1), &args)?;
+    check_success(&output)?;
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_no_follow_symlinks() -> anyhow::Result<()> {
+    const ELOOP: i32 = 32;
+    let temp_dir = tempfile::tempdir()?;
+    let mapdirs = create_symlink_fixture(temp_dir.path(), "inside.txt")?;
+    let mut args = mapdirs.iter().map(String::as_str).collect::<Vec<_>>();
+
+    let output = run_wat(temp_dir.path(), &open_link_wat(0), &args)?;
+    check_success(&output)?;
+
+    args.push("--no-follow-symlinks");
+    let output = run_wat(temp_dir.path(), &open_link_wat(0), &args)?;
+    assert_eq!(output.status.code(), Some(ELOOP));
+    let output = run_wat(temp_dir.path(), &open_link_wat(1), &args)?;
+    check_success(&output)?;
+    Ok(())
+}
+
+/// A WASI module printing the contents of `data/hello.txt` in the first
+/// pre-opened directory (fd 4, right after the virtual root).
+const READ_HELLO_WAT: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "data/hello.txt")
+  (func (export "_start")
+    (if (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 14) (i32.const 0)
+                         (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16))
+      (then unreachable))
+    (i32.store (i32.const 32) (i32.const 64))
+    (i32.store (i32.const 36) (i32.const 64))
+    (if (call $fd_read (i32.load (i32.const 16)) (i32.const 32) (i32.const 1) (i32.const 40))
+      (then unreachable))
+    (i32.store (i32.const 48) (i32.const 64))
+    (i32.store (i32.const 52) (i32.load (i32.const 40)))
+    (drop (call $fd_write (i32.const 1) (i32.const 48) (i32.const 1) (i32.const 56)))))
+"#;
+
+/// Write a tar archive with the given files to `path`.  Paths are stored
+/// verbatim, without any validation.
+fn write_tar(path: &Path, files: &[(&str, &str)]) -> anyhow::Result<()> {
+    let mut builder = tar::Builder::new(fs::File::create(path)?);
+    for (name, contents) in files {
+        let mut header = tar::Header::new_gnu();
+        header.as_gnu_mut().unwrap().name[..name.len()].copy_from_slice(name.as_bytes());
+        header.set_size(contents.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        builder.append(&header, contents.as_bytes())?;
+    }
+    builder.finish()?;
+    Ok(())
+}
+
+#[test]
+fn run_fs_archive() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let archive = temp_dir.path().join("fixtures.tar");
+    write_tar(&archive, &[("data/hello.txt", "hello from the archive")])?;
+
+    let fs_arg = format!("fixtures:{}", archive.display());
+    let output = run_wat(temp_dir.path(), READ_HELLO_WAT, &["--fs", &fs_arg])?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"hello from the archive");
+    Ok(())
+}
+
+#[test]
+fn run_fs_archive_rejects_invalid_archives() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let archive = temp_dir.path().join("fixtures.tar");
+    let fs_arg = format!("fixtures:{}", archive.display());
+
+    write_tar(&archive, &[])?;
+    let output = run_wat(temp_dir.path(), READ_HELLO_WAT, &["--fs", &fs_arg])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("contains no files"));
+
+    write_tar(&archive, &[("../data/hello.txt", "escaped")])?;
+    let output = run_wat(temp_dir.path(), READ_HELLO_WAT, &["--fs", &fs_arg])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("outside of its root"));
+    Ok(())
+}
+
+#[test]
+fn run_mapfile() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let host_dir = temp_dir.path().join("host");
+    fs::create_dir(&host_dir)?;
+    fs::write(host_dir.join("hello.txt"), "hello from the mapped file")?;
+    fs::write(host_dir.join("other.txt"), "a sibling")?;
+
+    let mapfile_arg = format!(
+        "fixtures/data/hello.txt={}",
+        host_dir.join("hello.txt").display()
+    );
+    let output = run_wat(
+        temp_dir.path(),
+        READ_HELLO_WAT,
+        &["--mapfile", &mapfile_arg],
+    )?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"hello from the mapped file");
+
+    let read_sibling_wat = READ_HELLO_WAT.replace("data/hello.txt", "data/other.txt");
+    let output = run_wat(
+        temp_dir.path(),
+        &read_sibling_wat,
+        &["--mapfile", &mapfile_arg],
+    )?;
+    assert!(!output.status.success());
+    assert!(output.stdout.is_empty());
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_mapfile_follows_symlinks() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::write(temp_dir.path().join("hello.txt"), "hello through a link")?;
+    let link = temp_dir.path().join("link.txt");
+    std::os::unix::fs::symlink("hello.txt", &link)?;
+
+    let mapfile_arg = format!("fixtures/data/hello.txt={}", link.display());
+    let output = run_wat(
+        temp_dir.path(),
+        READ_HELLO_WAT,
+        &["--mapfile", &mapfile_arg],
+    )?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"hello through a link");
+
+    fs::remove_file(temp_dir.path().join("hello.txt"))?;
+    let output = run_wat(
+        temp_dir.path(),
+        READ_HELLO_WAT,
+        &["--mapfile", &mapfile_arg],
+    )?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("failed to resolve the mapped file"));
+    Ok(())
+}
+
+#[test]
+fn run_max_open_files() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::write(temp_dir.path().join("hello.txt"), "hello")?;
+    // Open `hello.txt` until `path_open` fails, trapping unless it fails
+    // with `EMFILE`, and exit with the number of files opened.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 0) "hello.txt")
+  (func (export "_start")
+    (local $opened i32)
+    (local $errno i32)
+    (block $done
+      (loop $open
+        (local.set $errno
+          (call $path_open (i32.const 4) (i32.const 0) (i32.const 0) (i32.const 9) (i32.const 0)
+                           (i64.const -1) (i64.const -1) (i32.const 0) (i32.const 16)))
+        (br_if $done (local.get $errno))
+        (local.set $opened (i32.add (local.get $opened) (i32.const 1)))
+        (br_if $open (i32.lt_u (local.get $opened) (i32.const 100)))))
+    (if (i32.ne (local.get $errno) (i32.const 33))
+      (then unreachable))
+    (call $proc_exit (local.get $opened))))"#;
+    let dir_arg = format!("data:{}", temp_dir.path().display());
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--mapdir", &dir_arg, "--max-open-files", "3"],
+    )?;
+    assert_eq!(output.status.code(), Some(3));
+    Ok(())
+}
+
+#[test]
+fn run_reports_non_wasi_imports() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+             (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+             (import "env" "host_callback" (func))
+             (func (export "_start")))"#,
+        &[],
+    )?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("WASI doesn't provide"));
+    assert!(stderr.contains("`env`.`host_callback`"));
+    Ok(())
+}
+
+#[test]
+fn run_rejects_modules_importing_both_wasi_and_emscripten() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+             (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+             (import "env" "_emscripten_memcpy_big" (func (param i32 i32 i32) (result i32)))
+             (func (export "_start")))"#,
+        &[],
+    )?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+
+    assert!(!output.status.success());
+    assert!(stderr.contains("imports both the WASI and the Emscripten ABIs"));
+    assert!(stderr.contains("--abi"));
+    Ok(())
+}
+
+#[test]
+fn run_emscripten_module() -> anyhow::Result<()> {
+    let wasm_path = format!(
+        "{}/../deprecated/assets/emscripten_hello_world.wasm",
+        ASSET_PATH
+    );
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&wasm_path)
+        .args(&["--argv0", "hello", "--", "first", "second"])
+        .output()?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "Hello, world\nArg 0: 'hello'\nArg 1: 'first'\nArg 2: 'second'\n"
+    );
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&wasm_path)
+        .args(&["--abi", "emscripten", "--dry-run"])
+        .output()?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"Emscripten module instantiated\n");
+    Ok(())
+}
+
+#[test]
+fn run_pre_open_current_dir() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::write(temp_dir.path().join("hello.txt"), "hello")?;
+    // Print the name of the first pre-opened directory followed by the raw
+    // directory entries read from it.
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+  (import "wasi_snapshot_preview1" "fd_prestat_dir_name" (func $fd_prestat_dir_name (param i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_readdir" (func $fd_readdir (param i32 i32 i32 i64 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (if (call $fd_prestat_dir_name (i32.const 4) (i32.const 0) (i32.const 1))
+      (then unreachable))
+    (i32.store (i32.const 16) (i32.const 0))
+    (i32.store (i32.const 20) (i32.const 1))
+    (drop (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 24)))
+    (if (call $fd_readdir (i32.const 4) (i32.const 128) (i32.const 1024) (i64.const 0) (i32.const 32))
+      (then unreachable))
+    (i32.store (i32.const 40) (i32.const 128))
+    (i32.store (i32.const 44) (i32.load (i32.const 32)))
+    (drop (call $fd_write (i32.const 1) (i32.const 40) (i32.const 1) (i32.const 24)))))"#,
+        &["--pre-open-current-dir"],
+    )?;
+    check_success(&output)?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.starts_with('.'));
+    assert!(stdout.contains("hello.txt"));
+    assert!(stdout.contains("module.wat"));
+    Ok(())
+}
+
+#[test]
+fn run_dry_run_does_not_call_start() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+             (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+             (func (export "_start") unreachable))"#,
+        &["--dry-run"],
+    )?;
+    check_success(&output)?;
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(stdout.contains("WASI versions: `wasi_snapshot_preview1`"));
+    assert!(stdout.contains("Entry point: `_start` (command)"));
+    Ok(())
+}
+
+#[test]
+fn run_argv0() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Print argv[0], assuming it's the only argument.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+    (drop (call $args_get (i32.const 16) (i32.const 256)))
+    (i32.store (i32.const 32) (i32.load (i32.const 16)))
+    (i32.store (i32.const 36) (i32.sub (i32.load (i32.const 4)) (i32.const 1)))
+    (drop (call $fd_write (i32.const 1) (i32.const 32) (i32.const 1) (i32.const 40)))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &[])?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"module.wat");
+
+    let output = run_wat(temp_dir.path(), wat, &["--argv0", "my-program"])?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"my-program");
+    Ok(())
+}
+
+#[test]
+fn run_stdin_string() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Exit with the number of bytes read from stdin, like `wc -c`.
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+  (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (local $total i32)
+    (i32.store (i32.const 0) (i32.const 64))
+    (i32.store (i32.const 4) (i32.const 4))
+    (block $done
+      (loop $read
+        (br_if $done (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+        (br_if $done (i32.eqz (i32.load (i32.const 8))))
+        (local.set $total (i32.add (local.get $total) (i32.load (i32.const 8))))
+        (br $read)))
+    (call $proc_exit (local.get $total))))"#,
+        &["--stdin-string", "hello, wörld"],
+    )?;
+
+    assert_eq!(output.status.code(), Some("hello, wörld".len() as i32));
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_stdin_fifo_is_opened_lazily() -> anyhow::Result<()> {
+    use std::io::{Read, Write};
+    use std::process::Stdio;
+    use std::sync::mpsc;
+    use std::time::Duration;
+
+    let temp_dir = tempfile::tempdir()?;
+    let fifo = temp_dir.path().join("stdin.fifo");
+    let status = Command::new("mkfifo").arg(&fifo).status()?;
+    if !status.success() {
+        bail!("mkfifo failed with {}", status);
+    }
+    // Print `started\n`, then copy stdin to stdout until it ends.
+    let wasm_path = temp_dir.path().join("module.wat");
+    fs::write(
+        &wasm_path,
+        r#"(module
+  (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 64) "started\n")
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 64))
+    (i32.store (i32.const 4) (i32.const 8))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+    (i32.store (i32.const 0) (i32.const 128))
+    (i32.store (i32.const 4) (i32.const 64))
+    (block $done
+      (loop $copy
+        (br_if $done (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+        (br_if $done (i32.eqz (i32.load (i32.const 8))))
+        (i32.store (i32.const 16) (i32.const 128))
+        (i32.store (i32.const 20) (i32.load (i32.const 8)))
+        (drop (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 24)))
+        (br $copy)))))"#,
+    )?;
+    let mut child = Command::new(get_wasmer_path())
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg(&wasm_path)
+        .arg("--stdin")
+        .arg(&fifo)
+        .stdout(Stdio::piped())
+        .spawn()?;
+
+    // The module must start while nothing has opened the FIFO for writing.
+    let mut stdout = child.stdout.take().unwrap();
+    let (sender, receiver) = mpsc::channel();
+    std::thread::spawn(move || {
+        let mut started = [0; 8];
+        let result = stdout.read_exact(&mut started).map(|()| (started, stdout));
+        sender.send(result).ok();
+    });
+    let (started, mut stdout) = match receiver.recv_timeout(Duration::from_secs(60)) {
+        Ok(result) => result?,
+        Err(_) => {
+            child.kill()?;
+            bail!("the module didn't start before its stdin FIFO was opened");
+        }
+    };
+    assert_eq!(&started, b"started\n");
+
+    fs::OpenOptions::new()
+        .write(true)
+        .open(&fifo)?
+        .write_all(b"sent after instantiation")?;
+    let mut rest = Vec::new();
+    stdout.read_to_end(&mut rest)?;
+    assert!(child.wait()?.success());
+    assert_eq!(rest, b"sent after instantiation");
+    Ok(())
+}
+
+/// A WASI module printing `started\n` and then calling `sched_yield`
+/// forever.
+const SPIN_WAT: &str = r#"
+(module
+  (import "wasi_snapshot_preview1" "sched_yield" (func $sched_yield (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 64) "started\n")
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 64))
+    (i32.store (i32.const 4) (i32.const 8))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))
+    (loop $spin
+      (drop (call $sched_yield))
+      (br $spin))))
+"#;
+
+#[test]
+fn run_timeout_stops_spinning_module() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output = run_wat(temp_dir.path(), SPIN_WAT, &["--timeout", "200ms"])?;
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, b"started\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("WASI execution was interrupted"));
+    Ok(())
+}
+
+/// Runs `SPIN_WAT` with `args`, sends it `SIGINT` once it has started, and
+/// returns how it exited along with its stderr.
+#[cfg(unix)]
+fn interrupt_spinning_module(args: &[&str]) -> anyhow::Result<(std::process::ExitStatus, String)> {
+    use std::io::Read;
+    use std::process::Stdio;
+    use std::time::{Duration, Instant};
+
+    let temp_dir = tempfile::tempdir()?;
+    let wasm_path = temp_dir.path().join("module.wat");
+    fs::write(&wasm_path, SPIN_WAT)?;
+    let mut child = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&wasm_path)
+        .args(args)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()?;
+    let mut started = [0; 8];
+    child.stdout.take().unwrap().read_exact(&mut started)?;
+    assert_eq!(&started, b"started\n");
+
+    let status = Command::new("kill")
+        .args(&["-INT", &child.id().to_string()])
+        .status()?;
+    assert!(status.success());
+    let deadline = Instant::now() + Duration::from_secs(60);
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if Instant::now() >= deadline {
+            child.kill()?;
+            bail!("the module kept running after SIGINT");
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    };
+    let mut stderr = String::new();
+    child.stderr.take().unwrap().read_to_string(&mut stderr)?;
+    Ok((status, stderr))
+}
+
+#[cfg(unix)]
+#[test]
+fn run_sigint_stops_spinning_module() -> anyhow::Result<()> {
+    let (status, stderr) = interrupt_spinning_module(&["--timeout", "1h"])?;
+    assert!(!status.success());
+    assert!(
+        stderr.contains("WASI execution was interrupted"),
+        "{}",
+        stderr
+    );
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_sigint_kills_module_without_timeout() -> anyhow::Result<()> {
+    use std::os::unix::process::ExitStatusExt;
+
+    let (status, _) = interrupt_spinning_module(&[])?;
+    // `SIGINT`
+    assert_eq!(status.signal(), Some(2));
+    Ok(())
+}
+
+#[test]
+fn run_freeze_time() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Trap unless both the realtime and the monotonic clocks report
+    // 2001-02-03T04:05:06Z.
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+  (import "wasi_snapshot_preview1" "clock_time_get" (func $clock_time_get (param i32 i64 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func $check (param $clock i32)
+    (if (call $clock_time_get (local.get $clock) (i64.const 1) (i32.const 0))
+      (then unreachable))
+    (if (i64.ne (i64.load (i32.const 0)) (i64.const 981173106000000000))
+      (then unreachable)))
+  (func (export "_start")
+    (call $check (i32.const 0))
+    (call $check (i32.const 1))))"#,
+        &["--freeze-time", "2001-02-03T04:05:06Z"],
+    )?;
+
+    check_success(&output)
+}
+
+#[test]
+fn run_stack_size() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Recurse four million times, which needs far more than the usual 8 MiB
+    // of stack.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (func $depth (param i32) (result i32)
+    (if (result i32) (i32.eqz (local.get 0))
+      (then (i32.const 0))
+      (else (i32.add (i32.const 1) (call $depth (i32.sub (local.get 0) (i32.const 1)))))))
+  (func (export "_start")
+    (drop (call $depth (i32.const 4000000)))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &[])?;
+    assert!(!output.status.success());
+
+    let output = run_wat(temp_dir.path(), wat, &["--stack-size", "1073741824"])?;
+    check_success(&output)
+}
+
+#[test]
+fn run_stack_size_reports_host_panics() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // `proc_raise` is not implemented and panics.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_raise" (func $proc_raise (param i32) (result i32)))
+  (func (export "_start")
+    (drop (call $proc_raise (i32.const 9)))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &["--stack-size", "8388608"])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(1), "{}", stderr);
+    assert!(
+        stderr.contains("The `wasmer-wasi` thread panicked"),
+        "{}",
+        stderr
+    );
+    Ok(())
+}
+
+#[test]
+fn run_coredump() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let coredump_path = temp_dir.path().join("module.coredump");
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "memory contents")
+  (func $crash unreachable)
+  (func (export "_start") (call $crash)))"#,
+        &["--coredump", coredump_path.to_str().unwrap()],
+    )?;
+    assert!(!output.status.success());
+
+    let coredump = fs::read(&coredump_path)?;
+    assert!(coredump.starts_with(b"\0asm"));
+    let contains = |needle: &[u8]| coredump.windows(needle.len()).any(|w| w == needle);
+    assert!(contains(b"corestack"));
+    assert!(contains(b"memory contents"));
+    Ok(())
+}
+
+#[test]
+fn run_log_syscall_timing() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let profile_path = temp_dir.path().join("profile.csv");
+    // Copy stdin to stdout, four bytes at a time.
+    let output = run_wat(
+        temp_dir.path(),
+        r#"(module
+  (import "wasi_snapshot_preview1" "fd_read" (func $fd_read (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 64))
+    (i32.store (i32.const 4) (i32.const 4))
+    (block $done
+      (loop $copy
+        (br_if $done (call $fd_read (i32.const 0) (i32.const 0) (i32.const 1) (i32.const 8)))
+        (br_if $done (i32.eqz (i32.load (i32.const 8))))
+        (i32.store (i32.const 16) (i32.const 64))
+        (i32.store (i32.const 20) (i32.load (i32.const 8)))
+        (drop (call $fd_write (i32.const 1) (i32.const 16) (i32.const 1) (i32.const 24)))
+        (br $copy)))))"#,
+        &[
+            "--stdin-string",
+            "hello, world",
+            "--log-syscall-timing",
+            "--profile-out",
+            profile_path.to_str().unwrap(),
+        ],
+    )?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "hello, world");
+
+    let profile = fs::read_to_string(&profile_path)?;
+    let calls = |syscall: &str| -> Option<u64> {
+        profile.lines().find_map(|line| {
+            let fields = line.split(',').collect::<Vec<_>>();
+            if fields[0] == syscall {
+                fields[1].parse().ok()
+            } else {
+                None
+            }
+        })
+    };
+    assert_eq!(calls("fd_read"), Some(4));
+    assert_eq!(calls("fd_write"), Some(3));
+    Ok(())
+}
+
+#[test]
+fn run_wasm_features() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat = r#"(module
+  (func (export "_start")
+    (drop (i32x4.extract_lane 0 (v128.const i32x4 1 2 3 4)))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &["--wasm-features", "-simd"])?;
+    assert!(!output.status.success());
+
+    let output = run_wat(temp_dir.path(), wat, &["--wasm-features", "+simd,-threads"])?;
+    check_success(&output)?;
+
+    let output = run_wat(temp_dir.path(), wat, &["--wasm-features", "+gc"])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Unknown Wasm feature `gc`"));
+    Ok(())
+}
+
+#[test]
+fn run_precompiled_artifact() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let artifact_path = temp_dir.path().join("module.wasmu");
+    let seed = ["--random-seed", "42"];
+
+    let source = run_wat(temp_dir.path(), RANDOM_BYTES_WAT, &seed)?;
+    check_success(&source)?;
+    let precompile = run_wat(
+        temp_dir.path(),
+        RANDOM_BYTES_WAT,
+        &["--precompile", artifact_path.to_str().unwrap()],
+    )?;
+    check_success(&precompile)?;
+    assert!(precompile.stdout.is_empty());
+
+    let artifact = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&artifact_path)
+        .args(&seed)
+        .output()?;
+    check_success(&artifact)?;
+    assert_eq!(artifact.stdout, source.stdout);
+    Ok(())
+}
+
+#[test]
+fn run_quiet() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Importing from two WASI versions makes `wasmer run` warn.
+    let wat = r#"(module
+  (import "wasi_unstable" "proc_exit" (func (param i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "hello")
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 5))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &[])?;
+    check_success(&output)?;
+    assert!(String::from_utf8_lossy(&output.stderr).contains("more than 1 WASI version"));
+
+    let output = run_wat(temp_dir.path(), wat, &["--quiet"])?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"hello");
+    assert!(output.stderr.is_empty());
+
+    let artifact_path = temp_dir.path().join("module.wasmu");
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["-q", "--precompile", artifact_path.to_str().unwrap()],
+    )?;
+    check_success(&output)?;
+    assert!(output.stdout.is_empty());
+    assert!(output.stderr.is_empty());
+    Ok(())
+}
+
+#[test]
+fn run_preopen_fd() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::create_dir(temp_dir.path().join("a"))?;
+    fs::create_dir(temp_dir.path().join("b"))?;
+    // Print the names of the directories pre-opened at fds 4 and 5; fd 3 is
+    // the virtual root.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "fd_prestat_get" (func $fd_prestat_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_prestat_dir_name" (func $fd_prestat_dir_name (param i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 100) "\n")
+  (func $print_name (param $fd i32)
+    (drop (call $fd_prestat_get (local.get $fd) (i32.const 0)))
+    (drop (call $fd_prestat_dir_name (local.get $fd) (i32.const 32) (i32.load (i32.const 4))))
+    (i32.store (i32.const 8) (i32.const 32))
+    (i32.store (i32.const 12) (i32.load (i32.const 4)))
+    (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 104)))
+    (i32.store (i32.const 8) (i32.const 100))
+    (i32.store (i32.const 12) (i32.const 1))
+    (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 104))))
+  (func (export "_start")
+    (call $print_name (i32.const 4))
+    (call $print_name (i32.const 5))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &["--dir", "a", "--dir", "b"])?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "a\nb\n");
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--dir", "a", "--dir", "b", "--preopen-fd", "b=4"],
+    )?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "b\na\n");
+
+    let output = run_wat(temp_dir.path(), wat, &["--dir", "a", "--preopen-fd", "c=4"])?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("`c` is pinned with `--preopen-fd` but is not pre-opened"));
+    Ok(())
+}
+
+#[test]
+fn run_max_memory() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Grow the memory from one page to two, then trap unless growing it to
+    // three pages fails.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory 1)
+  (func (export "_start")
+    (if (i32.ne (memory.grow (i32.const 1)) (i32.const 1)) (then unreachable))
+    (if (i32.ne (memory.grow (i32.const 1)) (i32.const -1)) (then unreachable))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &[])?;
+    assert!(!output.status.success());
+
+    let output = run_wat(temp_dir.path(), wat, &["--max-memory", "2"])?;
+    check_success(&output)?;
+
+    let output = run_wat(temp_dir.path(), wat, &["--max-memory", "128KiB"])?;
+    check_success(&output)?;
+
+    let output = run_wat(temp_dir.path(), wat, &["--max-memory", "0"])?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+/// A WASI module writing its environment to stdout, each variable followed
+/// by a NUL.
+const PRINT_ENV_WAT: &str = r#"(module
+  (import "wasi_snapshot_preview1" "environ_sizes_get" (func $environ_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "environ_get" (func $environ_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (drop (call $environ_sizes_get (i32.const 0) (i32.const 4)))
+    (drop (call $environ_get (i32.const 256) (i32.const 1024)))
+    (i32.store (i32.const 8) (i32.const 1024))
+    (i32.store (i32.const 12) (i32.load (i32.const 4)))
+    (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 16)))))"#;
+
+#[test]
+fn run_env_inherits_host_variables() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wasm_path = temp_dir.path().join("module.wat");
+    fs::write(&wasm_path, PRINT_ENV_WAT)?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("run")
+        .arg(&wasm_path)
+        .args(&["--env", "GIVEN=value", "--env", "INHERITED"])
+        .args(&["--env", "WASMER_TEST_UNSET"])
+        .env("INHERITED", "from host")
+        .env_remove("WASMER_TEST_UNSET")
+        .output()?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "GIVEN=value\0INHERITED=from host\0"
+    );
+    Ok(())
+}
+
+#[test]
+fn run_wasi_version() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    for (namespace, version, other) in &[
+        ("wasi_unstable", "snapshot0", "snapshot1"),
+        ("wasi_snapshot_preview1", "snapshot1", "snapshot0"),
+    ] {
+        let wat = format!(
+            r#"(module
+  (import "{}" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "hello")
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const 5))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#,
+            namespace
+        );
+
+        let output = run_wat(temp_dir.path(), &wat, &["--wasi-version", version])?;
+        check_success(&output)?;
+        assert_eq!(output.stdout, b"hello");
+
+        let output = run_wat(temp_dir.path(), &wat, &["--wasi-version", other])?;
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(!output.status.success());
+        assert!(
+            stderr.contains(&format!("This module imports WASI `{}`", namespace)),
+            "{}",
+            stderr
+        );
+    }
+    Ok(())
+}
+
+#[test]
+fn run_create_dirs() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Create `file` in the directory pre-opened at fd 4 and write to it.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 32) "file")
+  (data (i32.const 64) "written")
+  (func (export "_start")
+    (local $errno i32)
+    (local.set $errno
+      (call $path_open (i32.const 4) (i32.const 0) (i32.const 32) (i32.const 4)
+        (i32.const 1) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 0)))
+    (if (local.get $errno) (then (call $proc_exit (local.get $errno))))
+    (i32.store (i32.const 8) (i32.const 64))
+    (i32.store (i32.const 12) (i32.const 7))
+    (drop (call $fd_write (i32.load (i32.const 0)) (i32.const 8) (i32.const 1) (i32.const 16)))))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &["--mapdir", "out:./nonexistent"])?;
+    assert!(!output.status.success());
+    assert!(!temp_dir.path().join("nonexistent").exists());
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--mapdir", "out:./nonexistent", "--create-dirs"],
+    )?;
+    check_success(&output)?;
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("nonexistent/file"))?,
+        "written"
+    );
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--mapdir", "out:./read-only:read", "--create-dirs"],
+    )?;
+    assert!(!output.status.success());
+    assert!(!temp_dir.path().join("read-only").exists());
+    Ok(())
+}
+
+#[test]
+fn run_repeat() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat = r#"(module
+  (global $counter (mut i32) (i32.const 0))
+  (func (export "increment") (result i32)
+    (global.set $counter (i32.add (global.get $counter) (i32.const 1)))
+    (global.get $counter)))"#;
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--invoke", "increment", "--repeat", "3"],
+    )?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "1\n2\n3\n");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("3 calls: min "));
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--invoke", "increment", "--repeat", "0"],
+    )?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+const MATH_WAT: &str = r#"(module
+  (func (export "add") (param i32 i32) (result i32)
+    (i32.add (local.get 0) (local.get 1))))"#;
+
+#[test]
+fn run_preload() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::write(temp_dir.path().join("math.wat"), MATH_WAT)?;
+
+    let wat = r#"(module
+  (import "math" "add" (func $add (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (func (export "_start")
+    (call $proc_exit (call $add (i32.const 2) (i32.const 3)))))"#;
+    let output = run_wat(temp_dir.path(), wat, &["--preload", "math=math.wat"])?;
+    assert_eq!(output.status.code(), Some(5));
+
+    let wat = r#"(module
+  (import "math" "add" (func $add (param i32 i32) (result i32)))
+  (func (export "double") (param i32) (result i32)
+    (call $add (local.get 0) (local.get 0))))"#;
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--preload", "math=math.wat", "--invoke", "double", "21"],
+    )?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "42\n");
+    Ok(())
+}
+
+#[test]
+fn run_preload_reports_unresolved_imports() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::write(temp_dir.path().join("math.wat"), MATH_WAT)?;
+
+    let wat = r#"(module
+  (import "math" "sub" (func $sub (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (func (export "_start")
+    (call $proc_exit (call $sub (i32.const 3) (i32.const 2)))))"#;
+    let output = run_wat(temp_dir.path(), wat, &["--preload", "math=math.wat"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`math`.`sub`"), "{}", stderr);
+    assert!(stderr.contains("the preloaded module `math`"), "{}", stderr);
+    Ok(())
+}
+
+#[cfg(unix)]
+#[test]
+fn run_profile_flamegraph() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let profile_path = temp_dir.path().join("profile.folded");
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (global $sum (mut i32) (i32.const 0))
+  (func $hot (param $n i32)
+    (loop $spin
+      (global.set $sum (i32.add (global.get $sum) (local.get $n)))
+      (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+      (br_if $spin (local.get $n))))
+  (func (export "_start")
+    (call $hot (i32.const 300000000))))"#;
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &[
+            "--profile",
+            "flamegraph",
+            "--profile-out",
+            profile_path.to_str().unwrap(),
+        ],
+    )?;
+    check_success(&output)?;
+
+    let profile = fs::read_to_string(&profile_path)?;
+    assert!(!profile.is_empty());
+    for line in profile.lines() {
+        let count = line.rsplit(' ').next().unwrap();
+        assert!(count.parse::<u64>().is_ok(), "{}", line);
+    }
+    assert!(
+        profile.lines().any(|line| line.contains("hot")),
+        "{}",
+        profile
+    );
+
+    let output = run_wat(temp_dir.path(), wat, &["--profile", "flamegraph"])?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+#[test]
+fn run_duplicate_preopens() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    for (dir, text) in &[("host", "first"), ("other", "second")] {
+        fs::create_dir_all(temp_dir.path().join(dir).join("data"))?;
+        fs::write(temp_dir.path().join(dir).join("data/hello.txt"), text)?;
+    }
+
+    // The duplicate is dropped, so `c` gets the file descriptor 5.
+    let read_fd5_wat =
+        READ_HELLO_WAT.replace("$path_open (i32.const 4)", "$path_open (i32.const 5)");
+    let args = [
+        "--mapdir", "a:host", "--mapdir", "b:./host", "--mapdir", "c:other",
+    ];
+    let output = run_wat(temp_dir.path(), &read_fd5_wat, &args)?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"second");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("`b` is the same host directory as `a`"),
+        "{}",
+        stderr
+    );
+
+    let output = run_wat(
+        temp_dir.path(),
+        &read_fd5_wat,
+        &[&args[..], &["-q"]].concat(),
+    )?;
+    check_success(&output)?;
+    assert!(output.stderr.is_empty());
+
+    let output = run_wat(
+        temp_dir.path(),
+        &read_fd5_wat,
+        &[&args[..], &["--strict-preopens"]].concat(),
+    )?;
+    assert!(!output.status.success());
+    Ok(())
+}
+
+#[test]
+fn run_nested_preopens() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::create_dir_all(temp_dir.path().join("host/data"))?;
+    fs::write(temp_dir.path().join("host/data/hello.txt"), "nested")?;
+
+    let args = ["--mapdir", "inner:host/data", "--mapdir", "outer:host"];
+    let read_outer_wat =
+        READ_HELLO_WAT.replace("$path_open (i32.const 4)", "$path_open (i32.const 5)");
+    let output = run_wat(temp_dir.path(), &read_outer_wat, &args)?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"nested");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("`inner` is inside the host directory of `outer`"),
+        "{}",
+        stderr
+    );
+
+    let output = run_wat(
+        temp_dir.path(),
+        &read_outer_wat,
+        &[&args[..], &["--strict-preopens"]].concat(),
+    )?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("--strict-preopens"), "{}", stderr);
+    Ok(())
+}
+
+#[test]
+fn run_memory_dump() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let dump_path = temp_dir.path().join("memory.bin");
+    let dump_arg = dump_path.to_str().unwrap();
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (func $fill
+    (i64.store (i32.const 16) (i64.const 0x0807060504030201)))
+  (func (export "_start") (call $fill))
+  (func (export "fill_then_trap")
+    (call $fill)
+    unreachable))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &["--memory-dump", dump_arg])?;
+    check_success(&output)?;
+    let dump = fs::read(&dump_path)?;
+    assert_eq!(dump.len(), 65536);
+    assert_eq!(&dump[16..24], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    assert!(dump[..16].iter().chain(&dump[24..]).all(|byte| *byte == 0));
+
+    // `--invoke` doesn't provide WASI.
+    fs::remove_file(&dump_path)?;
+    let wat = wat.replace(
+        r#"(import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))"#,
+        "",
+    );
+    let output = run_wat(
+        temp_dir.path(),
+        &wat,
+        &["--memory-dump", dump_arg, "--invoke", "fill_then_trap"],
+    )?;
+    assert!(!output.status.success());
+    let dump = fs::read(&dump_path)?;
+    assert_eq!(&dump[16..24], &[1, 2, 3, 4, 5, 6, 7, 8]);
+    Ok(())
+}
+
+#[test]
+fn run_exit_on_trap_code() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let trap_wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (func (export "_start") unreachable))"#;
+    let exit_wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (func (export "_start") (call $proc_exit (i32.const 5))))"#;
+
+    let output = run_wat(temp_dir.path(), trap_wat, &[])?;
+    assert_eq!(output.status.code(), Some(1));
+
+    let output = run_wat(temp_dir.path(), trap_wat, &["--exit-on-trap-code", "42"])?;
+    assert_eq!(output.status.code(), Some(42));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("unreachable"));
+
+    let output = run_wat(temp_dir.path(), exit_wat, &["--exit-on-trap-code", "42"])?;
+    assert_eq!(output.status.code(), Some(5));
+
+    // Errors other than traps keep exiting with 1.
+    let output = run_wat(
+        temp_dir.path(),
+        trap_wat,
+        &["--exit-on-trap-code", "42", "--invoke", "missing"],
+    )?;
+    assert_eq!(output.status.code(), Some(1));
+    Ok(())
+}
+
+#[test]
+fn run_nested_mapdir() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::create_dir(temp_dir.path().join("data"))?;
+    fs::write(
+        temp_dir.path().join("data/hello.txt"),
+        "hello from /app/data",
+    )?;
+    // Open the file through the root, fd 3, as `/app` isn't preopened.
+    let wat = READ_HELLO_WAT
+        .replace("\"data/hello.txt\"", "\"app/data/hello.txt\"")
+        .replace(
+            "(i32.const 4) (i32.const 0) (i32.const 0) (i32.const 14)",
+            "(i32.const 3) (i32.const 0) (i32.const 0) (i32.const 18)",
+        );
+    let output = run_wat(temp_dir.path(), &wat, &["--mapdir", "/app/data:data"])?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"hello from /app/data");
+    Ok(())
+}
+
+#[test]
+fn run_wasi_env_inherit_path() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wasm_path = temp_dir.path().join("module.wat");
+    fs::write(&wasm_path, PRINT_ENV_WAT)?;
+    let run = |args: &[&str]| {
+        Command::new(get_wasmer_path())
+            .arg("run")
+            .arg(&wasm_path)
+            .args(args)
+            .env_clear()
+            .env("PATH", "/usr/bin")
+            .env("HOME", "/home/host")
+            .env("TERM", "dumb")
+            .env("SECRET", "hidden")
+            .output()
+    };
+
+    let output = run(&["--wasi-env-inherit-path", "--env", "HOME=/home/guest"])?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "PATH=/usr/bin\0TERM=dumb\0HOME=/home/guest\0"
+    );
+
+    let output = run(&[
+        "--wasi-env-inherit-path",
+        "--wasi-env-inherit-set",
+        "TERM,SECRET",
+    ])?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "TERM=dumb\0SECRET=hidden\0"
+    );
+    Ok(())
+}
+
+/// Every file under `dir`, recursively.
+fn files_in(dir: &Path) -> anyhow::Result<Vec<std::path::PathBuf>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            files.extend(files_in(&path)?);
+        } else {
+            files.push(path);
+        }
+    }
+    Ok(files)
+}
+
+#[test]
+fn run_cache_dir() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let print_wat = |text: &str| {
+        format!(
+            r#"(module
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 16) "{}")
+  (func (export "_start")
+    (i32.store (i32.const 0) (i32.const 16))
+    (i32.store (i32.const 4) (i32.const {}))
+    (drop (call $fd_write (i32.const 1) (i32.const 0) (i32.const 1) (i32.const 8)))))"#,
+            text,
+            text.len()
+        )
+    };
+    let (first_wat, second_wat) = (print_wat("first"), print_wat("second"));
+    let first_cache = temp_dir.path().join("first-cache");
+    let second_cache = temp_dir.path().join("second-cache");
+    let first_cache_arg = first_cache.to_str().unwrap();
+    let second_cache_arg = second_cache.to_str().unwrap();
+
+    let output = run_wat(
+        temp_dir.path(),
+        &first_wat,
+        &["--cache-dir", first_cache_arg],
+    )?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"first");
+    let output = run_wat(
+        temp_dir.path(),
+        &second_wat,
+        &["--cache-dir", second_cache_arg],
+    )?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"second");
+    let first_files = files_in(&first_cache)?;
+    let second_files = files_in(&second_cache)?;
+    assert_eq!((first_files.len(), second_files.len()), (1, 1));
+
+    // Running the first module again loads its artifact rather than
+    // compiling it, which shows once the artifact is swapped for the
+    // second module's.
+    fs::copy(&second_files[0], &first_files[0])?;
+    let output = run_wat(
+        temp_dir.path(),
+        &first_wat,
+        &["--cache-dir", first_cache_arg],
+    )?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"second");
+
+    // Other Wasm features don't use that artifact.
+    let output = run_wat(
+        temp_dir.path(),
+        &first_wat,
+        &[
+            "--cache-dir",
+            first_cache_arg,
+            "--wasm-features=-reference-types",
+        ],
+    )?;
+    check_success(&output)?;
+    assert_eq!(output.stdout, b"first");
+    assert_eq!(files_in(&first_cache)?.len(), 2);
+    Ok(())
+}
+
+#[test]
+fn run_input() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Sums the bytes at 16, whose count is at 8.
+    let wat = r#"(module
+  (memory (export "memory") 1)
+  (func (export "checksum") (result i32)
+    (local $i i32) (local $end i32) (local $sum i32)
+    (local.set $i (i32.const 16))
+    (local.set $end (i32.add (i32.const 16) (i32.load (i32.const 8))))
+    (block $done
+      (loop $next
+        (br_if $done (i32.ge_u (local.get $i) (local.get $end)))
+        (local.set $sum (i32.add (local.get $sum) (i32.load8_u (local.get $i))))
+        (local.set $i (i32.add (local.get $i) (i32.const 1)))
+        (br $next)))
+    (local.get $sum)))"#;
+    // More than the single page the module starts with.
+    let input = (0..100_000).map(|i| (i % 251) as u8).collect::<Vec<_>>();
+    let checksum = input.iter().map(|&byte| u32::from(byte)).sum::<u32>();
+    let input_path = temp_dir.path().join("input.bin");
+    fs::write(&input_path, &input)?;
+    let input_arg = input_path.to_str().unwrap();
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &[
+            "--input",
+            input_arg,
+            "--input-addr",
+            "16",
+            "--input-len-addr",
+            "8",
+            "--invoke",
+            "checksum",
+        ],
+    )?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout).trim(),
+        checksum.to_string()
+    );
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &[
+            "--input",
+            input_arg,
+            "--input-addr",
+            "4294967200",
+            "--invoke",
+            "checksum",
+        ],
+    )?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("doesn't fit in memory"));
+    Ok(())
+}
+
+#[test]
+fn run_color_never() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (func $crash unreachable)
+  (func (export "_start") (call $crash)))"#;
+
+    let output = run_wat(temp_dir.path(), wat, &["--color", "never"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("\x1b["), "{}", stderr);
+    assert!(stderr.contains("Wasm backtrace:"), "{}", stderr);
+    assert!(stderr.contains("crash"), "{}", stderr);
+
+    let output = run_wat(temp_dir.path(), wat, &["--color", "always"])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("\x1b["));
+    Ok(())
+}
+
+/// A WASI module writing its arguments to stdout, each one followed by a
+/// NUL.
+const PRINT_ARGS_WAT: &str = r#"(module
+  (import "wasi_snapshot_preview1" "args_sizes_get" (func $args_sizes_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "args_get" (func $args_get (param i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (func (export "_start")
+    (drop (call $args_sizes_get (i32.const 0) (i32.const 4)))
+    (drop (call $args_get (i32.const 256) (i32.const 1024)))
+    (i32.store (i32.const 8) (i32.const 1024))
+    (i32.store (i32.const 12) (i32.load (i32.const 4)))
+    (drop (call $fd_write (i32.const 1) (i32.const 8) (i32.const 1) (i32.const 16)))))"#;
+
+#[test]
+fn run_argv0_from_basename() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let output = run_wat(
+        temp_dir.path(),
+        PRINT_ARGS_WAT,
+        &["--argv0-from-basename", "--", "first"],
+    )?;
+    check_success(&output)?;
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "module\0first\0");
+    Ok(())
+}
+
+#[test]
+fn run_argv_from_file() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::write(temp_dir.path().join("args.txt"), "second\nthird arg\r\n")?;
+    let output = run_wat(
+        temp_dir.path(),
+        PRINT_ARGS_WAT,
+        &["--argv-from-file", "args.txt", "--", "first"],
+    )?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "module.wat\0first\0second\0third arg\0"
+    );
+
+    let output = run_wat(
+        temp_dir.path(),
+        PRINT_ARGS_WAT,
+        &["--argv-from-file", "missing.txt"],
+    )?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("failed to read the arguments file"));
+    Ok(())
+}
+
+#[test]
+fn run_fs_readonly() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Open `file` for writing in the directory pre-opened at fd 4, creating
+    // it if needed, and write to it.
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "path_open" (func $path_open (param i32 i32 i32 i32 i32 i64 i64 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "fd_write" (func $fd_write (param i32 i32 i32 i32) (result i32)))
+  (import "wasi_snapshot_preview1" "proc_exit" (func $proc_exit (param i32)))
+  (memory (export "memory") 1)
+  (data (i32.const 32) "file")
+  (data (i32.const 64) "written")
+  (func (export "_start")
+    (local $errno i32)
+    (local.set $errno
+      (call $path_open (i32.const 4) (i32.const 0) (i32.const 32) (i32.const 4)
+        (i32.const 1) (i64.const 64) (i64.const 0) (i32.const 0) (i32.const 0)))
+    (if (local.get $errno) (then (call $proc_exit (local.get $errno))))
+    (i32.store (i32.const 8) (i32.const 64))
+    (i32.store (i32.const 12) (i32.const 7))
+    (drop (call $fd_write (i32.load (i32.const 0)) (i32.const 8) (i32.const 1) (i32.const 16)))))"#;
+    fs::create_dir(temp_dir.path().join("empty"))?;
+    fs::create_dir(temp_dir.path().join("existing"))?;
+    fs::write(temp_dir.path().join("existing/file"), "original")?;
+
+    for mapdir in &["out:./empty", "out:./empty:read,write,create"] {
+        let output = run_wat(temp_dir.path(), wat, &["--mapdir", mapdir, "--fs-readonly"])?;
+        assert!(!output.status.success());
+        assert!(!temp_dir.path().join("empty/file").exists());
+    }
+
+    let output = run_wat(
+        temp_dir.path(),
+        wat,
+        &["--mapdir", "out:./existing", "--fs-readonly"],
+    )?;
+    assert!(!output.status.success());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("existing/file"))?,
+        "original"
+    );
+
+    let output = run_wat(temp_dir.path(), wat, &["--mapdir", "out:./empty"])?;
+    check_success(&output)?;
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("empty/file"))?,
+        "written"
+    );
+    Ok(())
+}
+
+#[test]
+fn run_host_function_timeout() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    // Sleep for the number of nanoseconds in the `ns` global with
+    // `poll_oneoff` on the realtime clock.
+    let sleep_wat = |ns: u64| {
+        format!(
+            r#"(module
+  (import "wasi_snapshot_preview1" "poll_oneoff" (func $poll_oneoff (param i32 i32 i32 i32) (result i32)))
+  (memory (export "memory") 1)
+  (global $ns i64 (i64.const {}))
+  (func (export "_start")
+    (i64.store (i32.const 24) (global.get $ns))
+    (drop (call $poll_oneoff (i32.const 0) (i32.const 64) (i32.const 1) (i32.const 128)))))"#,
+            ns
+        )
+    };
+
+    // The run still ends on the module's thread, which dumps its memory.
+    let dump_path = temp_dir.path().join("memory.bin");
+    let start = std::time::Instant::now();
+    let output = run_wat(
+        temp_dir.path(),
+        &sleep_wat(10_000_000_000),
+        &[
+            "--host-function-timeout",
+            "200ms",
+            "--memory-dump",
+            dump_path.to_str().unwrap(),
+        ],
+    )?;
+    assert!(start.elapsed() < std::time::Duration::from_secs(5));
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`poll_oneoff`"), "{}", stderr);
+    assert!(stderr.contains("--host-function-timeout"), "{}", stderr);
+    assert_eq!(fs::read(&dump_path)?.len(), 65536);
+
+    let output = run_wat(
+        temp_dir.path(),
+        &sleep_wat(10_000_000),
+        &["--host-function-timeout", "5s"],
+    )?;
+    check_success(&output)?;
+    Ok(())
+}
+
+#[test]
+fn run_log_level() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let check_logs = |output: &Output| -> anyhow::Result<()> {
+        check_success(output)?;
+        assert_eq!(output.stdout, b"module.wat\0");
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains("Compiling `"), "{}", stderr);
+        assert!(stderr.contains("Compiled `"), "{}", stderr);
+        assert!(
+            stderr.contains("Instantiated the module with WASI `wasi_snapshot_preview1`"),
+            "{}",
+            stderr
+        );
+        assert!(stderr.contains("`_start` returned after"), "{}", stderr);
+        Ok(())
+    };
+
+    let output = run_wat(temp_dir.path(), PRINT_ARGS_WAT, &["--log-level", "info"])?;
+    check_logs(&output)?;
+
+    let wasm_path = temp_dir.path().join("module.wat");
+    let output = Command::new(get_wasmer_path())
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg(&wasm_path)
+        .env("RUST_LOG", "info")
+        .output()?;
+    check_logs(&output)?;
+
+    let output = Command::new(get_wasmer_path())
+        .current_dir(temp_dir.path())
+        .arg("run")
+        .arg(&wasm_path)
+        .env_remove("RUST_LOG")
+        .output()?;
+    check_success(&output)?;
+    assert!(output.stderr.is_empty());
+    Ok(())
+}
+
+#[test]
+fn run_env_passthrough_prefix() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wasm_path = temp_dir.path().join("module.wat");
+    fs::write(&wasm_path, PRINT_ENV_WAT)?;
+    let run = |args: &[&str]| {
+        Command::new(get_wasmer_path())
+            .arg("run")
+            .arg(&wasm_path)
+            .args(args)
+            .env_clear()
+            .env("MYAPP_NAME", "host")
+            .env("MYAPP_DEBUG", "1")
+            .env("MYAPP_", "bare")
+            .env("OTHER_DEBUG", "0")
+            .output()
+    };
+
+    let output = run(&[
+        "--env-passthrough-prefix",
+        "MYAPP_",
+        "--env",
+        "MYAPP_NAME=given",
+    ])?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "MYAPP_=bare\0MYAPP_DEBUG=1\0MYAPP_NAME=given\0"
+    );
+
+    let output = run(&[
+        "--env-passthrough-prefix",
+        "MYAPP_",
+        "--env-strip-prefix",
+        "--env",
+        "NAME=given",
+    ])?;
+    check_success(&output)?;
+    assert_eq!(
+        String::from_utf8_lossy(&output.stdout),
+        "DEBUG=1\0NAME=given\0"
+    );
+    Ok(())
+}
+
+#[test]
+fn run_preopen_stdio_as_files() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    fs::create_dir_all(temp_dir.path().join("out/dir"))?;
+    let run = |stdio: &[&str]| {
+        let mut args = vec!["--mapdir", "out:./out"];
+        for file in stdio {
+            args.extend(&["--preopen-stdio-as-files", *file]);
+        }
+        args.extend(&["--", "first"]);
+        run_wat(temp_dir.path(), PRINT_ARGS_WAT, &args)
+    };
+
+    let output = run(&["stdout=out/args.txt"])?;
+    check_success(&output)?;
+    assert!(output.stdout.is_empty());
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out/args.txt"))?,
+        "module.wat\0first\0"
+    );
+
+    // An existing file is truncated.
+    fs::write(temp_dir.path().join("out/args.txt"), "stale output, longer")?;
+    let output = run(&["stdout=/out/args.txt"])?;
+    check_success(&output)?;
+    assert_eq!(
+        fs::read_to_string(temp_dir.path().join("out/args.txt"))?,
+        "module.wat\0first\0"
+    );
+
+    let output = run(&["stdout=out/dir"])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("it is a directory"));
+
+    let output = run(&["stdout=elsewhere/args.txt"])?;
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("isn't in a pre-opened directory"));
+
+    // Nothing is created when any of the streams can't be bound.
+    let output = run(&["stdout=out/new.txt", "stderr=out/dir"])?;
+    assert!(!output.status.success());
+    assert!(!temp_dir.path().join("out/new.txt").exists());
+    Ok(())
+}
+
+#[test]
+fn run_log_level_debug_lists_every_non_wasi_import() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wat = r#"(module
+  (import "wasi_snapshot_preview1" "proc_exit" (func (param i32)))
+  (import "env" "f1" (func))
+  (import "env" "f2" (func))
+  (import "env" "f3" (func))
+  (import "env" "f4" (func))
+  (import "env" "f5" (func))
+  (import "env" "f6" (func))
+  (func (export "_start")))"#;
+
+    // Not left to `RUST_LOG`, which the tests may run with.
+    let output = run_wat(temp_dir.path(), wat, &["--log-level", "off"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!stderr.contains("`env`.`f6`"), "{}", stderr);
+    assert!(
+        stderr.contains("... and 1 more (pass `--log-level debug` to list them all)"),
+        "{}",
+        stderr
+    );
+
+    let output = run_wat(temp_dir.path(), wat, &["--log-level", "debug"])?;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("`env`.`f6`"), "{}", stderr);
+    assert!(!stderr.contains("more (pass"), "{}", stderr);
+    Ok(())
+}
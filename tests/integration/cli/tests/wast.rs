@@ -0,0 +1,33 @@
+//! CLI tests for the wast subcommand.
+
+use std::fs;
+use std::process::Command;
+use wasmer_integration_tests_cli::get_wasmer_path;
+
+#[test]
+fn wast_names_the_exports_of_a_missing_function() -> anyhow::Result<()> {
+    let temp_dir = tempfile::tempdir()?;
+    let wast_path = temp_dir.path().join("test.wast");
+    fs::write(
+        &wast_path,
+        r#"(module
+  (func (export "renamed") (result i32) (i32.const 1))
+  (func (export "other")))
+(assert_return (invoke "answer") (i32.const 1))"#,
+    )?;
+
+    let output = Command::new(get_wasmer_path())
+        .arg("wast")
+        .arg(&wast_path)
+        .output()?;
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(
+        stderr.contains(
+            "no function named `answer` is exported; the instance exports `renamed`, `other`"
+        ),
+        "{}",
+        stderr
+    );
+    Ok(())
+}
@@ -8,7 +8,7 @@
 mod processors;
 
 pub use crate::processors::{emscripten_processor, wasi_processor, wast_processor};
-use anyhow::Context;
+use anyhow::{bail, Context};
 use std::collections::HashSet;
 use std::fmt::Write;
 use std::fs::File;
@@ -21,6 +21,8 @@ pub struct Testsuite {
     pub buffer: String,
     pub path: Vec<String>,
     pub ignores: Ignores,
+    /// The full path of every test written so far.
+    pub generated: Vec<String>,
 }
 
 impl Testsuite {
@@ -113,9 +115,56 @@ fn write_test(out: &mut Testsuite, testname: &str, body: &str) -> anyhow::Result
     writeln!(out.buffer, "{}", body)?;
     writeln!(out.buffer, "}}")?;
     writeln!(out.buffer)?;
+    out.generated.push(out.path.join("::"));
     Ok(())
 }
 
+/// Writes a `GENERATED_MODULES` list of every test generated so far, and
+/// a `spectest_inventory` test checking it has one test per compiler for
+/// each `.wast` file in `dirs`, counted from the directories themselves,
+/// so that files dropping out of the build show up.
+pub fn write_inventory(out: &mut Testsuite, dirs: &[&str], compilers: usize) -> anyhow::Result<()> {
+    let expected = count_wast_files(dirs)? * compilers;
+    writeln!(out.buffer, "/// Every generated test, for each compiler.")?;
+    writeln!(out.buffer, "pub const GENERATED_MODULES: &[&str] = &[")?;
+    for test in out.generated.iter() {
+        writeln!(out.buffer, "    {:?},", test)?;
+    }
+    writeln!(out.buffer, "];")?;
+    writeln!(out.buffer)?;
+    writeln!(out.buffer, "#[test]")?;
+    writeln!(out.buffer, "fn spectest_inventory() {{")?;
+    writeln!(
+        out.buffer,
+        "    assert_eq!(GENERATED_MODULES.len(), {});",
+        expected
+    )?;
+    writeln!(out.buffer, "}}")?;
+    Ok(())
+}
+
+/// Counts the `.wast` files directly in each of `dirs`, leaving out hidden
+/// ones such as editor temporary files.
+fn count_wast_files(dirs: &[&str]) -> anyhow::Result<usize> {
+    let mut count = 0;
+    for dir in dirs {
+        for entry in Path::new(dir)
+            .read_dir()
+            .context(format!("failed to read {:?}", dir))?
+        {
+            let path = entry?.path();
+            let is_hidden = path
+                .file_name()
+                .and_then(|name| name.to_str())
+                .map_or(false, |name| name.starts_with('.'));
+            if path.extension().map_or(false, |ext| ext == "wast") && !is_hidden {
+                count += 1;
+            }
+        }
+    }
+    Ok(count)
+}
+
 pub fn test_directory(
     out: &mut Testsuite,
     path: impl AsRef<Path>,
@@ -126,18 +175,31 @@ pub fn test_directory(
         .read_dir()
         .context(format!("failed to read {:?}", path))?
         .map(|r| r.expect("reading testsuite directory entry"))
-        .filter_map(|dir_entry| processor(out, dir_entry.path()))
+        .filter_map(|dir_entry| {
+            let path = dir_entry.path();
+            processor(out, path.clone()).map(|test| (test, path))
+        })
         .collect();
 
     dir_entries.sort();
 
-    for Test {
-        name: testname,
-        body,
-    } in dir_entries.iter()
-    {
-        out.path.push(testname.to_string());
-        write_test(out, &testname, &body).unwrap();
+    // Files whose names only differ by characters which aren't valid in
+    // identifiers, e.g. `a-b.wast` and `a_b.wast`, generate the same test
+    // function, which rustc would only report as defined multiple times.
+    for pair in dir_entries.windows(2) {
+        if pair[0].0.name == pair[1].0.name {
+            bail!(
+                "{:?} and {:?} both generate the test `{}`",
+                pair[0].1,
+                pair[1].1,
+                pair[0].0.name
+            );
+        }
+    }
+
+    for (test, _) in dir_entries.iter() {
+        out.path.push(test.name.to_string());
+        write_test(out, &test.name, &test.body).unwrap();
         out.path.pop().unwrap();
     }
 
@@ -172,6 +234,27 @@ pub fn with_test_module<T>(
     Ok(result)
 }
 
+/// Returns the comment to put at the top of a generated file whose tests
+/// are split by [`with_features`], showing how to select the compilers.
+pub fn generated_header(features: &[&str]) -> String {
+    let mut header = String::from(
+        "// This file is generated by `build.rs`, do not edit it by hand.\n\
+         //\n\
+         // The tests for each compiler live in their own module, guarded by the\n\
+         // `test-<compiler>` feature, so this one file serves the whole test\n\
+         // matrix.  Pick a compiler and an engine with, e.g.:\n\
+         //\n",
+    );
+    for compiler in features.iter() {
+        header.push_str(&format!(
+            "//     cargo test --features \"test-{} test-jit\"\n",
+            compiler
+        ));
+    }
+    header.push('\n');
+    header
+}
+
 pub fn with_features(
     mut out: &mut Testsuite,
     features: &[&str],
@@ -185,3 +268,79 @@ pub fn with_features(
     }
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{test_directory, wast_processor, write_test, Ignores, Testsuite};
+    use std::fs;
+
+    #[test]
+    fn write_test_returns_the_body_result() {
+        let mut out = Testsuite {
+            buffer: String::new(),
+            path: vec!["cranelift".to_string()],
+            ignores: Ignores::new(),
+            generated: vec![],
+        };
+
+        write_test(&mut out, "fails", "Err(anyhow::anyhow!(\"failed\"))").unwrap();
+
+        // The body is the tail expression of a test returning a `Result`, so
+        // an error fails the test rather than being dropped.
+        assert_eq!(
+            out.buffer,
+            "#[test]\nfn r#fails() -> anyhow::Result<()> {\nErr(anyhow::anyhow!(\"failed\"))\n}\n\n"
+        );
+    }
+
+    #[test]
+    fn test_directory_rejects_colliding_names() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        fs::write(dir.join("a-b.wast"), "").unwrap();
+        fs::write(dir.join("a_b.wast"), "").unwrap();
+        let mut out = Testsuite {
+            buffer: String::new(),
+            path: vec!["cranelift".to_string()],
+            ignores: Ignores::new(),
+            generated: vec![],
+        };
+
+        let result = test_directory(&mut out, dir, wast_processor);
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.contains("a-b.wast"), "{}", message);
+        assert!(message.contains("a_b.wast"), "{}", message);
+        assert!(message.contains("`a_b`"), "{}", message);
+    }
+
+    #[test]
+    fn test_directory_writes_tests_in_name_order() {
+        let temp_dir = tempfile::tempdir().unwrap();
+        let dir = temp_dir.path();
+        for name in &["c.wast", "a.wast", "b.wast"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+        let mut out = Testsuite {
+            buffer: String::new(),
+            path: vec!["cranelift".to_string()],
+            ignores: Ignores::new(),
+            generated: vec![],
+        };
+
+        let result = test_directory(&mut out, dir, wast_processor);
+
+        // The order doesn't depend on the order the directory is read in, so
+        // regenerating the file gives the same output.
+        assert_eq!(result.unwrap(), 3);
+        assert_eq!(
+            out.generated,
+            vec!["cranelift::a", "cranelift::b", "cranelift::c"]
+        );
+        let positions: Vec<_> = ["fn r#a(", "fn r#b(", "fn r#c("]
+            .iter()
+            .map(|test| out.buffer.find(test).unwrap())
+            .collect();
+        assert!(positions.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+}
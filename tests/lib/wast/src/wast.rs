@@ -115,34 +115,50 @@ impl Wast {
 
     fn assert_return(
         &self,
+        field: &str,
         result: Result<Vec<Val>>,
         results: &[wast::AssertExpression],
     ) -> Result<()> {
         let values = result?;
-        for (v, e) in values.iter().zip(results) {
-            if self.val_matches(v, e)? {
-                continue;
-            }
-            if let Val::V128(bits) = v {
-                if let wast::AssertExpression::V128(pattern) = e {
-                    bail!(
-                        "expected {:?}, got {:?} (v128 bits: {})",
-                        e,
-                        v128_format(*bits, pattern),
-                        bits
-                    );
-                }
+        check_result_count(field, &values, results)?;
+        // Each result is compared on its own, so that e.g. a NaN pattern
+        // and an exact value can be mixed in a multi-value return.
+        for (i, (v, e)) in values.iter().zip(results).enumerate() {
+            if !self.val_matches(v, e)? {
+                bail!("{}", describe_mismatch(field, i, v, e));
             }
-            bail!("expected {:?}, got {:?}", e, v)
         }
         Ok(())
     }
 
     fn assert_trap(&self, result: Result<Vec<Val>>, expected: &str) -> Result<()> {
-        let actual = match result {
+        let (actual, actual_category) = match result {
             Ok(values) => bail!("expected trap, got {:?}", values),
-            Err(t) => format!("{}", t),
+            Err(t) => (format!("{}", t), classify_trap(&t)),
         };
+        let expected_category = TrapCategory::from_spec_message(expected);
+        match (expected_category, actual_category) {
+            (Some(expected_category), Some(actual_category))
+                if expected_category == actual_category =>
+            {
+                return Ok(())
+            }
+            // A trap of another category fails even if its message happens
+            // to contain the expected one, unless an alternative message is
+            // allowed for it.
+            (Some(expected_category), Some(actual_category))
+                if !self.match_trap_messages.contains_key(expected) =>
+            {
+                bail!(
+                    "expected '{}' ({:?}), got '{}' ({:?})",
+                    expected,
+                    expected_category,
+                    actual,
+                    actual_category
+                )
+            }
+            _ => {}
+        }
         if self.matches_message_assert_trap(expected, &actual) {
             return Ok(());
         }
@@ -172,8 +188,20 @@ impl Wast {
                 exec,
                 results,
             } => {
+                if let wast::WastExecute::Invoke(invoke) = &exec {
+                    self.check_result_types(
+                        invoke.module.as_ref().map(|i| i.name()),
+                        invoke.name,
+                        &results,
+                    )?;
+                }
+                let field = match &exec {
+                    wast::WastExecute::Invoke(invoke) => invoke.name,
+                    wast::WastExecute::Get { global, .. } => global,
+                    wast::WastExecute::Module(_) => "module",
+                };
                 let result = self.perform_execute(exec);
-                self.assert_return(result, &results)?;
+                self.assert_return(field, result, &results)?;
             }
             AssertTrap {
                 span: _,
@@ -214,8 +242,9 @@ impl Wast {
                     )
                 }
             }
-            QuoteModule { .. } => {
-                // Do nothing
+            QuoteModule { span: _, source } => {
+                let (name, binary) = Self::encode_quote_module(&source)?;
+                self.module(name.as_deref(), &binary)?;
             }
             AssertMalformed {
                 module,
@@ -257,7 +286,29 @@ impl Wast {
         Ok(())
     }
 
+    /// Parses the text of a `(module quote ...)`, which holds either a
+    /// whole module or only its fields, returning the module name and its
+    /// binary encoding.
+    fn encode_quote_module(source: &[&[u8]]) -> Result<(Option<String>, Vec<u8>)> {
+        let mut text = String::new();
+        for part in source {
+            text.push_str(str::from_utf8(part)?);
+            text.push(' ');
+        }
+        if !text.trim_start().starts_with("(module") {
+            text = format!("(module {})", text);
+        }
+        let buf = wast::parser::ParseBuffer::new(&text)?;
+        let mut module = wast::parser::parse::<wast::Module>(&buf)?;
+        let name = module.id.map(|id| id.name().to_string());
+        Ok((name, module.encode()?))
+    }
+
     /// Run a wast script from a byte buffer.
+    ///
+    /// The directives run one at a time in the order of the script, against
+    /// the same instances, so the effects of an action (e.g. a `memory.grow`)
+    /// are seen by all the directives after it and none before it.
     pub fn run_buffer(&mut self, filename: &str, wast: &[u8]) -> Result<()> {
         let wast = str::from_utf8(wast)?;
 
@@ -272,7 +323,12 @@ impl Wast {
         let mut errors = Vec::with_capacity(ast.directives.len());
         for directive in ast.directives {
             let sp = directive.span();
-            if let Err(e) = self.run_directive(directive) {
+            #[cfg(feature = "catch-panics")]
+            let result =
+                with_directive_position(filename, wast, sp, || self.run_directive(directive));
+            #[cfg(not(feature = "catch-panics"))]
+            let result = self.run_directive(directive);
+            if let Err(e) = result {
                 let message = format!("{}", e);
                 // If depends on an instance that doesn't exist
                 if message.contains("no previous instance found") {
@@ -375,13 +431,65 @@ impl Wast {
         args: &[Val],
     ) -> Result<Vec<Val>> {
         let instance = self.get_instance(instance_name.as_deref())?;
-        let func: &Function = instance.exports.get(field)?;
+        let func = get_function(&instance, field)?;
+        let ty = func.ty();
+        if ty.params().len() != args.len() {
+            bail!(
+                "export `{}` takes {} argument(s) but is invoked with {}: {:?}",
+                field,
+                ty.params().len(),
+                args.len(),
+                args
+            );
+        }
+        let arg_types = args.iter().map(|arg| arg.ty()).collect::<Vec<_>>();
+        if ty.params() != arg_types.as_slice() {
+            bail!(
+                "export `{}` has signature {} but is invoked with arguments of types {:?}",
+                field,
+                ty,
+                arg_types
+            );
+        }
         match func.call(args) {
             Ok(result) => Ok(result.into()),
             Err(e) => Err(e.into()),
         }
     }
 
+    /// Check that an exported function returns values of the types
+    /// expected by an `assert_return`.
+    fn check_result_types(
+        &self,
+        instance_name: Option<&str>,
+        field: &str,
+        expected: &[wast::AssertExpression],
+    ) -> Result<()> {
+        let instance = self.get_instance(instance_name)?;
+        let func = get_function(&instance, field)?;
+        let ty = func.ty();
+        if ty.results().len() != expected.len() {
+            bail!(
+                "export `{}` has signature {} but {} results are expected",
+                field,
+                ty,
+                expected.len()
+            );
+        }
+        for (result, expected) in ty.results().iter().zip(expected) {
+            match assert_expression_type(expected) {
+                Some(expected_ty) if expected_ty != *result => bail!(
+                    "export `{}` has signature {} but a result of type {:?} is expected",
+                    field,
+                    ty,
+                    expected_ty
+                ),
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
     /// Get the value of an exported global from an instance.
     fn get(&mut self, instance_name: Option<&str>, field: &str) -> Result<Vec<Val>> {
         let instance = self.get_instance(instance_name.as_deref())?;
@@ -436,6 +544,10 @@ impl Wast {
             // handle `unknown memory $NUM` error messages that wasmparser doesn't return yet
             || (expected.contains("unknown memory") && actual.contains("unknown memory"))
             || (expected.contains("unknown memory") && actual.contains("Data segment extends past end of the data section"))
+            // wasmparser words some unknown indices as bounds checks
+            || (expected.contains("unknown local") && actual.contains("local index out of bounds"))
+            || (expected.contains("unknown function") && actual.contains("function index out of bounds"))
+            || (expected.contains("unknown label") && actual.contains("branch depth too large"))
     }
 
     // Checks if the `assert_trap` message matches the expected one
@@ -449,6 +561,15 @@ impl Wast {
     }
 
     fn val_matches(&self, actual: &Val, expected: &wast::AssertExpression) -> Result<bool> {
+        if let Some(expected_ty) = assert_expression_type(expected) {
+            if actual.ty() != expected_ty {
+                bail!(
+                    "expected a value of type {:?} but got {:?}",
+                    expected_ty,
+                    actual
+                );
+            }
+        }
         Ok(match (actual, expected) {
             (Val::I32(a), wast::AssertExpression::I32(b)) => a == b,
             (Val::I64(a), wast::AssertExpression::I64(b)) => a == b,
@@ -463,6 +584,9 @@ impl Wast {
             (Val::FuncRef(Some(_)), wast::AssertExpression::RefNull(_)) => false,
             (Val::FuncRef(None), wast::AssertExpression::RefFunc(None)) => true,
             (Val::FuncRef(None), wast::AssertExpression::RefFunc(Some(_))) => false,
+            // Function references can't be compared by identity, so only
+            // check that a non-null reference is returned.
+            (Val::FuncRef(Some(_)), wast::AssertExpression::RefFunc(_)) => true,
             (
                 Val::ExternRef(extern_ref),
                 wast::AssertExpression::RefNull(Some(wast::HeapType::Extern)),
@@ -490,6 +614,113 @@ impl Wast {
     }
 }
 
+/// Checks that an action returned as many values as expected, including
+/// none at all: an `assert_return` without results checks that the action
+/// returns nothing, it doesn't skip the check.
+fn check_result_count(
+    field: &str,
+    values: &[Val],
+    results: &[wast::AssertExpression],
+) -> Result<()> {
+    if values.len() != results.len() {
+        bail!(
+            "`{}`: expected {} results {:?}, got {} results {:?}",
+            field,
+            results.len(),
+            results,
+            values.len(),
+            values
+        );
+    }
+    Ok(())
+}
+
+/// Describes a result of `field` which doesn't match the expected one,
+/// giving the bits of integers and floats and the lanes of vectors on both
+/// sides.
+fn describe_mismatch(
+    field: &str,
+    index: usize,
+    actual: &Val,
+    expected: &wast::AssertExpression,
+) -> String {
+    let actual = match (actual, expected) {
+        (Val::I32(value), _) => format!("i32 {} (bits {:#010x})", value, value),
+        (Val::I64(value), _) => format!("i64 {} (bits {:#018x})", value, value),
+        (Val::F32(value), _) => format!("f32 {} (bits {:#010x})", value, value.to_bits()),
+        (Val::F64(value), _) => format!("f64 {} (bits {:#018x})", value, value.to_bits()),
+        (Val::V128(bits), wast::AssertExpression::V128(pattern)) => {
+            format!("{:?} (bits {:#034x})", v128_format(*bits, pattern), bits)
+        }
+        (Val::V128(bits), _) => format!("v128 {:#034x}", bits),
+        (value, _) => format!("{:?}", value),
+    };
+    let expected = match expected {
+        wast::AssertExpression::I32(value) => format!("i32 {} (bits {:#010x})", value, value),
+        wast::AssertExpression::I64(value) => format!("i64 {} (bits {:#018x})", value, value),
+        wast::AssertExpression::F32(wast::NanPattern::Value(value)) => format!(
+            "f32 {} (bits {:#010x})",
+            f32::from_bits(value.bits),
+            value.bits
+        ),
+        wast::AssertExpression::F64(wast::NanPattern::Value(value)) => format!(
+            "f64 {} (bits {:#018x})",
+            f64::from_bits(value.bits),
+            value.bits
+        ),
+        wast::AssertExpression::F32(wast::NanPattern::CanonicalNan) => "f32 nan:canonical".into(),
+        wast::AssertExpression::F32(wast::NanPattern::ArithmeticNan) => "f32 nan:arithmetic".into(),
+        wast::AssertExpression::F64(wast::NanPattern::CanonicalNan) => "f64 nan:canonical".into(),
+        wast::AssertExpression::F64(wast::NanPattern::ArithmeticNan) => "f64 nan:arithmetic".into(),
+        expected => format!("{:?}", expected),
+    };
+    format!(
+        "`{}` result {}: expected {}, got {}",
+        field, index, expected, actual
+    )
+}
+
+/// Gets an exported function, failing with the list of the functions the
+/// instance does export if there is none named `field`.
+fn get_function<'a>(instance: &'a Instance, field: &str) -> Result<&'a Function> {
+    if let Ok(func) = instance.exports.get_function(field) {
+        return Ok(func);
+    }
+    let exported = instance
+        .exports
+        .iter()
+        .functions()
+        .map(|(name, _)| format!("`{}`", name))
+        .collect::<Vec<_>>();
+    if exported.is_empty() {
+        bail!(
+            "no function named `{}` is exported; the instance exports no functions",
+            field
+        )
+    }
+    bail!(
+        "no function named `{}` is exported; the instance exports {}",
+        field,
+        exported.join(", ")
+    )
+}
+
+/// The type of the value an `AssertExpression` matches, if it is known.
+fn assert_expression_type(expected: &wast::AssertExpression) -> Option<ValType> {
+    match expected {
+        wast::AssertExpression::I32(_) => Some(ValType::I32),
+        wast::AssertExpression::I64(_) => Some(ValType::I64),
+        wast::AssertExpression::F32(_) => Some(ValType::F32),
+        wast::AssertExpression::F64(_) => Some(ValType::F64),
+        wast::AssertExpression::V128(_) => Some(ValType::V128),
+        wast::AssertExpression::RefNull(Some(wast::HeapType::Func))
+        | wast::AssertExpression::RefFunc(_) => Some(ValType::FuncRef),
+        wast::AssertExpression::RefNull(Some(wast::HeapType::Extern))
+        | wast::AssertExpression::RefExtern(_) => Some(ValType::ExternRef),
+        _ => None,
+    }
+}
+
 fn extract_lane_as_i8(bytes: u128, lane: usize) -> i8 {
     (bytes >> (lane * 8)) as i8
 }
@@ -506,6 +737,125 @@ fn extract_lane_as_i64(bytes: u128, lane: usize) -> i64 {
     (bytes >> (lane * 64)) as i64
 }
 
+/// Runs `run`, the directive at `span` in `wast`, panicking again with the
+/// directive's position if it panics, e.g. in a host function.  Otherwise
+/// the test only reports that the whole script failed.
+#[cfg(feature = "catch-panics")]
+fn with_directive_position<T>(
+    filename: &str,
+    wast: &str,
+    span: wast::Span,
+    run: impl FnOnce() -> T,
+) -> T {
+    use std::panic::{catch_unwind, AssertUnwindSafe};
+    match catch_unwind(AssertUnwindSafe(run)) {
+        Ok(result) => result,
+        Err(payload) => {
+            let message = if let Some(message) = payload.downcast_ref::<&str>() {
+                message.to_string()
+            } else if let Some(message) = payload.downcast_ref::<String>() {
+                message.clone()
+            } else {
+                "Box<Any>".to_string()
+            };
+            let (line, col) = span.linecol_in(wast);
+            panic!(
+                "directive at {}:{}:{} panicked: {}",
+                filename,
+                line + 1,
+                col,
+                message
+            )
+        }
+    }
+}
+
+/// The kinds of traps which spec tests tell apart by their message.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TrapCategory {
+    IntegerOverflow,
+    IntegerDivideByZero,
+    InvalidConversionToInteger,
+    OutOfBoundsMemoryAccess,
+    UndefinedElement,
+    UninitializedElement,
+    IndirectCallTypeMismatch,
+    Unreachable,
+    CallStackExhausted,
+}
+
+impl TrapCategory {
+    /// Maps the message expected by a spec test to the category of trap.
+    fn from_spec_message(message: &str) -> Option<Self> {
+        Some(if message.starts_with("integer overflow") {
+            Self::IntegerOverflow
+        } else if message.starts_with("integer divide by zero") {
+            Self::IntegerDivideByZero
+        } else if message.starts_with("invalid conversion to integer") {
+            Self::InvalidConversionToInteger
+        } else if message.starts_with("out of bounds memory access") {
+            Self::OutOfBoundsMemoryAccess
+        } else if message.starts_with("undefined element") {
+            Self::UndefinedElement
+        } else if message.starts_with("out of bounds table access") {
+            // The runtime reports an access past the end of a table or of a
+            // passive element segment, e.g. a dropped one, the same way
+            // whether it comes from `call_indirect` or `table.init`.
+            Self::UndefinedElement
+        } else if message.starts_with("uninitialized element") {
+            Self::UninitializedElement
+        } else if message.starts_with("indirect call type mismatch") {
+            Self::IndirectCallTypeMismatch
+        } else if message.starts_with("unreachable") {
+            Self::Unreachable
+        } else if message.starts_with("call stack exhausted") {
+            Self::CallStackExhausted
+        } else {
+            return None;
+        })
+    }
+
+    /// Maps the trap code reported by the runtime to the category of trap.
+    fn from_trap_code(code: TrapCode) -> Option<Self> {
+        Some(match code {
+            TrapCode::IntegerOverflow => Self::IntegerOverflow,
+            TrapCode::IntegerDivisionByZero => Self::IntegerDivideByZero,
+            TrapCode::BadConversionToInteger => Self::InvalidConversionToInteger,
+            TrapCode::HeapAccessOutOfBounds => Self::OutOfBoundsMemoryAccess,
+            TrapCode::TableAccessOutOfBounds => Self::UndefinedElement,
+            TrapCode::IndirectCallToNull => Self::UninitializedElement,
+            TrapCode::BadSignature => Self::IndirectCallTypeMismatch,
+            TrapCode::UnreachableCodeReached => Self::Unreachable,
+            TrapCode::StackOverflow => Self::CallStackExhausted,
+            _ => return None,
+        })
+    }
+}
+
+/// Classifies the trap an error comes from, whether it is a bare
+/// `RuntimeError`, the start function's error from instantiating a module,
+/// or either of them under added context. Returns `None` for errors which
+/// aren't traps.
+fn classify_trap(error: &anyhow::Error) -> Option<TrapCategory> {
+    error.chain().find_map(|cause| {
+        let error = match cause.downcast_ref::<InstantiationError>() {
+            Some(InstantiationError::Start(error)) => error,
+            _ => cause.downcast_ref::<RuntimeError>()?,
+        };
+        error
+            .clone()
+            .to_trap()
+            .and_then(TrapCategory::from_trap_code)
+    })
+}
+
+/// Checks a float result against the expected one.
+///
+/// The spec mandates bit-exact results, even for the operations a libm
+/// would only get within some ULPs, such as `sqrt`, so values are compared
+/// by bits, with no slack. This also tells `-0.0` from `0.0` and compares
+/// subnormals exactly, which `==` would not. Only NaNs are matched by
+/// pattern, as the spec allows any payload of the expected kind.
 fn f32_matches(actual: f32, expected: &wast::NanPattern<wast::Float32>) -> bool {
     match expected {
         wast::NanPattern::CanonicalNan => actual.is_canonical_nan(),
@@ -514,6 +864,7 @@ fn f32_matches(actual: f32, expected: &wast::NanPattern<wast::Float32>) -> bool
     }
 }
 
+/// Like [`f32_matches`], for `f64`.
 fn f64_matches(actual: f64, expected: &wast::NanPattern<wast::Float64>) -> bool {
     match expected {
         wast::NanPattern::CanonicalNan => actual.is_canonical_nan(),
@@ -642,3 +993,199 @@ impl NaNCheck for f64 {
         (self.to_bits() & 0x7fff_ffff_ffff_ffff) == 0x7ff8_0000_0000_0000
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use wasmer_vm::Trap;
+
+    fn runtime_trap(code: TrapCode) -> RuntimeError {
+        RuntimeError::from_trap(Trap::new_from_runtime(code))
+    }
+
+    #[test]
+    fn test_describe_mismatch() {
+        let expected = wast::AssertExpression::F32(wast::NanPattern::Value(wast::Float32 {
+            bits: 0x3fc0_0000,
+        }));
+        assert_eq!(
+            describe_mismatch("f", 0, &Val::F32(1.0), &expected),
+            "`f` result 0: expected f32 1.5 (bits 0x3fc00000), got f32 1 (bits 0x3f800000)"
+        );
+
+        let expected = wast::AssertExpression::F64(wast::NanPattern::CanonicalNan);
+        assert_eq!(
+            describe_mismatch("g", 1, &Val::F64(-0.0), &expected),
+            "`g` result 1: expected f64 nan:canonical, got f64 -0 (bits 0x8000000000000000)"
+        );
+
+        let expected = wast::AssertExpression::I32(2);
+        assert_eq!(
+            describe_mismatch("h", 0, &Val::I32(1), &expected),
+            "`h` result 0: expected i32 2 (bits 0x00000002), got i32 1 (bits 0x00000001)"
+        );
+
+        let expected = wast::AssertExpression::I64(i64::MIN);
+        assert_eq!(
+            describe_mismatch("i", 0, &Val::I64(-1), &expected),
+            "`i` result 0: expected i64 -9223372036854775808 (bits 0x8000000000000000), \
+             got i64 -1 (bits 0xffffffffffffffff)"
+        );
+    }
+
+    #[test]
+    fn test_check_result_count() {
+        assert!(check_result_count("void", &[], &[]).is_ok());
+        let error = check_result_count("f", &[Val::I32(1)], &[]).unwrap_err();
+        assert_eq!(
+            error.to_string(),
+            "`f`: expected 0 results [], got 1 results [I32(1)]"
+        );
+        let expected = [wast::AssertExpression::I32(1)];
+        assert!(check_result_count("g", &[], &expected).is_err());
+        assert!(check_result_count("g", &[Val::I32(2)], &expected).is_ok());
+    }
+
+    #[test]
+    fn test_floats_match_by_bits() {
+        let f32_value = |value: f32| {
+            wast::NanPattern::Value(wast::Float32 {
+                bits: value.to_bits(),
+            })
+        };
+        assert!(f32_matches(0.0, &f32_value(0.0)));
+        assert!(!f32_matches(-0.0, &f32_value(0.0)));
+        assert!(!f32_matches(0.0, &f32_value(-0.0)));
+        let subnormal = f32::from_bits(1);
+        assert!(f32_matches(subnormal, &f32_value(subnormal)));
+        assert!(!f32_matches(f32::from_bits(2), &f32_value(subnormal)));
+        assert!(!f32_matches(0.0, &f32_value(subnormal)));
+        assert!(!f32_matches(
+            f32::from_bits(0x7fc0_0001),
+            &wast::NanPattern::CanonicalNan
+        ));
+        assert!(f32_matches(
+            f32::from_bits(0x7fc0_0001),
+            &wast::NanPattern::ArithmeticNan
+        ));
+
+        let f64_value = |value: f64| {
+            wast::NanPattern::Value(wast::Float64 {
+                bits: value.to_bits(),
+            })
+        };
+        assert!(!f64_matches(-0.0, &f64_value(0.0)));
+        let subnormal = f64::from_bits(1);
+        assert!(f64_matches(subnormal, &f64_value(subnormal)));
+        assert!(!f64_matches(0.0, &f64_value(subnormal)));
+        // One ULP away is a mismatch.
+        assert!(!f64_matches(
+            f64::from_bits(2.0f64.sqrt().to_bits() + 1),
+            &f64_value(2.0f64.sqrt())
+        ));
+
+        // A NaN given by value, as `nan:0x...` is, only matches the same
+        // payload and sign, e.g. for checking `select` doesn't canonicalize.
+        let f32_nan = |bits: u32| wast::NanPattern::Value(wast::Float32 { bits });
+        assert!(f32_matches(
+            f32::from_bits(0x7f80_0001),
+            &f32_nan(0x7f80_0001)
+        ));
+        assert!(!f32_matches(
+            f32::from_bits(0x7fc0_0000),
+            &f32_nan(0x7f80_0001)
+        ));
+        assert!(!f32_matches(
+            f32::from_bits(0xff80_0001),
+            &f32_nan(0x7f80_0001)
+        ));
+        let f64_nan = |bits: u64| wast::NanPattern::Value(wast::Float64 { bits });
+        assert!(f64_matches(
+            f64::from_bits(0x7ff0_0000_0000_0001),
+            &f64_nan(0x7ff0_0000_0000_0001)
+        ));
+        assert!(!f64_matches(
+            f64::from_bits(0x7ff8_0000_0000_0000),
+            &f64_nan(0x7ff0_0000_0000_0001)
+        ));
+    }
+
+    #[test]
+    fn test_matches_message_assert_invalid() {
+        assert!(Wast::matches_message_assert_invalid(
+            "type mismatch",
+            "Validation error: type mismatch: expected i32 but nothing on stack (at offset 23)"
+        ));
+        assert!(Wast::matches_message_assert_invalid(
+            "unknown local",
+            "Validation error: local index out of bounds (at offset 22)"
+        ));
+        assert!(Wast::matches_message_assert_invalid(
+            "unknown function",
+            "Validation error: function index out of bounds (at offset 22)"
+        ));
+        assert!(Wast::matches_message_assert_invalid(
+            "unknown label",
+            "Validation error: branch depth too large (at offset 22)"
+        ));
+        assert!(!Wast::matches_message_assert_invalid(
+            "unknown local",
+            "Validation error: type mismatch: expected i32, found i64 (at offset 24)"
+        ));
+        assert!(!Wast::matches_message_assert_invalid(
+            "type mismatch",
+            "Validation error: local index out of bounds (at offset 22)"
+        ));
+    }
+
+    #[test]
+    fn test_classify_trap() {
+        let bare = anyhow::Error::from(runtime_trap(TrapCode::UnreachableCodeReached));
+        assert_eq!(classify_trap(&bare), Some(TrapCategory::Unreachable));
+
+        let with_context = anyhow::Error::from(runtime_trap(TrapCode::BadSignature))
+            .context("failed to invoke `f`");
+        assert_eq!(
+            classify_trap(&with_context),
+            Some(TrapCategory::IndirectCallTypeMismatch)
+        );
+
+        let start = anyhow::Error::from(InstantiationError::Start(runtime_trap(
+            TrapCode::HeapAccessOutOfBounds,
+        )));
+        assert_eq!(
+            classify_trap(&start),
+            Some(TrapCategory::OutOfBoundsMemoryAccess)
+        );
+
+        let start_with_context = start.context("failed to instantiate");
+        assert_eq!(
+            classify_trap(&start_with_context),
+            Some(TrapCategory::OutOfBoundsMemoryAccess)
+        );
+
+        let table_init = anyhow::Error::from(runtime_trap(TrapCode::TableAccessOutOfBounds));
+        assert_eq!(
+            classify_trap(&table_init),
+            TrapCategory::from_spec_message("out of bounds table access")
+        );
+
+        for (code, message) in &[
+            (TrapCode::IntegerOverflow, "integer overflow"),
+            (
+                TrapCode::BadConversionToInteger,
+                "invalid conversion to integer",
+            ),
+        ] {
+            let conversion = anyhow::Error::from(runtime_trap(*code));
+            assert_eq!(
+                classify_trap(&conversion),
+                TrapCategory::from_spec_message(message)
+            );
+        }
+
+        let user = anyhow::Error::from(RuntimeError::new("unreachable"));
+        assert_eq!(classify_trap(&user), None);
+        assert_eq!(classify_trap(&anyhow!("unreachable")), None);
+    }
+}
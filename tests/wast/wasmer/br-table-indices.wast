@@ -0,0 +1,108 @@
+;; `br_table` with indices around the end of its table and far past it,
+;; which all take the default arm, as the index is unsigned.
+
+(module
+  (func (export "small") (param i32) (result i32)
+    (block $default
+      (block $arm2
+        (block $arm1
+          (block $arm0
+            (br_table $arm0 $arm1 $arm2 $default (local.get 0))
+          )
+          (return (i32.const 100))
+        )
+        (return (i32.const 101))
+      )
+      (return (i32.const 102))
+    )
+    (i32.const 99)
+  )
+
+  ;; A table with nothing but a default, carrying a value.
+  (func (export "only-default") (param i32) (result i32)
+    (block $default (result i32)
+      (br_table $default (i32.const 7) (local.get 0))
+    )
+  )
+
+  ;; The index comes first, the others are the values the arms return,
+  ;; and the default returns their sum.
+  (func (export "pick") (param $i i32) (param $a i32) (param $b i32) (param $c i32) (result i32)
+    (block $default
+      (block $arm2
+        (block $arm1
+          (block $arm0
+            (br_table $arm0 $arm1 $arm2 $default (local.get $i))
+          )
+          (return (local.get $a))
+        )
+        (return (local.get $b))
+      )
+      (return (local.get $c))
+    )
+    (i32.add (local.get $a) (i32.add (local.get $b) (local.get $c)))
+  )
+
+  ;; Sixteen arms going to two labels.
+  (func (export "parity") (param i32) (result i32)
+    (block $default
+      (block $odd
+        (block $even
+          (br_table
+            $even $odd $even $odd $even $odd $even $odd
+            $even $odd $even $odd $even $odd $even $odd
+            $default
+            (local.get 0)
+          )
+        )
+        (return (i32.const 0))
+      )
+      (return (i32.const 1))
+    )
+    (i32.const -1)
+  )
+
+  ;; Index 0 leaves the loop and anything else, here 1, goes around again.
+  (func (export "countdown") (param $n i32) (result i32)
+    (local $steps i32)
+    (block $done
+      (loop $again
+        (local.set $steps (i32.add (local.get $steps) (i32.const 1)))
+        (local.set $n (i32.sub (local.get $n) (i32.const 1)))
+        (br_table $done $again (i32.ne (local.get $n) (i32.const 0)))
+      )
+    )
+    (local.get $steps)
+  )
+)
+
+(assert_return (invoke "small" (i32.const 0)) (i32.const 100))
+(assert_return (invoke "small" (i32.const 1)) (i32.const 101))
+(assert_return (invoke "small" (i32.const 2)) (i32.const 102))
+(assert_return (invoke "small" (i32.const 3)) (i32.const 99))
+(assert_return (invoke "small" (i32.const 4)) (i32.const 99))
+(assert_return (invoke "small" (i32.const 0x7fffffff)) (i32.const 99))
+(assert_return (invoke "small" (i32.const 0x80000000)) (i32.const 99))
+(assert_return (invoke "small" (i32.const -1)) (i32.const 99))
+
+(assert_return (invoke "only-default" (i32.const 0)) (i32.const 7))
+(assert_return (invoke "only-default" (i32.const 1)) (i32.const 7))
+(assert_return (invoke "only-default" (i32.const -1)) (i32.const 7))
+
+(assert_return (invoke "pick" (i32.const 0) (i32.const 10) (i32.const 20) (i32.const 30)) (i32.const 10))
+(assert_return (invoke "pick" (i32.const 1) (i32.const 10) (i32.const 20) (i32.const 30)) (i32.const 20))
+(assert_return (invoke "pick" (i32.const 2) (i32.const 10) (i32.const 20) (i32.const 30)) (i32.const 30))
+(assert_return (invoke "pick" (i32.const 3) (i32.const 10) (i32.const 20) (i32.const 30)) (i32.const 60))
+(assert_return (invoke "pick" (i32.const -2) (i32.const 10) (i32.const 20) (i32.const 30)) (i32.const 60))
+
+(assert_return (invoke "parity" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "parity" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "parity" (i32.const 8)) (i32.const 0))
+(assert_return (invoke "parity" (i32.const 14)) (i32.const 0))
+(assert_return (invoke "parity" (i32.const 15)) (i32.const 1))
+(assert_return (invoke "parity" (i32.const 16)) (i32.const -1))
+(assert_return (invoke "parity" (i32.const 0x10000)) (i32.const -1))
+(assert_return (invoke "parity" (i32.const -16)) (i32.const -1))
+
+(assert_return (invoke "countdown" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "countdown" (i32.const 5)) (i32.const 5))
@@ -0,0 +1,40 @@
+;; `memory.init` and `table.init` work from a passive segment until it is
+;; dropped. Afterwards the segment is empty: initializing nothing from it
+;; still succeeds, but anything more traps as an out of bounds access, and
+;; dropping it again is allowed.
+(module
+  (memory 1)
+  (data $passive "\01\02\03\04")
+  (table 4 funcref)
+  (elem $funcs func $one $two)
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (func (export "memory.init") (param i32 i32 i32)
+    (memory.init $passive (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "data.drop") (data.drop $passive))
+  (func (export "load8") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "table.init") (param i32 i32 i32)
+    (table.init $funcs (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "elem.drop") (elem.drop $funcs))
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (result i32) (local.get 0))))
+
+(invoke "memory.init" (i32.const 8) (i32.const 1) (i32.const 3))
+(assert_return (invoke "load8" (i32.const 8)) (i32.const 2))
+(assert_return (invoke "load8" (i32.const 10)) (i32.const 4))
+(invoke "data.drop")
+(invoke "memory.init" (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_trap (invoke "memory.init" (i32.const 0) (i32.const 0) (i32.const 1)) "out of bounds memory access")
+(assert_return (invoke "load8" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "load8" (i32.const 8)) (i32.const 2))
+(invoke "data.drop")
+
+(invoke "table.init" (i32.const 1) (i32.const 0) (i32.const 2))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "call" (i32.const 2)) (i32.const 2))
+(invoke "elem.drop")
+(invoke "table.init" (i32.const 0) (i32.const 0) (i32.const 0))
+(assert_trap (invoke "table.init" (i32.const 0) (i32.const 0) (i32.const 1)) "out of bounds table access")
+(assert_trap (invoke "call" (i32.const 0)) "uninitialized element")
+(assert_return (invoke "call" (i32.const 2)) (i32.const 2))
+(invoke "elem.drop")
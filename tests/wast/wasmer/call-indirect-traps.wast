@@ -0,0 +1,22 @@
+;; Each way `call_indirect` can fail traps with its own category, and the
+;; table set up by the element segment is indexed as declared.
+(module
+  (type $i32 (func (result i32)))
+  (type $i64 (func (result i64)))
+  (table 3 funcref)
+  (elem (i32.const 0) $const-i32 $unreachable)
+  (func $const-i32 (type $i32) (i32.const 7))
+  (func $unreachable (type $i32) unreachable)
+  (func (export "call-i32") (param i32) (result i32)
+    (call_indirect (type $i32) (local.get 0)))
+  (func (export "call-i64") (param i32) (result i64)
+    (call_indirect (type $i64) (local.get 0))))
+
+(assert_return (invoke "call-i32" (i32.const 0)) (i32.const 7))
+(assert_trap (invoke "call-i32" (i32.const 1)) "unreachable")
+(assert_trap (invoke "call-i64" (i32.const 0)) "indirect call type mismatch")
+;; The type is checked before the callee runs, so this doesn't reach its
+;; `unreachable`.
+(assert_trap (invoke "call-i64" (i32.const 1)) "indirect call type mismatch")
+(assert_trap (invoke "call-i32" (i32.const 2)) "uninitialized element")
+(assert_trap (invoke "call-i32" (i32.const 3)) "undefined element")
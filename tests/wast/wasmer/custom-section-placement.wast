@@ -0,0 +1,58 @@
+;; Custom sections may appear before, between and after any of the known
+;; sections, and must not change what the module does.
+(module binary
+  "\00asm" "\01\00\00\00"
+  ;; before the type section
+  "\00\05\04head"
+  ;; type 0: [] -> [i32]
+  "\01\05\01\60\00\01\7f"
+  ;; a payload that looks like a type section
+  "\00\0a\04mid1\01\05\01\60\00"
+  ;; func 0: type 0
+  "\03\02\01\00"
+  ;; memory 0: 1 page
+  "\05\03\01\00\01"
+  "\00\05\04mid2"
+  ;; export "answer" as func 0
+  "\07\0a\01\06answer\00\00"
+  "\00\05\04mid3"
+  ;; func 0: i32.load (i32.const 0)
+  "\0a\09\01\07\00\41\00\28\02\00\0b"
+  "\00\05\04mid4"
+  ;; data at 0: 42 as a little-endian i32
+  "\0b\0a\01\00\41\00\0b\04\2a\00\00\00"
+  ;; after the last section
+  "\00\05\04tail"
+)
+
+(assert_return (invoke "answer") (i32.const 42))
+
+;; A custom section whose size runs past the end of the module.
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\00\0a\04name"
+  )
+  "unexpected end"
+)
+
+;; A custom section whose name runs past the end of the section, even
+;; though the module has more bytes after it.
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    "\00\03\08abc"
+    "\01\01\00"
+  )
+  "length out of bounds"
+)
+
+;; Custom sections can only appear between sections, not inside one.
+(assert_malformed
+  (module binary
+    "\00asm" "\01\00\00\00"
+    ;; type section with a custom section spliced in before its entry
+    "\01\09\01\00\03abc\60\00\00"
+  )
+  "malformed functype"
+)
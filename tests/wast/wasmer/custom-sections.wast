@@ -0,0 +1,31 @@
+;; Modules with a `name` section, or with custom sections the runtime
+;; doesn't know, compile and keep the exports and types they declare.
+(module binary
+  "\00asm" "\01\00\00\00"
+  ;; type 0: [] -> [i32]
+  "\01\05\01\60\00\01\7f"
+  ;; a custom section the runtime doesn't know, between known sections
+  "\00\0b\07unknown\01\02\03"
+  ;; func 0: type 0
+  "\03\02\01\00"
+  ;; export "answer" as func 0
+  "\07\0a\01\06answer\00\00"
+  ;; func 0: i32.const 42
+  "\0a\06\01\04\00\41\2a\0b"
+  ;; name section naming func 0 `the_answer`
+  "\00\14\04name\01\0d\01\00\0athe_answer"
+)
+
+(assert_return (invoke "answer") (i32.const 42))
+
+;; The text format stores `$` identifiers in a name section.
+(module $named
+  (func $double (param $x i32) (result i32)
+    (i32.mul (local.get $x) (i32.const 2)))
+  (func $trap (unreachable))
+  (func (export "double") (param i32) (result i32)
+    (call $double (local.get 0)))
+  (func (export "trap") (call $trap)))
+
+(assert_return (invoke "double" (i32.const 21)) (i32.const 42))
+(assert_trap (invoke "trap") "unreachable")
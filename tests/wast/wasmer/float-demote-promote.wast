@@ -0,0 +1,44 @@
+;; We assert that `f32.demote_f64` and `f64.promote_f32` produce results of
+;; the right type and handle the edges of the `f32` range.
+
+(module
+  (func (export "demote") (param f64) (result f32) (f32.demote_f64 (local.get 0)))
+  (func (export "promote") (param f32) (result f64) (f64.promote_f32 (local.get 0))))
+
+(assert_return (invoke "demote" (f64.const 0x1.fffffep+127)) (f32.const 0x1.fffffep+127))
+(assert_return (invoke "demote" (f64.const 0x1.fffffffp+127)) (f32.const inf))
+(assert_return (invoke "demote" (f64.const 0x1p-149)) (f32.const 0x1p-149))
+(assert_return (invoke "demote" (f64.const 0x1p-150)) (f32.const 0.0))
+(assert_return (invoke "demote" (f64.const -0x1p-150)) (f32.const -0.0))
+(assert_return (invoke "demote" (f64.const -0.0)) (f32.const -0.0))
+(assert_return (invoke "demote" (f64.const nan)) (f32.const nan:canonical))
+
+(assert_return (invoke "promote" (f32.const 0x1.fffffep+127)) (f64.const 0x1.fffffep+127))
+(assert_return (invoke "promote" (f32.const 0x1p-149)) (f64.const 0x1p-149))
+(assert_return (invoke "promote" (f32.const -inf)) (f64.const -inf))
+(assert_return (invoke "promote" (f32.const -0.0)) (f64.const -0.0))
+(assert_return (invoke "promote" (f32.const nan)) (f64.const nan:canonical))
+
+;; NaNs of either sign come out as NaNs of the result's width: a canonical
+;; NaN stays canonical, and any other NaN, signalling ones included, comes
+;; out as an arithmetic NaN.
+(assert_return (invoke "demote" (f64.const -nan)) (f32.const nan:canonical))
+(assert_return (invoke "demote" (f64.const nan:0x4000000000000)) (f32.const nan:arithmetic))
+(assert_return (invoke "demote" (f64.const -nan:0xfffffffffffff)) (f32.const nan:arithmetic))
+(assert_return (invoke "promote" (f32.const -nan)) (f64.const nan:canonical))
+(assert_return (invoke "promote" (f32.const nan:0x200000)) (f64.const nan:arithmetic))
+(assert_return (invoke "promote" (f32.const -nan:0x7fffff)) (f64.const nan:arithmetic))
+
+;; The same with constant operands, which the compiler may fold.
+(module
+  (func (export "demote_canonical") (result f32) (f32.demote_f64 (f64.const nan)))
+  (func (export "demote_signalling") (result f32)
+    (f32.demote_f64 (f64.const nan:0x4000000000000)))
+  (func (export "promote_canonical") (result f64) (f64.promote_f32 (f32.const nan)))
+  (func (export "promote_signalling") (result f64)
+    (f64.promote_f32 (f32.const nan:0x200000))))
+
+(assert_return (invoke "demote_canonical") (f32.const nan:canonical))
+(assert_return (invoke "demote_signalling") (f32.const nan:arithmetic))
+(assert_return (invoke "promote_canonical") (f64.const nan:canonical))
+(assert_return (invoke "promote_signalling") (f64.const nan:arithmetic))
@@ -0,0 +1,47 @@
+;; Globals initialized from constant expressions, including `global.get` of
+;; imported globals, read the initialized values through `get` and from
+;; code, and a mutable copy doesn't write through to the imported global.
+(module
+  (import "spectest" "global_i32" (global $imported_i32 i32))
+  (import "spectest" "global_i64" (global $imported_i64 i64))
+  (import "spectest" "global_f32" (global $imported_f32 f32))
+  (import "spectest" "global_f64" (global $imported_f64 f64))
+  (global $const_i32 i32 (i32.const -7))
+  (global $const_f64 f64 (f64.const -0x1.8p+1))
+  (global (export "from_i32") i32 (global.get $imported_i32))
+  (global (export "from_i64") i64 (global.get $imported_i64))
+  (global (export "from_f32") f32 (global.get $imported_f32))
+  (global (export "from_f64") f64 (global.get $imported_f64))
+  (global $copy (export "copy") (mut i32) (global.get $imported_i32))
+  (global (export "const_i32") i32 (global.get $const_i32))
+  (global (export "const_f64") f64 (global.get $const_f64))
+  (func (export "bump") (result i32)
+    (global.set $copy (i32.add (global.get $copy) (i32.const 1)))
+    (global.get $copy))
+  (func (export "imported") (result i32) (global.get $imported_i32)))
+
+(assert_return (get "from_i32") (i32.const 666))
+(assert_return (get "from_i64") (i64.const 666))
+(assert_return (get "from_f32") (f32.const 666))
+(assert_return (get "from_f64") (f64.const 666))
+(assert_return (get "const_i32") (i32.const -7))
+(assert_return (get "const_f64") (f64.const -3))
+(assert_return (get "copy") (i32.const 666))
+(assert_return (invoke "bump") (i32.const 667))
+(assert_return (get "copy") (i32.const 667))
+(assert_return (invoke "imported") (i32.const 666))
+
+;; A global exported by a registered module initializes the globals of
+;; the module importing it.
+(module $exporter
+  (global (export "base") i32 (i32.const 40)))
+(register "exporter" $exporter)
+
+(module
+  (import "exporter" "base" (global $base i32))
+  (global (export "answer") i32 (global.get $base))
+  (func (export "answer_plus") (param i32) (result i32)
+    (i32.add (global.get $base) (local.get 0))))
+
+(assert_return (get "answer") (i32.const 40))
+(assert_return (invoke "answer_plus" (i32.const 2)) (i32.const 42))
@@ -0,0 +1,53 @@
+;; Mutable globals set by one directive and read back by the next ones,
+;; through `get` and from code, of each type, and through a module which
+;; imports them.  Each value depends on the directives before it.
+
+(module $counter
+  (global $g (export "g") (mut i32) (i32.const 0))
+  (global $wide (export "wide") (mut i64) (i64.const 0))
+  (global $ratio (export "ratio") (mut f32) (f32.const 0))
+  (global $precise (export "precise") (mut f64) (f64.const 0))
+  (func (export "set_g") (param i32) (global.set $g (local.get 0)))
+  (func (export "add_g") (param i32) (result i32)
+    (global.set $g (i32.add (global.get $g) (local.get 0)))
+    (global.get $g)
+  )
+  (func (export "get_g") (result i32) (global.get $g))
+  (func (export "set_all") (param i64 f32 f64)
+    (global.set $wide (local.get 0))
+    (global.set $ratio (local.get 1))
+    (global.set $precise (local.get 2))
+  )
+)
+
+(assert_return (get "g") (i32.const 0))
+(invoke "set_g" (i32.const 42))
+(assert_return (get "g") (i32.const 42))
+(assert_return (invoke "get_g") (i32.const 42))
+(assert_return (invoke "add_g" (i32.const 8)) (i32.const 50))
+(assert_return (get "g") (i32.const 50))
+(invoke "set_g" (i32.const -1))
+(assert_return (get "g") (i32.const -1))
+(assert_return (invoke "add_g" (i32.const 1)) (i32.const 0))
+
+(assert_return (get "wide") (i64.const 0))
+(invoke "set_all" (i64.const 0x7fffffffffffffff) (f32.const -1.5) (f64.const 0x1p-1074))
+(assert_return (get "wide") (i64.const 0x7fffffffffffffff))
+(assert_return (get "ratio") (f32.const -1.5))
+(assert_return (get "precise") (f64.const 0x1p-1074))
+
+;; A module importing the global sees and makes the same changes.
+(register "counter" $counter)
+(module $user
+  (import "counter" "g" (global $g (mut i32)))
+  (func (export "double") (result i32)
+    (global.set $g (i32.mul (global.get $g) (i32.const 2)))
+    (global.get $g)
+  )
+)
+
+(invoke $counter "set_g" (i32.const 21))
+(assert_return (invoke $user "double") (i32.const 42))
+(assert_return (get $counter "g") (i32.const 42))
+(assert_return (invoke $counter "add_g" (i32.const 1)) (i32.const 43))
+(assert_return (invoke $user "double") (i32.const 86))
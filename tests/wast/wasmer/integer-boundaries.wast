@@ -0,0 +1,45 @@
+;; We assert that integer constants at the signed/unsigned boundaries
+;; are passed to and returned from functions bit-for-bit.
+
+(module
+  (func (export "i32.id") (param i32) (result i32) (local.get 0))
+  (func (export "i64.id") (param i64) (result i64) (local.get 0))
+  (func (export "i32.min_s") (result i32) (i32.const 0x8000_0000))
+  (func (export "i32.all_ones") (result i32) (i32.const 0xFFFF_FFFF))
+  (func (export "i64.min_s") (result i64) (i64.const 0x8000_0000_0000_0000))
+  (func (export "i64.all_ones") (result i64) (i64.const 0xFFFF_FFFF_FFFF_FFFF)))
+
+(assert_return (invoke "i32.id" (i32.const 0x7FFF_FFFF)) (i32.const 2147483647))
+(assert_return (invoke "i32.id" (i32.const 0x8000_0000)) (i32.const -2147483648))
+(assert_return (invoke "i32.id" (i32.const 0xFFFF_FFFF)) (i32.const -1))
+(assert_return (invoke "i64.id" (i64.const 0x8000_0000)) (i64.const 2147483648))
+(assert_return (invoke "i64.id" (i64.const 0xFFFF_FFFF)) (i64.const 4294967295))
+(assert_return (invoke "i64.id" (i64.const 0x7FFF_FFFF_FFFF_FFFF)) (i64.const 9223372036854775807))
+(assert_return (invoke "i64.id" (i64.const 0x8000_0000_0000_0000)) (i64.const -9223372036854775808))
+(assert_return (invoke "i64.id" (i64.const 0xFFFF_FFFF_FFFF_FFFF)) (i64.const -1))
+(assert_return (invoke "i32.min_s") (i32.const 0x8000_0000))
+(assert_return (invoke "i32.all_ones") (i32.const -1))
+(assert_return (invoke "i64.min_s") (i64.const 0x8000_0000_0000_0000))
+(assert_return (invoke "i64.all_ones") (i64.const -1))
+
+;; Operations which tell the signed and unsigned readings of the same
+;; 64-bit pattern apart see exactly the bits above `i64::MAX`.
+(module
+  (func (export "i64.lt_u") (param i64 i64) (result i32) (i64.lt_u (local.get 0) (local.get 1)))
+  (func (export "i64.lt_s") (param i64 i64) (result i32) (i64.lt_s (local.get 0) (local.get 1)))
+  (func (export "i64.shr_u") (param i64 i64) (result i64) (i64.shr_u (local.get 0) (local.get 1)))
+  (func (export "i64.div_u") (param i64 i64) (result i64) (i64.div_u (local.get 0) (local.get 1)))
+  (func (export "i64.extend_i32_u") (param i32) (result i64) (i64.extend_i32_u (local.get 0)))
+  (func (export "i64.extend_i32_s") (param i32) (result i64) (i64.extend_i32_s (local.get 0))))
+
+(assert_return (invoke "i64.lt_u" (i64.const 0x7FFF_FFFF_FFFF_FFFF) (i64.const 0x8000_0000_0000_0000)) (i32.const 1))
+(assert_return (invoke "i64.lt_s" (i64.const 0x7FFF_FFFF_FFFF_FFFF) (i64.const 0x8000_0000_0000_0000)) (i32.const 0))
+(assert_return (invoke "i64.lt_u" (i64.const 0) (i64.const 0xFFFF_FFFF_FFFF_FFFF)) (i32.const 1))
+(assert_return (invoke "i64.lt_s" (i64.const 0) (i64.const 0xFFFF_FFFF_FFFF_FFFF)) (i32.const 0))
+(assert_return (invoke "i64.shr_u" (i64.const 0xFFFF_FFFF_FFFF_FFFF) (i64.const 1)) (i64.const 0x7FFF_FFFF_FFFF_FFFF))
+(assert_return (invoke "i64.shr_u" (i64.const 0x8000_0000_0000_0000) (i64.const 63)) (i64.const 1))
+(assert_return (invoke "i64.div_u" (i64.const 0xFFFF_FFFF_FFFF_FFFF) (i64.const 0xFFFF_FFFF)) (i64.const 0x1_0000_0001))
+(assert_return (invoke "i64.div_u" (i64.const -2) (i64.const 0x8000_0000_0000_0000)) (i64.const 1))
+(assert_return (invoke "i64.extend_i32_u" (i32.const 0xFFFF_FFFF)) (i64.const 0xFFFF_FFFF))
+(assert_return (invoke "i64.extend_i32_s" (i32.const 0xFFFF_FFFF)) (i64.const 0xFFFF_FFFF_FFFF_FFFF))
+(assert_return (invoke "i64.extend_i32_s" (i32.const 0x8000_0000)) (i64.const 0xFFFF_FFFF_8000_0000))
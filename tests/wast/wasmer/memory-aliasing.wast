@@ -0,0 +1,58 @@
+;; We assert that the optimizer keeps loads and stores which alias each
+;; other through accesses of different widths, calls, loops and traps, in
+;; the spirit of the spec's `memory_redundancy.wast`.
+
+(module
+  (memory 1 1)
+
+  (func (export "load32") (param i32) (result i32) (i32.load (local.get 0)))
+
+  (func $poke (param i32 i32) (i32.store (local.get 0) (local.get 1)))
+
+  ;; A narrower store into the middle of a wider value.
+  (func (export "wide_then_narrow") (result i64)
+    (i64.store (i32.const 0) (i64.const 0x1111111122222222))
+    (i32.store (i32.const 4) (i32.const 0x33333333))
+    (i64.load (i32.const 0)))
+
+  ;; A byte store overlapping a word loaded again afterwards.
+  (func (export "byte_overlap") (result i32)
+    (i32.store (i32.const 16) (i32.const 0x44444444))
+    (i32.store8 (i32.const 17) (i32.const 0x55))
+    (i32.load (i32.const 16)))
+
+  ;; A load can't be reused after a call which stores to the same address.
+  (func (export "load_across_call") (result i32)
+    (local $before i32)
+    (i32.store (i32.const 32) (i32.const 1))
+    (local.set $before (i32.load (i32.const 32)))
+    (call $poke (i32.const 32) (i32.const 2))
+    (i32.add (local.get $before) (i32.load (i32.const 32))))
+
+  ;; A load in a loop which stores to the same address can't be hoisted.
+  (func (export "loop_counter") (result i32)
+    (i32.store (i32.const 48) (i32.const 0))
+    (loop $count
+      (i32.store (i32.const 48) (i32.add (i32.load (i32.const 48)) (i32.const 1)))
+      (br_if $count (i32.lt_u (i32.load (i32.const 48)) (i32.const 10))))
+    (i32.load (i32.const 48)))
+
+  ;; A store before a trap isn't dead, even if a later store overwrites it
+  ;; on the path which doesn't trap.
+  (func (export "store_then_trap") (param i32)
+    (i32.store (i32.const 64) (local.get 0))
+    (drop (i32.load (i32.const 65536)))
+    (i32.store (i32.const 64) (i32.const 0)))
+
+  ;; A float stored and loaded back as an integer.
+  (func (export "float_as_int") (result i32)
+    (f32.store (i32.const 80) (f32.const 1.0))
+    (i32.load (i32.const 80))))
+
+(assert_return (invoke "wide_then_narrow") (i64.const 0x3333333322222222))
+(assert_return (invoke "byte_overlap") (i32.const 0x44445544))
+(assert_return (invoke "load_across_call") (i32.const 3))
+(assert_return (invoke "loop_counter") (i32.const 10))
+(assert_trap (invoke "store_then_trap" (i32.const 7)) "out of bounds memory access")
+(assert_return (invoke "load32" (i32.const 64)) (i32.const 7))
+(assert_return (invoke "float_as_int") (i32.const 0x3f800000))
@@ -0,0 +1,48 @@
+;; We assert that memory accesses are little-endian regardless of the
+;; host byte order, by storing and loading values against explicit bytes.
+
+(module
+  (memory 1)
+  ;; 0x3f800000 (f32 1.0) and 0x3ff0000000000000 (f64 1.0), little-endian
+  (data (i32.const 0) "\00\00\80\3f")
+  (data (i32.const 8) "\00\00\00\00\00\00\f0\3f")
+  ;; 0x01020304 and 0x0102030405060708, little-endian
+  (data (i32.const 16) "\04\03\02\01")
+  (data (i32.const 24) "\08\07\06\05\04\03\02\01")
+
+  (func (export "f32.load") (param i32) (result f32) (f32.load (local.get 0)))
+  (func (export "f64.load") (param i32) (result f64) (f64.load (local.get 0)))
+  (func (export "i32.load") (param i32) (result i32) (i32.load (local.get 0)))
+  (func (export "i64.load") (param i32) (result i64) (i64.load (local.get 0)))
+  (func (export "byte") (param i32) (result i32) (i32.load8_u (local.get 0)))
+
+  (func (export "f32.store") (param i32 f32) (f32.store (local.get 0) (local.get 1)))
+  (func (export "f64.store") (param i32 f64) (f64.store (local.get 0) (local.get 1)))
+  (func (export "i32.store") (param i32 i32) (i32.store (local.get 0) (local.get 1)))
+  (func (export "i64.store") (param i32 i64) (i64.store (local.get 0) (local.get 1))))
+
+;; Loads of bytes placed in memory by data segments.
+(assert_return (invoke "f32.load" (i32.const 0)) (f32.const 1.0))
+(assert_return (invoke "f64.load" (i32.const 8)) (f64.const 1.0))
+(assert_return (invoke "i32.load" (i32.const 16)) (i32.const 0x01020304))
+(assert_return (invoke "i64.load" (i32.const 24)) (i64.const 0x0102030405060708))
+
+;; Stores, observed byte by byte.
+(invoke "f32.store" (i32.const 32) (f32.const -0x1.fffffep+127))
+(assert_return (invoke "byte" (i32.const 32)) (i32.const 0xff))
+(assert_return (invoke "byte" (i32.const 33)) (i32.const 0xff))
+(assert_return (invoke "byte" (i32.const 34)) (i32.const 0x7f))
+(assert_return (invoke "byte" (i32.const 35)) (i32.const 0xff))
+
+(invoke "f64.store" (i32.const 40) (f64.const -0.0))
+(assert_return (invoke "byte" (i32.const 40)) (i32.const 0x00))
+(assert_return (invoke "byte" (i32.const 46)) (i32.const 0x00))
+(assert_return (invoke "byte" (i32.const 47)) (i32.const 0x80))
+
+(invoke "i32.store" (i32.const 48) (i32.const 0x0a0b0c0d))
+(assert_return (invoke "byte" (i32.const 48)) (i32.const 0x0d))
+(assert_return (invoke "byte" (i32.const 51)) (i32.const 0x0a))
+
+(invoke "i64.store" (i32.const 56) (i64.const 0x0102030405060708))
+(assert_return (invoke "byte" (i32.const 56)) (i32.const 0x08))
+(assert_return (invoke "byte" (i32.const 63)) (i32.const 0x01))
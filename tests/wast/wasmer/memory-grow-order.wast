@@ -0,0 +1,32 @@
+;; We assert that directives run in order against the same instance, so
+;; each `memory.grow` sees the size left by the previous one, and loads
+;; are bounded by the current size.
+
+(module
+  (memory 1 3)
+  (func (export "grow") (param i32) (result i32) (memory.grow (local.get 0)))
+  (func (export "size") (result i32) (memory.size))
+  (func (export "load") (param i32) (result i32) (i32.load8_u (local.get 0)))
+  (func (export "store") (param i32 i32) (i32.store8 (local.get 0) (local.get 1))))
+
+(assert_return (invoke "size") (i32.const 1))
+(assert_trap (invoke "load" (i32.const 0x10000)) "out of bounds memory access")
+
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 1))
+(assert_return (invoke "size") (i32.const 2))
+(assert_return (invoke "load" (i32.const 0x10000)) (i32.const 0))
+(invoke "store" (i32.const 0x10000) (i32.const 42))
+(assert_trap (invoke "load" (i32.const 0x20000)) "out of bounds memory access")
+
+;; Growing past the maximum fails and leaves the memory as it was.
+(assert_return (invoke "grow" (i32.const 2)) (i32.const -1))
+(assert_return (invoke "size") (i32.const 2))
+(assert_return (invoke "load" (i32.const 0x10000)) (i32.const 42))
+(assert_trap (invoke "load" (i32.const 0x20000)) "out of bounds memory access")
+
+(assert_return (invoke "grow" (i32.const 0)) (i32.const 2))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const 2))
+(assert_return (invoke "size") (i32.const 3))
+(assert_return (invoke "load" (i32.const 0x2ffff)) (i32.const 0))
+(assert_return (invoke "grow" (i32.const 1)) (i32.const -1))
+(assert_return (invoke "load" (i32.const 0x10000)) (i32.const 42))
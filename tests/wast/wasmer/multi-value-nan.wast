@@ -0,0 +1,21 @@
+;; We assert that each result of a multi-value return is compared on its
+;; own, so NaN patterns and exact values can be mixed.
+
+(module
+  (func (export "nan-and-i32") (param f32 i32) (result f32 i32)
+    (f32.div (local.get 0) (f32.const 0)) (local.get 1))
+  (func (export "i64-and-nan") (param i64 f64) (result i64 f64)
+    (local.get 0) (f64.sqrt (local.get 1)))
+  (func (export "nan-and-nan") (result f32 f64)
+    (f32.const nan:0x600000) (f64.const -nan)))
+
+(assert_return (invoke "nan-and-i32" (f32.const 0) (i32.const 3))
+  (f32.const nan:canonical) (i32.const 3))
+(assert_return (invoke "nan-and-i32" (f32.const 1) (i32.const -1))
+  (f32.const inf) (i32.const -1))
+(assert_return (invoke "i64-and-nan" (i64.const 7) (f64.const -1))
+  (i64.const 7) (f64.const nan:canonical))
+(assert_return (invoke "i64-and-nan" (i64.const -7) (f64.const 4))
+  (i64.const -7) (f64.const 2))
+(assert_return (invoke "nan-and-nan")
+  (f32.const nan:arithmetic) (f64.const nan:arithmetic))
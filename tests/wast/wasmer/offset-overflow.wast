@@ -0,0 +1,58 @@
+;; We assert that the effective address of a load or store, the address
+;; plus the `offset` immediate, is computed without wrapping around 2^32,
+;; so an access whose sum overflows 32 bits traps rather than reaching the
+;; start of the memory, while the accesses right below the end of the
+;; memory still work.
+
+(module
+  (memory 1)
+  (data (i32.const 0) "\01\02\03\04\05\06\07\08")
+  (data (i32.const 65528) "\f8\f9\fa\fb\fc\fd\fe\ff")
+
+  (func (export "load8_max_offset") (param i32) (result i32)
+    (i32.load8_u offset=4294967295 (local.get 0)))
+  (func (export "load_max_offset") (param i32) (result i32)
+    (i32.load offset=4294967295 (local.get 0)))
+  (func (export "load64_offset_65528") (param i32) (result i64)
+    (i64.load offset=65528 (local.get 0)))
+  (func (export "load8_offset_1") (param i32) (result i32)
+    (i32.load8_u offset=1 (local.get 0)))
+  (func (export "load_offset_4") (param i32) (result i32)
+    (i32.load offset=4 (local.get 0)))
+  (func (export "store_max_offset") (param i32)
+    (i32.store offset=4294967295 (local.get 0) (i32.const 0)))
+  (func (export "store64_offset_8") (param i32)
+    (i64.store offset=8 (local.get 0) (i64.const 0)))
+  (func (export "load_at") (param i32) (result i32)
+    (i32.load (local.get 0))))
+
+;; In bounds, up to the last byte.
+(assert_return (invoke "load64_offset_65528" (i32.const 0)) (i64.const 0xfffefdfcfbfaf9f8))
+(assert_return (invoke "load8_offset_1" (i32.const 65534)) (i32.const 0xff))
+(assert_return (invoke "load_offset_4" (i32.const 65528)) (i32.const 0xfffefdfc))
+(assert_return (invoke "load_offset_4" (i32.const 0)) (i32.const 0x08070605))
+
+;; Past the end of the memory, without any overflow.
+(assert_trap (invoke "load64_offset_65528" (i32.const 1)) "out of bounds memory access")
+(assert_trap (invoke "load8_offset_1" (i32.const 65535)) "out of bounds memory access")
+(assert_trap (invoke "load_offset_4" (i32.const 65529)) "out of bounds memory access")
+(assert_trap (invoke "load8_max_offset" (i32.const 0)) "out of bounds memory access")
+
+;; Sums of exactly 2^32 and above, which would wrap to the start of the
+;; memory with 32-bit arithmetic.
+(assert_trap (invoke "load8_max_offset" (i32.const 1)) "out of bounds memory access")
+(assert_trap (invoke "load8_max_offset" (i32.const 2)) "out of bounds memory access")
+(assert_trap (invoke "load_max_offset" (i32.const 1)) "out of bounds memory access")
+(assert_trap (invoke "load_max_offset" (i32.const -1)) "out of bounds memory access")
+(assert_trap (invoke "load8_offset_1" (i32.const -1)) "out of bounds memory access")
+(assert_trap (invoke "load_offset_4" (i32.const -4)) "out of bounds memory access")
+(assert_trap (invoke "load_offset_4" (i32.const -2)) "out of bounds memory access")
+(assert_trap (invoke "load64_offset_65528" (i32.const -65528)) "out of bounds memory access")
+
+;; A store whose address wraps traps without writing anything at the start
+;; of the memory.
+(assert_trap (invoke "store_max_offset" (i32.const 1)) "out of bounds memory access")
+(assert_trap (invoke "store_max_offset" (i32.const 5)) "out of bounds memory access")
+(assert_trap (invoke "store64_offset_8" (i32.const -8)) "out of bounds memory access")
+(assert_return (invoke "load_at" (i32.const 0)) (i32.const 0x04030201))
+(assert_return (invoke "load_at" (i32.const 4)) (i32.const 0x08070605))
@@ -0,0 +1,12 @@
+;; A module given as quoted text is compiled and instantiated like any
+;; other module, and the following assertions run against it.
+(module quote
+  "(func (export \"answer\") (result i32)"
+  "  (i32.const 42))")
+
+(assert_return (invoke "answer") (i32.const 42))
+
+;; The quoted text may also hold a whole module.
+(module quote "(module (func (export \"double\") (param i32) (result i32) (i32.add (local.get 0) (local.get 0))))")
+
+(assert_return (invoke "double" (i32.const 21)) (i32.const 42))
@@ -0,0 +1,65 @@
+;; Growing tables of references with `table.grow`, and the sizes `table.size`
+;; reports along the way, in the same way as `memory.grow` and `memory.size`.
+
+(module
+  (table $funcs 1 4 funcref)
+  (table $externs 0 externref)
+  (func $seven (result i32) (i32.const 7))
+  (elem declare func $seven)
+  (type $i32 (func (result i32)))
+
+  (func (export "size-funcs") (result i32) (table.size $funcs))
+  (func (export "size-externs") (result i32) (table.size $externs))
+
+  ;; Grows the table with `$seven` in the new slots, returning the old size.
+  (func (export "grow-funcs") (param i32) (result i32)
+    (table.grow $funcs (ref.func $seven) (local.get 0))
+  )
+  (func (export "grow-funcs-null") (param i32) (result i32)
+    (table.grow $funcs (ref.null func) (local.get 0))
+  )
+  (func (export "grow-externs") (param externref i32) (result i32)
+    (table.grow $externs (local.get 0) (local.get 1))
+  )
+
+  (func (export "call") (param i32) (result i32)
+    (call_indirect $funcs (type $i32) (local.get 0))
+  )
+  (func (export "get-funcs") (param i32) (result funcref)
+    (table.get $funcs (local.get 0))
+  )
+  (func (export "get-externs") (param i32) (result externref)
+    (table.get $externs (local.get 0))
+  )
+)
+
+(assert_return (invoke "size-funcs") (i32.const 1))
+(assert_trap (invoke "get-funcs" (i32.const 1)) "out of bounds table access")
+
+(assert_return (invoke "grow-funcs" (i32.const 2)) (i32.const 1))
+(assert_return (invoke "size-funcs") (i32.const 3))
+(assert_return (invoke "get-funcs" (i32.const 0)) (ref.null func))
+(assert_return (invoke "get-funcs" (i32.const 2)) (ref.func))
+(assert_return (invoke "call" (i32.const 1)) (i32.const 7))
+(assert_trap (invoke "call" (i32.const 0)) "uninitialized element")
+(assert_trap (invoke "get-funcs" (i32.const 3)) "out of bounds table access")
+
+;; Growing by nothing succeeds and leaves the size alone, and growing past
+;; the maximum fails with -1 and leaves it alone as well.
+(assert_return (invoke "grow-funcs" (i32.const 0)) (i32.const 3))
+(assert_return (invoke "grow-funcs-null" (i32.const 2)) (i32.const -1))
+(assert_return (invoke "size-funcs") (i32.const 3))
+(assert_return (invoke "grow-funcs-null" (i32.const 1)) (i32.const 3))
+(assert_return (invoke "size-funcs") (i32.const 4))
+(assert_return (invoke "get-funcs" (i32.const 3)) (ref.null func))
+(assert_return (invoke "grow-funcs" (i32.const 1)) (i32.const -1))
+
+;; A table without a maximum starting out empty.
+(assert_return (invoke "size-externs") (i32.const 0))
+(assert_trap (invoke "get-externs" (i32.const 0)) "out of bounds table access")
+(assert_return (invoke "grow-externs" (ref.extern 1) (i32.const 2)) (i32.const 0))
+(assert_return (invoke "grow-externs" (ref.null extern) (i32.const 1)) (i32.const 2))
+(assert_return (invoke "size-externs") (i32.const 3))
+(assert_return (invoke "get-externs" (i32.const 1)) (ref.extern 1))
+(assert_return (invoke "get-externs" (i32.const 2)) (ref.null extern))
+(assert_trap (invoke "get-externs" (i32.const 3)) "out of bounds table access")
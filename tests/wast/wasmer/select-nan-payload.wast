@@ -0,0 +1,48 @@
+;; We assert that `select` returns the float operand it picks bit for bit,
+;; keeping the payload and sign of NaNs, signalling ones included, as it
+;; isn't an arithmetic operation and must not canonicalize NaNs.
+
+(module
+  (memory 1)
+
+  (func (export "select_f32") (param f32 f32 i32) (result f32)
+    (select (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "select_f64") (param f64 f64 i32) (result f64)
+    (select (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "typed_select_f32") (param f32 f32 i32) (result f32)
+    (select (result f32) (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "typed_select_f64") (param f64 f64 i32) (result f64)
+    (select (result f64) (local.get 0) (local.get 1) (local.get 2)))
+
+  ;; The NaNs are loaded from memory and the result stored back and read as
+  ;; an integer, so the bits are checked without going through a float
+  ;; result.
+  (func (export "select_f32_bits") (param i32 i32) (result i32)
+    (i32.store (i32.const 0) (local.get 0))
+    (f32.store (i32.const 8)
+      (select (f32.load (i32.const 0)) (f32.const 1) (local.get 1)))
+    (i32.load (i32.const 8)))
+  (func (export "select_f64_bits") (param i64 i32) (result i64)
+    (i64.store (i32.const 0) (local.get 0))
+    (f64.store (i32.const 8)
+      (select (f64.const 1) (f64.load (i32.const 0)) (local.get 1)))
+    (i64.load (i32.const 8))))
+
+(assert_return (invoke "select_f32" (f32.const nan:0x1) (f32.const 1) (i32.const 1)) (f32.const nan:0x1))
+(assert_return (invoke "select_f32" (f32.const 1) (f32.const -nan:0x200001) (i32.const 0)) (f32.const -nan:0x200001))
+(assert_return (invoke "select_f32" (f32.const -nan) (f32.const nan:0x1) (i32.const 1)) (f32.const -nan))
+(assert_return (invoke "select_f32" (f32.const -nan) (f32.const nan:0x1) (i32.const 0)) (f32.const nan:0x1))
+(assert_return (invoke "select_f64" (f64.const nan:0x1) (f64.const 1) (i32.const 1)) (f64.const nan:0x1))
+(assert_return (invoke "select_f64" (f64.const 1) (f64.const -nan:0x8000000000001) (i32.const 0)) (f64.const -nan:0x8000000000001))
+(assert_return (invoke "select_f64" (f64.const -nan) (f64.const nan:0x1) (i32.const 1)) (f64.const -nan))
+(assert_return (invoke "typed_select_f32" (f32.const nan:0x1) (f32.const 1) (i32.const 1)) (f32.const nan:0x1))
+(assert_return (invoke "typed_select_f32" (f32.const 1) (f32.const -nan:0x7fffff) (i32.const 0)) (f32.const -nan:0x7fffff))
+(assert_return (invoke "typed_select_f64" (f64.const nan:0x1) (f64.const 1) (i32.const 1)) (f64.const nan:0x1))
+(assert_return (invoke "typed_select_f64" (f64.const 1) (f64.const -nan:0xfffffffffffff) (i32.const 0)) (f64.const -nan:0xfffffffffffff))
+
+(assert_return (invoke "select_f32_bits" (i32.const 0x7f800001) (i32.const 1)) (i32.const 0x7f800001))
+(assert_return (invoke "select_f32_bits" (i32.const 0xffa00000) (i32.const -1)) (i32.const 0xffa00000))
+(assert_return (invoke "select_f32_bits" (i32.const 0x7f800001) (i32.const 0)) (i32.const 0x3f800000))
+(assert_return (invoke "select_f64_bits" (i64.const 0x7ff0000000000001) (i32.const 0)) (i64.const 0x7ff0000000000001))
+(assert_return (invoke "select_f64_bits" (i64.const 0xfff4000000000000) (i32.const 0)) (i64.const 0xfff4000000000000))
+(assert_return (invoke "select_f64_bits" (i64.const 0x7ff0000000000001) (i32.const 1)) (i64.const 0x3ff0000000000000))
@@ -0,0 +1,38 @@
+;; We assert that float results are compared bit-for-bit, so that `+0.0`
+;; and `-0.0` are told apart.
+
+(module
+  (func (export "f32.copysign") (param f32 f32) (result f32)
+    (f32.copysign (local.get 0) (local.get 1)))
+  (func (export "f64.copysign") (param f64 f64) (result f64)
+    (f64.copysign (local.get 0) (local.get 1)))
+  (func (export "f32.neg") (param f32) (result f32) (f32.neg (local.get 0)))
+  (func (export "f64.neg") (param f64) (result f64) (f64.neg (local.get 0)))
+  (func (export "f32.min") (param f32 f32) (result f32) (f32.min (local.get 0) (local.get 1)))
+  (func (export "f32.max") (param f32 f32) (result f32) (f32.max (local.get 0) (local.get 1)))
+  (func (export "f64.min") (param f64 f64) (result f64) (f64.min (local.get 0) (local.get 1)))
+  (func (export "f64.max") (param f64 f64) (result f64) (f64.max (local.get 0) (local.get 1)))
+  (func (export "f32.nearest") (param f32) (result f32) (f32.nearest (local.get 0)))
+  (func (export "f64.nearest") (param f64) (result f64) (f64.nearest (local.get 0))))
+
+(assert_return (invoke "f32.copysign" (f32.const 0.0) (f32.const -1.0)) (f32.const -0.0))
+(assert_return (invoke "f32.copysign" (f32.const -0.0) (f32.const 1.0)) (f32.const 0.0))
+(assert_return (invoke "f64.copysign" (f64.const 0.0) (f64.const -1.0)) (f64.const -0.0))
+(assert_return (invoke "f64.copysign" (f64.const -0.0) (f64.const 1.0)) (f64.const 0.0))
+
+(assert_return (invoke "f32.neg" (f32.const 0.0)) (f32.const -0.0))
+(assert_return (invoke "f32.neg" (f32.const -0.0)) (f32.const 0.0))
+(assert_return (invoke "f64.neg" (f64.const 0.0)) (f64.const -0.0))
+(assert_return (invoke "f64.neg" (f64.const -0.0)) (f64.const 0.0))
+
+(assert_return (invoke "f32.min" (f32.const 0.0) (f32.const -0.0)) (f32.const -0.0))
+(assert_return (invoke "f32.min" (f32.const -0.0) (f32.const 0.0)) (f32.const -0.0))
+(assert_return (invoke "f32.max" (f32.const 0.0) (f32.const -0.0)) (f32.const 0.0))
+(assert_return (invoke "f32.max" (f32.const -0.0) (f32.const 0.0)) (f32.const 0.0))
+(assert_return (invoke "f64.min" (f64.const 0.0) (f64.const -0.0)) (f64.const -0.0))
+(assert_return (invoke "f64.min" (f64.const -0.0) (f64.const 0.0)) (f64.const -0.0))
+(assert_return (invoke "f64.max" (f64.const 0.0) (f64.const -0.0)) (f64.const 0.0))
+(assert_return (invoke "f64.max" (f64.const -0.0) (f64.const 0.0)) (f64.const 0.0))
+
+(assert_return (invoke "f32.nearest" (f32.const -0.4)) (f32.const -0.0))
+(assert_return (invoke "f64.nearest" (f64.const -0.4)) (f64.const -0.0))
@@ -0,0 +1,65 @@
+;; Function pointers stored in the `spectest` table and called through it
+;; from the modules importing it. The table has 10 elements and a maximum
+;; of 20, which the cases at the end depend on.
+
+(module $a
+  (import "spectest" "table" (table 10 20 funcref))
+  (type $i32 (func (result i32)))
+  (func $one (result i32) (i32.const 1))
+  (func $two (result i32) (i32.const 2))
+  (func $add (param i32 i32) (result i32) (i32.add (local.get 0) (local.get 1)))
+  (elem (i32.const 0) $one $two)
+  (elem (i32.const 9) $add)
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (type $i32) (local.get 0))
+  )
+  (func (export "add") (param i32 i32) (result i32)
+    (call_indirect (param i32 i32) (result i32)
+      (local.get 0) (local.get 1) (i32.const 9)
+    )
+  )
+)
+
+(assert_return (invoke $a "call" (i32.const 0)) (i32.const 1))
+(assert_return (invoke $a "call" (i32.const 1)) (i32.const 2))
+(assert_return (invoke $a "add" (i32.const 2) (i32.const 3)) (i32.const 5))
+(assert_trap (invoke $a "call" (i32.const 2)) "uninitialized element")
+(assert_trap (invoke $a "call" (i32.const 9)) "indirect call type mismatch")
+(assert_trap (invoke $a "call" (i32.const 10)) "undefined element")
+
+;; A second module sees the functions of the first one and replaces one of
+;; them, which the first module sees in turn since the table is shared.
+(module $b
+  (import "spectest" "table" (table 10 funcref))
+  (type $i32 (func (result i32)))
+  (func $three (result i32) (i32.const 3))
+  (elem (i32.const 1) $three)
+  (func (export "call") (param i32) (result i32)
+    (call_indirect (type $i32) (local.get 0))
+  )
+)
+
+(assert_return (invoke $b "call" (i32.const 0)) (i32.const 1))
+(assert_return (invoke $b "call" (i32.const 1)) (i32.const 3))
+(assert_return (invoke $a "call" (i32.const 1)) (i32.const 3))
+(assert_trap (invoke $b "call" (i32.const 9)) "indirect call type mismatch")
+
+;; The bounds of the table: the last element is at index 9, and it can't be
+;; imported with a larger minimum or a smaller maximum.
+(assert_unlinkable
+  (module
+    (import "spectest" "table" (table 10 funcref))
+    (func $f)
+    (elem (i32.const 10) $f)
+  )
+  "elements segment does not fit"
+)
+(assert_unlinkable
+  (module (import "spectest" "table" (table 11 funcref)))
+  "incompatible import type"
+)
+(assert_unlinkable
+  (module (import "spectest" "table" (table 10 15 funcref)))
+  "incompatible import type"
+)
+(assert_return (invoke $a "call" (i32.const 0)) (i32.const 1))
@@ -0,0 +1,44 @@
+;; We assert that the start function runs exactly once, during
+;; instantiation, and that its writes to globals and memory are visible
+;; afterwards.
+
+(module
+  (memory 1)
+  (global $runs (mut i32) (i32.const 0))
+  (func $start
+    (global.set $runs (i32.add (global.get $runs) (i32.const 1)))
+    (i32.store (i32.const 8) (i32.const 0xdeadbeef)))
+  (start $start)
+  (func (export "runs") (result i32) (global.get $runs))
+  (func (export "load") (param i32) (result i32) (i32.load (local.get 0))))
+
+(assert_return (invoke "runs") (i32.const 1))
+(assert_return (invoke "load" (i32.const 8)) (i32.const 0xdeadbeef))
+(assert_return (invoke "load" (i32.const 0)) (i32.const 0))
+(assert_return (invoke "runs") (i32.const 1))
+
+;; A start function that traps fails the instantiation.
+(assert_trap
+  (module (func $start unreachable) (start $start))
+  "unreachable")
+
+;; Without a start function, instantiating runs nothing, not even a
+;; function exported as `_start` or one that would trap.
+(module
+  (memory 1)
+  (global $runs (mut i32) (i32.const 0))
+  (func $count
+    (global.set $runs (i32.add (global.get $runs) (i32.const 1)))
+    (i32.store (i32.const 8) (i32.const 0xdeadbeef)))
+  (func $trap unreachable)
+  (export "_start" (func $count))
+  (export "trap" (func $trap))
+  (func (export "runs") (result i32) (global.get $runs))
+  (func (export "load") (param i32) (result i32) (i32.load (local.get 0))))
+
+(assert_return (invoke "runs") (i32.const 0))
+(assert_return (invoke "load" (i32.const 8)) (i32.const 0))
+(invoke "_start")
+(assert_return (invoke "runs") (i32.const 1))
+(assert_return (invoke "load" (i32.const 8)) (i32.const 0xdeadbeef))
+(assert_trap (invoke "trap") "unreachable")
@@ -0,0 +1,62 @@
+;; Validation failures, each checked against the reason the spec gives.
+
+;; Operand stack underflow, for each kind of operator.
+(assert_invalid (module (func (drop (i32.eqz (nop))))) "type mismatch")
+(assert_invalid (module (func (drop (i32.add (nop) (i32.const 0))))) "type mismatch")
+(assert_invalid (module (func (drop (i32.add (i32.const 0) (nop))))) "type mismatch")
+(assert_invalid (module (func (drop (i64.extend_i32_s (nop))))) "type mismatch")
+(assert_invalid (module (func (if (nop) (then)))) "type mismatch")
+(assert_invalid (module (func (block (br_if 0 (nop))))) "type mismatch")
+(assert_invalid (module (func (block (br_table 0 (nop))))) "type mismatch")
+(assert_invalid
+  (module (func $f (param i32)) (func (call $f (nop))))
+  "type mismatch"
+)
+(assert_invalid
+  (module
+    (type $t (func (param i32)))
+    (table 1 funcref)
+    (func (call_indirect (type $t) (nop) (i32.const 0)))
+  )
+  "type mismatch"
+)
+(assert_invalid (module (func (result i32) (return (nop)))) "type mismatch")
+(assert_invalid (module (func (local i32) (local.set 0 (nop)))) "type mismatch")
+(assert_invalid (module (memory 1) (func (drop (i32.load (nop))))) "type mismatch")
+(assert_invalid (module (memory 1) (func (i32.store (nop) (i32.const 1)))) "type mismatch")
+(assert_invalid (module (memory 1) (func (i32.store (i32.const 0) (nop)))) "type mismatch")
+(assert_invalid (module (memory 1) (func (drop (memory.grow (nop))))) "type mismatch")
+
+;; Operands of the wrong type.
+(assert_invalid (module (func (drop (i32.eqz (i64.const 0))))) "type mismatch")
+(assert_invalid (module (func (drop (f32.add (f32.const 0) (f64.const 0))))) "type mismatch")
+(assert_invalid (module (func (if (f32.const 0) (then)))) "type mismatch")
+(assert_invalid (module (func (local i64) (local.set 0 (i32.const 0)))) "type mismatch")
+(assert_invalid (module (memory 1) (func (drop (i32.load (i64.const 0))))) "type mismatch")
+
+;; Results that don't match the function or block type.
+(assert_invalid (module (func (result i32) (i64.const 0))) "type mismatch")
+(assert_invalid (module (func (result i32))) "type mismatch")
+(assert_invalid (module (func (i32.const 0))) "type mismatch")
+(assert_invalid (module (func (if (i32.const 1) (then (i32.const 0))))) "type mismatch")
+(assert_invalid
+  (module (func (result i32) (block (result i32) (br 0 (f32.const 0)))))
+  "type mismatch"
+)
+
+;; References to things that don't exist.
+(assert_invalid (module (func (drop (local.get 0)))) "unknown local")
+(assert_invalid (module (func (call 1))) "unknown function")
+(assert_invalid (module (func (drop (global.get 0)))) "unknown global")
+(assert_invalid (module (func (br 1))) "unknown label")
+(assert_invalid (module (func (drop (i32.load (i32.const 0))))) "unknown memory")
+
+;; Other validation rules.
+(assert_invalid
+  (module (global i32 (i32.const 0)) (func (global.set 0 (i32.const 1))))
+  "global is immutable"
+)
+(assert_invalid
+  (module (memory 1) (func (drop (i32.load align=8 (i32.const 0)))))
+  "alignment must not be larger than natural"
+)
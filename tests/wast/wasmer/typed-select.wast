@@ -0,0 +1,41 @@
+;; We assert that the typed `select (result t)` form, introduced with
+;; reference types, behaves like the untyped one for every value type.
+
+(module
+  (func (export "select-i32") (param i32 i32 i32) (result i32)
+    (select (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "typed-select-i32") (param i32 i32 i32) (result i32)
+    (select (result i32) (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "typed-select-i64") (param i64 i64 i32) (result i64)
+    (select (result i64) (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "typed-select-f32") (param f32 f32 i32) (result f32)
+    (select (result f32) (local.get 0) (local.get 1) (local.get 2)))
+  (func (export "typed-select-f64") (param f64 f64 i32) (result f64)
+    (select (result f64) (local.get 0) (local.get 1) (local.get 2))))
+
+(assert_return (invoke "select-i32" (i32.const 1) (i32.const 2) (i32.const 1)) (i32.const 1))
+(assert_return (invoke "select-i32" (i32.const 1) (i32.const 2) (i32.const 0)) (i32.const 2))
+
+(assert_return (invoke "typed-select-i32" (i32.const 1) (i32.const 2) (i32.const 1)) (i32.const 1))
+(assert_return (invoke "typed-select-i32" (i32.const 1) (i32.const 2) (i32.const 0)) (i32.const 2))
+(assert_return (invoke "typed-select-i32" (i32.const 1) (i32.const 2) (i32.const -1)) (i32.const 1))
+
+(assert_return (invoke "typed-select-i64" (i64.const 1) (i64.const 2) (i32.const 1)) (i64.const 1))
+(assert_return (invoke "typed-select-i64" (i64.const 1) (i64.const 2) (i32.const 0)) (i64.const 2))
+
+(assert_return (invoke "typed-select-f32" (f32.const 1) (f32.const 2) (i32.const 1)) (f32.const 1))
+(assert_return (invoke "typed-select-f32" (f32.const 1) (f32.const 2) (i32.const 0)) (f32.const 2))
+(assert_return (invoke "typed-select-f32" (f32.const nan) (f32.const 2) (i32.const 1)) (f32.const nan))
+
+(assert_return (invoke "typed-select-f64" (f64.const 1) (f64.const 2) (i32.const 1)) (f64.const 1))
+(assert_return (invoke "typed-select-f64" (f64.const 1) (f64.const 2) (i32.const 0)) (f64.const 2))
+(assert_return (invoke "typed-select-f64" (f64.const 1) (f64.const -nan) (i32.const 0)) (f64.const -nan))
+
+;; Only the typed form can select reference types.
+(module
+  (func (param externref externref i32) (result externref)
+    (select (result externref) (local.get 0) (local.get 1) (local.get 2))))
+(assert_invalid
+  (module (func (param externref externref i32) (result externref)
+    (select (local.get 0) (local.get 1) (local.get 2))))
+  "type mismatch")
@@ -0,0 +1,68 @@
+;; We assert that loads and stores at addresses which don't match their
+;; alignment hint work and never trap for alignment, as the hint is only a
+;; promise that the address is probably aligned, whatever the width of the
+;; access and right up to the end of the memory.
+
+(module
+  (memory 1)
+
+  (func (export "i64_store_load") (param $addr i32) (param $value i64) (result i64)
+    (i64.store align=8 (local.get $addr) (local.get $value))
+    (i64.load align=8 (local.get $addr)))
+
+  ;; Stored with the largest hint and loaded back a byte, a halfword and
+  ;; a word at a time, with the largest hints too.
+  (func (export "i64_store_narrow_loads") (param $addr i32) (result i64)
+    (i64.store align=8 (local.get $addr) (i64.const 0x0807060504030201))
+    (i64.add
+      (i64.add
+        (i64.load8_u align=1 (i32.add (local.get $addr) (i32.const 7)))
+        (i64.load16_u align=2 (i32.add (local.get $addr) (i32.const 1))))
+      (i64.load32_u align=4 (i32.add (local.get $addr) (i32.const 3)))))
+
+  ;; Stored a byte at a time and loaded with the largest hint.
+  (func (export "bytes_then_i32_load") (param $addr i32) (result i32)
+    (i32.store8 (local.get $addr) (i32.const 0x11))
+    (i32.store8 (i32.add (local.get $addr) (i32.const 1)) (i32.const 0x22))
+    (i32.store8 (i32.add (local.get $addr) (i32.const 2)) (i32.const 0x33))
+    (i32.store8 (i32.add (local.get $addr) (i32.const 3)) (i32.const 0x84))
+    (i32.load align=4 (local.get $addr)))
+
+  (func (export "i32_load16_s") (param $addr i32) (result i32)
+    (i32.load16_s align=2 (local.get $addr)))
+
+  ;; Floats keep their bits, NaN payloads included.
+  (func (export "f32_store_load_bits") (param $addr i32) (param $bits i32) (result i32)
+    (f32.store align=4 (local.get $addr) (f32.reinterpret_i32 (local.get $bits)))
+    (i32.reinterpret_f32 (f32.load align=4 (local.get $addr))))
+  (func (export "f64_store_load_bits") (param $addr i32) (param $bits i64) (result i64)
+    (f64.store align=8 (local.get $addr) (f64.reinterpret_i64 (local.get $bits)))
+    (i64.reinterpret_f64 (f64.load align=8 (local.get $addr))))
+
+  ;; The offset makes the effective address unaligned too.
+  (func (export "i32_offset_store_load") (param $addr i32) (param $value i32) (result i32)
+    (i32.store offset=3 align=4 (local.get $addr) (local.get $value))
+    (i32.load offset=3 align=4 (local.get $addr))))
+
+(assert_return (invoke "i64_store_load" (i32.const 1) (i64.const 0x0123456789abcdef)) (i64.const 0x0123456789abcdef))
+(assert_return (invoke "i64_store_load" (i32.const 3) (i64.const -2)) (i64.const -2))
+(assert_return (invoke "i64_store_load" (i32.const 7) (i64.const 0x8000000000000001)) (i64.const 0x8000000000000001))
+(assert_return (invoke "i64_store_load" (i32.const 65527) (i64.const 0x1122334455667788)) (i64.const 0x1122334455667788))
+(assert_trap (invoke "i64_store_load" (i32.const 65529) (i64.const 0)) "out of bounds memory access")
+
+;; 0x08 + 0x0302 + 0x07060504
+(assert_return (invoke "i64_store_narrow_loads" (i32.const 1)) (i64.const 0x0706080e))
+(assert_return (invoke "i64_store_narrow_loads" (i32.const 65527)) (i64.const 0x0706080e))
+
+(assert_return (invoke "bytes_then_i32_load" (i32.const 5)) (i32.const 0x84332211))
+(assert_return (invoke "i32_load16_s" (i32.const 7)) (i32.const 0xffff8433))
+
+(assert_return (invoke "f32_store_load_bits" (i32.const 9) (i32.const 0x7fa00001)) (i32.const 0x7fa00001))
+(assert_return (invoke "f32_store_load_bits" (i32.const 65531) (i32.const 0x80000001)) (i32.const 0x80000001))
+(assert_return (invoke "f64_store_load_bits" (i32.const 13) (i64.const 0x7ff4000000000001)) (i64.const 0x7ff4000000000001))
+(assert_return (invoke "f64_store_load_bits" (i32.const 65527) (i64.const 0x8000000000000001)) (i64.const 0x8000000000000001))
+(assert_trap (invoke "f64_store_load_bits" (i32.const 65532) (i64.const 0)) "out of bounds memory access")
+
+(assert_return (invoke "i32_offset_store_load" (i32.const 0) (i32.const 0x12345678)) (i32.const 0x12345678))
+(assert_return (invoke "i32_offset_store_load" (i32.const 65529) (i32.const -1)) (i32.const -1))
+(assert_trap (invoke "i32_offset_store_load" (i32.const 65530) (i32.const 0)) "out of bounds memory access")
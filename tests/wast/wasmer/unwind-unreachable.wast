@@ -0,0 +1,29 @@
+;; An `unreachable` reached several frames deep traps as `unreachable`, and
+;; the instance keeps working, with the side effects made before the trap,
+;; for the directives after it.
+(module
+  (global $calls (mut i32) (i32.const 0))
+  (memory 1)
+  (func $leaf (param i32) (result i32)
+    (global.set $calls (i32.add (global.get $calls) (i32.const 1)))
+    (i32.store (i32.const 0) (local.get 0))
+    (if (result i32) (local.get 0)
+      (then (i32.const 42))
+      (else unreachable)))
+  (func $middle (param i32) (result i32)
+    (i32.add (i32.const 1) (call $leaf (local.get 0))))
+  (func (export "outer") (param i32) (result i32)
+    (block (result i32)
+      (loop (result i32)
+        (call $middle (local.get 0)))))
+  (func (export "calls") (result i32) (global.get $calls))
+  (func (export "stored") (result i32) (i32.load (i32.const 0))))
+
+(assert_return (invoke "outer" (i32.const 1)) (i32.const 43))
+(assert_trap (invoke "outer" (i32.const 0)) "unreachable")
+(assert_return (invoke "calls") (i32.const 2))
+(assert_return (invoke "stored") (i32.const 0))
+(assert_trap (invoke "outer" (i32.const 0)) "unreachable")
+(assert_return (invoke "outer" (i32.const 7)) (i32.const 43))
+(assert_return (invoke "calls") (i32.const 4))
+(assert_return (invoke "stored") (i32.const 7))
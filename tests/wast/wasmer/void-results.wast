@@ -0,0 +1,53 @@
+;; Functions returning nothing after `nop`, `drop` and blocks which leave
+;; nothing on the stack, asserted to return no results, and their side
+;; effects checked afterwards so the calls can't have been skipped.
+
+(module
+  (global $calls (mut i32) (i32.const 0))
+  (func $count (global.set $calls (i32.add (global.get $calls) (i32.const 1))))
+
+  (func (export "nops") (nop) (call $count) (nop) (nop))
+  (func (export "drops")
+    (drop (i32.const 1))
+    (drop (i64.const 2))
+    (drop (f32.const 3))
+    (drop (f64.const 4))
+    (call $count)
+  )
+  (func (export "block-drop")
+    (block (result i32) (call $count) (i32.const 5))
+    (drop)
+  )
+  (func (export "br-drops") (param i32)
+    (block $out
+      (drop (block $inner (result i32)
+        (br_if $inner (i32.const 6) (local.get 0))
+        (call $count)
+        (br $out)
+      ))
+      (call $count)
+    )
+  )
+  (func (export "loop-void")
+    (local $i i32)
+    (loop $again
+      (call $count)
+      (local.set $i (i32.add (local.get $i) (i32.const 1)))
+      (br_if $again (i32.lt_u (local.get $i) (i32.const 3)))
+    )
+  )
+  (func (export "calls") (result i32) (global.get $calls))
+)
+
+(assert_return (invoke "nops"))
+(assert_return (invoke "calls") (i32.const 1))
+(assert_return (invoke "drops"))
+(assert_return (invoke "calls") (i32.const 2))
+(assert_return (invoke "block-drop"))
+(assert_return (invoke "calls") (i32.const 3))
+(assert_return (invoke "br-drops" (i32.const 0)))
+(assert_return (invoke "calls") (i32.const 4))
+(assert_return (invoke "br-drops" (i32.const 1)))
+(assert_return (invoke "calls") (i32.const 5))
+(assert_return (invoke "loop-void"))
+(assert_return (invoke "calls") (i32.const 8))